@@ -3,9 +3,24 @@
 // Use of this source code is governed by a BSD-style
 // license that can be found in the LICENSE file.
 
+use std::error;
+use std::fmt::{self, Display, Formatter};
 use std::rc::Rc;
 
-use crate::{Inst, Sub, VM};
+use crate::{GaveUp, Inst, Sub, Unit, VM};
+
+/// The default maximum number of bytes used by the visited set of the
+/// backtracker.
+pub const DEFAULT_VISITED_CAPACITY: usize = 256 << 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BacktrackError {
+    /// The visited set for the span would exceed its capacity. The limit is
+    /// the longest span, in bytes, that the program can be run on.
+    HaystackTooLarge { len: usize, limit: usize },
+    /// The search exhausted its budget.
+    GaveUp(GaveUp),
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct Thread {
@@ -14,46 +29,93 @@ struct Thread {
     sub: Rc<Sub>,
 }
 
-const MAX_THREADS: usize = 1000;
-
 impl VM<'_, '_> {
-    pub fn match_backtrack(&mut self, sub_out: &mut Sub) -> bool {
-        let mut ready = Vec::with_capacity(MAX_THREADS);
-        ready.push(Thread {
-            pc: 0,
+    /// Backtracks with a visited set of the default capacity. See
+    /// [`VM::match_backtrack_with_capacity`].
+    #[inline]
+    pub fn match_backtrack(&mut self, sub_out: &mut Sub) -> Result<bool, BacktrackError> {
+        self.match_backtrack_with_capacity(sub_out, DEFAULT_VISITED_CAPACITY)
+    }
+
+    /// Backtracks until a match is found or every thread fails.
+    ///
+    /// Each (pc, offset) pair is executed at most once, since a thread that
+    /// reaches a pair already visited by a higher-priority thread would fail
+    /// the same way. This bounds the time by the number of instructions times
+    /// the length of the span, and the visited set takes one bit per pair. When
+    /// it would use more than `capacity` bytes, nothing is searched and an
+    /// error is returned.
+    pub fn match_backtrack_with_capacity(
+        &mut self,
+        sub_out: &mut Sub,
+        capacity: usize,
+    ) -> Result<bool, BacktrackError> {
+        let len = self.end - self.start;
+        if Visited::size(self.insts.len(), len) > capacity {
+            return Err(BacktrackError::HaystackTooLarge {
+                len,
+                limit: (capacity.saturating_mul(8) / self.insts.len().max(1)).saturating_sub(1),
+            });
+        }
+        let mut meter = self.budget.meter();
+        let mut visited = Visited::new(self.insts.len(), self.start, self.end);
+        let mut ready = vec![Thread {
+            pc: self.pc,
             offset: self.offset,
             sub: Rc::new(Sub::new(sub_out.len())),
-        });
+        }];
 
         while let Some(thread) = ready.pop() {
             self.pc = thread.pc;
             self.offset = thread.offset;
             let mut sub = thread.sub;
             loop {
+                meter.step()?;
+                let (pc, offset) = (self.pc, self.offset);
                 let inst = match self.next_inst() {
                     Some(inst) => inst,
-                    None => return false,
+                    None => return Ok(false),
                 };
+                if !visited.insert(pc, offset) {
+                    break;
+                }
                 match *inst {
                     Inst::Char(ch) => {
-                        if self.next_char() != Some(ch) {
+                        if self.next_unit() != Some(Unit::Char(ch)) {
                             break;
                         }
                     }
                     Inst::Any => {
-                        if self.next_char().is_none() {
+                        if !matches!(self.next_unit(), Some(Unit::Char(_))) {
+                            break;
+                        }
+                    }
+                    Inst::AnyNotNL => {
+                        if !matches!(self.next_unit(), Some(Unit::Char(ch)) if ch != '\n') {
+                            break;
+                        }
+                    }
+                    Inst::Class(ref class) => {
+                        if !self.next_unit().is_some_and(|unit| class.matches(unit)) {
+                            break;
+                        }
+                    }
+                    Inst::ByteRange(lo, hi) => {
+                        if !matches!(self.next_unit(), Some(Unit::Byte(b)) if lo <= b && b <= hi) {
+                            break;
+                        }
+                    }
+                    Inst::Assert(assertion) => {
+                        if !self.check(assertion) {
                             break;
                         }
                     }
                     Inst::Match => {
                         (*sub).clone_into(sub_out);
-                        return true;
+                        return Ok(true);
                     }
                     Inst::Jmp(x) => self.pc = x,
                     Inst::Split(x, y) => {
-                        if ready.len() >= MAX_THREADS {
-                            panic!("backtrack overflow");
-                        }
                         ready.push(Thread {
                             pc: y,
                             offset: self.offset,
@@ -67,6 +129,65 @@ impl VM<'_, '_> {
                 }
             }
         }
-        false
+        Ok(false)
+    }
+}
+
+// A set of (pc, offset) pairs in a span, with one bit for each.
+#[derive(Clone, Debug)]
+pub(crate) struct Visited {
+    bits: Vec<u64>,
+    stride: usize,
+    start: usize,
+}
+
+impl Visited {
+    #[inline]
+    pub(crate) fn new(ninsts: usize, start: usize, end: usize) -> Self {
+        let stride = end - start + 1;
+        Visited {
+            bits: vec![0; (ninsts * stride).div_ceil(64)],
+            stride,
+            start,
+        }
+    }
+
+    // Returns the number of bytes of the visited set for a span of len bytes.
+    #[inline]
+    pub(crate) fn size(ninsts: usize, len: usize) -> usize {
+        ninsts.saturating_mul(len + 1).div_ceil(8)
+    }
+
+    // Marks the pair as visited and reports whether it was not already.
+    #[inline]
+    pub(crate) fn insert(&mut self, pc: usize, offset: usize) -> bool {
+        let i = pc * self.stride + (offset - self.start);
+        let (word, bit) = (i / 64, 1 << (i % 64));
+        let inserted = self.bits[word] & bit == 0;
+        self.bits[word] |= bit;
+        inserted
+    }
+}
+
+impl From<GaveUp> for BacktrackError {
+    #[inline]
+    fn from(err: GaveUp) -> Self {
+        BacktrackError::GaveUp(err)
+    }
+}
+
+impl error::Error for BacktrackError {}
+
+impl Display for BacktrackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BacktrackError::HaystackTooLarge { len, limit } => {
+                write!(
+                    f,
+                    "span of {len} bytes exceeds backtracking limit of {limit} bytes"
+                )
+            }
+            BacktrackError::GaveUp(err) => write!(f, "{err}"),
+        }
     }
 }
@@ -0,0 +1,169 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A cap on the work done by a search, which is set on an
+/// [`Input`](crate::Input) for the engines or on a [`VM`](crate::VM) for its
+/// match methods.
+///
+/// A search gives up when it executes more steps than the step limit, when
+/// the deadline passes, or when the cancellation flag is set. A step is one
+/// instruction for the backtracking matchers, one thread at one position for
+/// the NFA simulations, and one unit of the haystack for the DFAs and the
+/// one-pass engine. An engine that runs several matchers counts the steps of
+/// each against the step limit separately. The deadline and the flag are
+/// checked periodically, so a search may run for a few more steps after
+/// either. By default, there is no limit.
+#[derive(Clone, Debug, Default)]
+pub struct Budget {
+    step_limit: Option<usize>,
+    deadline: Option<Instant>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+/// The reason a search gave up before deciding whether there is a match.
+///
+/// Besides exhausting the budget of the input, a recursive engine (see
+/// [`Recursive`](crate::Recursive)) gives up when it exceeds its limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GaveUp {
+    /// The search executed more steps than the step limit.
+    StepLimit { limit: usize },
+    /// The deadline passed.
+    Deadline,
+    /// The cancellation flag was set.
+    Cancelled,
+    /// A recursive engine recursed deeper than its depth limit.
+    DepthLimit { limit: usize },
+    /// A recursive engine had more pending jobs on its explicit stack than
+    /// its stack limit.
+    StackLimit { limit: usize },
+    /// The memo of a recursive engine for the span would exceed its capacity.
+    /// The limit is the longest span, in bytes, that the program can be run
+    /// on.
+    HaystackTooLarge { len: usize, limit: usize },
+}
+
+impl Budget {
+    #[inline]
+    pub fn new() -> Self {
+        Budget::default()
+    }
+
+    #[inline]
+    pub fn step_limit(&mut self, limit: usize) -> &mut Self {
+        self.step_limit = Some(limit);
+        self
+    }
+
+    #[inline]
+    pub fn deadline(&mut self, deadline: Instant) -> &mut Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets a flag that cancels the search when it is set, for example, by
+    /// another thread.
+    #[inline]
+    pub fn cancel_flag(&mut self, flag: Arc<AtomicBool>) -> &mut Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn meter(&self) -> Meter {
+        Meter {
+            budget: self.clone(),
+            steps: 0,
+        }
+    }
+}
+
+impl PartialEq for Budget {
+    // Flags are equal when they are the same flag.
+    fn eq(&self, other: &Self) -> bool {
+        self.step_limit == other.step_limit
+            && self.deadline == other.deadline
+            && match (&self.cancel, &other.cancel) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+    }
+}
+
+impl Eq for Budget {}
+
+// The number of steps between checks of the deadline and cancellation flag.
+const CHECK_INTERVAL: usize = 1024;
+
+// Counts the steps of a search against its budget.
+#[derive(Clone, Debug)]
+pub(crate) struct Meter {
+    budget: Budget,
+    steps: usize,
+}
+
+impl Meter {
+    // Counts a step and reports whether the search should give up. The
+    // deadline and flag are checked on the first step and periodically after.
+    #[inline]
+    pub(crate) fn step(&mut self) -> Result<(), GaveUp> {
+        self.steps += 1;
+        if let Some(limit) = self.budget.step_limit {
+            if self.steps > limit {
+                return Err(GaveUp::StepLimit { limit });
+            }
+        }
+        if self.steps % CHECK_INTERVAL == 1 {
+            self.check()?;
+        }
+        Ok(())
+    }
+
+    fn check(&self) -> Result<(), GaveUp> {
+        if self
+            .budget
+            .cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+        {
+            return Err(GaveUp::Cancelled);
+        }
+        if self
+            .budget
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(GaveUp::Deadline);
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for GaveUp {}
+
+impl Display for GaveUp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GaveUp::StepLimit { limit } => write!(f, "search exceeds step limit of {limit}"),
+            GaveUp::Deadline => write!(f, "search exceeds deadline"),
+            GaveUp::Cancelled => write!(f, "search cancelled"),
+            GaveUp::DepthLimit { limit } => {
+                write!(f, "recursion exceeds depth limit of {limit}")
+            }
+            GaveUp::StackLimit { limit } => {
+                write!(f, "backtracking stack exceeds limit of {limit} jobs")
+            }
+            GaveUp::HaystackTooLarge { len, limit } => {
+                write!(f, "span of {len} bytes exceeds memo limit of {limit} bytes")
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Regexes that search haystacks of bytes, which need not be valid UTF-8.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::regex::unwrap_search;
+use crate::{Engine, Error, GaveUp, Input, Prog, Sub};
+
+/// A compiled regular expression, which finds the leftmost-first match in a
+/// haystack of bytes.
+///
+/// The haystack is read as UTF-8 where it is valid, and each byte that is not
+/// part of a valid encoding can only be matched with `(?-u)`, such as by
+/// `(?-u:.)` or `(?-u:\xFF)` (see [`Unit`](crate::Unit)).
+#[derive(Clone, Debug)]
+pub struct Regex {
+    re: crate::Regex,
+}
+
+/// A single match of a regex in a haystack of bytes.
+pub type Match<'h> = crate::Match<'h, [u8]>;
+
+/// An iterator over all non-overlapping matches in a haystack.
+pub type Matches<'r, 'h> = crate::Matches<'r, 'h, [u8]>;
+
+/// An iterator over the capture groups of all non-overlapping matches in a
+/// haystack.
+pub type CaptureMatches<'r, 'h> = crate::CaptureMatches<'r, 'h, [u8]>;
+
+/// The capture groups of a single match of a regex in a haystack of bytes.
+/// Group 0 is the whole match.
+pub type Captures<'h> = crate::Captures<'h, [u8]>;
+
+impl Regex {
+    /// Parses and compiles a pattern with the default parser and compiler
+    /// options.
+    #[inline]
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        crate::Regex::new(pattern).map(|re| Regex { re })
+    }
+
+    /// Sets the engine used for searching, which is [`Meta`](crate::Meta) by
+    /// default.
+    #[inline]
+    pub fn with_engine<E: Engine + 'static>(self, engine: E) -> Self {
+        Regex {
+            re: self.re.with_engine(engine),
+        }
+    }
+
+    #[inline]
+    pub fn engine(&self) -> &dyn Engine {
+        self.re.engine()
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.re.as_str()
+    }
+
+    #[inline]
+    pub fn prog(&self) -> &Prog {
+        self.re.prog()
+    }
+
+    /// Returns the number of capture groups, including group 0.
+    #[inline]
+    pub fn captures_len(&self) -> usize {
+        self.re.captures_len()
+    }
+
+    /// Returns the names of the capture groups, indexed by group number.
+    #[inline]
+    pub fn capture_names(&self) -> impl Iterator<Item = Option<&str>> {
+        self.re.capture_names()
+    }
+
+    /// Reports whether the regex matches anywhere in the haystack.
+    #[inline]
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        self.re.search_sub(&Input::new(haystack), &mut Sub::new(0))
+    }
+
+    /// Returns the leftmost-first match in the haystack.
+    #[inline]
+    pub fn find<'h>(&self, haystack: &'h [u8]) -> Option<Match<'h>> {
+        self.search(&Input::new(haystack))
+    }
+
+    /// Returns the capture groups of the leftmost-first match in the haystack.
+    #[inline]
+    pub fn captures<'h>(&self, haystack: &'h [u8]) -> Option<Captures<'h>> {
+        self.search_captures(&Input::new(haystack))
+    }
+
+    /// Returns the leftmost-first match in the span of the input.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the search gives up, which can only happen when the input
+    /// has a budget.
+    #[inline]
+    pub fn search<'h>(&self, input: &Input<'h>) -> Option<Match<'h>> {
+        unwrap_search(self.try_search(input))
+    }
+
+    /// Returns the capture groups of the leftmost-first match in the span of
+    /// the input.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the search gives up, which can only happen when the input
+    /// has a budget.
+    #[inline]
+    pub fn search_captures<'h>(&self, input: &Input<'h>) -> Option<Captures<'h>> {
+        unwrap_search(self.try_search_captures(input))
+    }
+
+    /// Returns the leftmost-first match in the span of the input, or gives up
+    /// when the budget of the input is exhausted.
+    #[inline]
+    pub fn try_search<'h>(&self, input: &Input<'h>) -> Result<Option<Match<'h>>, GaveUp> {
+        self.re.try_search_in(input.haystack(), input)
+    }
+
+    /// Returns the capture groups of the leftmost-first match in the span of
+    /// the input, or gives up when the budget of the input is exhausted.
+    #[inline]
+    pub fn try_search_captures<'h>(
+        &self,
+        input: &Input<'h>,
+    ) -> Result<Option<Captures<'h>>, GaveUp> {
+        self.re.try_search_captures_in(input.haystack(), input)
+    }
+
+    /// Iterates over all non-overlapping matches in the haystack.
+    #[inline]
+    pub fn find_iter<'r, 'h>(&'r self, haystack: &'h [u8]) -> Matches<'r, 'h> {
+        Matches::new(&self.re, haystack)
+    }
+
+    /// Iterates over the capture groups of all non-overlapping matches in the
+    /// haystack.
+    #[inline]
+    pub fn captures_iter<'r, 'h>(&'r self, haystack: &'h [u8]) -> CaptureMatches<'r, 'h> {
+        CaptureMatches::new(&self.re, haystack)
+    }
+}
+
+impl Display for Regex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.re.fmt(f)
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::{unicode, Unit};
+
+/// A set of chars, stored as sorted, non-overlapping, non-adjacent inclusive
+/// ranges.
+///
+/// With `(?-u)`, a class may also contain bytes from 0x80 to 0xFF, which match
+/// bytes of a haystack that are not valid UTF-8 (see [`Unit`]).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Class {
+    ranges: Vec<(char, char)>,
+    // The bytes 0x80 to 0xFF in the class, with bit i for byte 0x80 + i.
+    bytes: u128,
+}
+
+impl Class {
+    #[inline]
+    pub fn new() -> Self {
+        Class {
+            ranges: Vec::new(),
+            bytes: 0,
+        }
+    }
+
+    pub fn from_ranges<I: IntoIterator<Item = (char, char)>>(ranges: I) -> Self {
+        let mut class = Class {
+            ranges: ranges.into_iter().collect(),
+            bytes: 0,
+        };
+        class.canonicalize();
+        class
+    }
+
+    /// Returns the class for a Perl shorthand. When unicode is false, the ASCII
+    /// definition is used.
+    pub fn perl(kind: Perl, unicode: bool) -> Self {
+        match (kind, unicode) {
+            (Perl::Digit, false) => Class::from_ranges([('0', '9')]),
+            (Perl::Word, false) => {
+                Class::from_ranges([('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')])
+            }
+            (Perl::Space, false) => Class::from_ranges([('\t', '\r'), (' ', ' ')]),
+            (Perl::Digit, true) => unicode::decimal_number().clone(),
+            (Perl::Word, true) => unicode::word().clone(),
+            (Perl::Space, true) => unicode::white_space().clone(),
+        }
+    }
+
+    #[inline]
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.ranges
+    }
+
+    /// Returns the ranges of bytes in the class.
+    pub fn byte_ranges(&self) -> Vec<(u8, u8)> {
+        let mut ranges = Vec::new();
+        let mut bytes = self.bytes;
+        while bytes != 0 {
+            let lo = bytes.trailing_zeros();
+            let hi = lo + (bytes >> lo).trailing_ones() - 1;
+            ranges.push((0x80 + lo as u8, 0x80 + hi as u8));
+            bytes &= !(u128::MAX >> (127 - hi));
+        }
+        ranges
+    }
+
+    /// Reports whether the unit of a haystack is in the class.
+    #[inline]
+    pub fn matches(&self, unit: Unit) -> bool {
+        match unit {
+            Unit::Char(ch) => self.contains(ch),
+            Unit::Byte(b) => self.contains_byte(b),
+        }
+    }
+
+    #[inline]
+    pub fn contains_byte(&self, b: u8) -> bool {
+        b >= 0x80 && self.bytes & 1 << (b - 0x80) != 0
+    }
+
+    pub fn contains(&self, ch: char) -> bool {
+        self.ranges
+            .binary_search_by(|&(lo, hi)| {
+                if hi < ch {
+                    std::cmp::Ordering::Less
+                } else if lo > ch {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    pub fn push(&mut self, lo: char, hi: char) {
+        debug_assert!(lo <= hi);
+        self.ranges.push((lo, hi));
+        self.canonicalize();
+    }
+
+    /// Adds the bytes from lo to hi, which must be at least 0x80.
+    pub fn push_bytes(&mut self, lo: u8, hi: u8) {
+        assert!(0x80 <= lo && lo <= hi);
+        self.bytes |= (u128::MAX >> (127 - (hi - 0x80))) & (u128::MAX << (lo - 0x80));
+    }
+
+    pub fn union(&mut self, other: &Class) {
+        self.ranges.extend_from_slice(&other.ranges);
+        self.bytes |= other.bytes;
+        self.canonicalize();
+    }
+
+    /// Adds all chars that are equivalent to a char in the class under simple
+    /// case folding. When unicode is false, only ASCII letters are folded.
+    pub fn case_fold(&mut self, unicode: bool) {
+        let mut added = Vec::new();
+        if unicode {
+            for &(ch, orbit) in unicode::case_orbits() {
+                if self.contains(ch) {
+                    added.extend(orbit.iter().map(|&ch| (ch, ch)));
+                }
+            }
+        } else {
+            for ch in ('A'..='Z').chain('a'..='z') {
+                if self.contains(ch) {
+                    let folded = (ch as u8 ^ 0x20) as char;
+                    added.push((folded, folded));
+                }
+            }
+        }
+        self.ranges.extend(added);
+        self.canonicalize();
+    }
+
+    /// Negates the chars in the class, leaving the bytes as they are.
+    pub fn negate(&mut self) {
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 1);
+        let mut next = Some('\0');
+        for &(lo, hi) in &self.ranges {
+            if let Some(next) = next {
+                if next < lo {
+                    ranges.push((next, char_pred(lo)));
+                }
+            }
+            next = char_succ(hi);
+        }
+        if let Some(next) = next {
+            ranges.push((next, char::MAX));
+        }
+        self.ranges = ranges;
+    }
+
+    /// Negates the bytes in the class, leaving the chars as they are.
+    #[inline]
+    pub fn negate_bytes(&mut self) {
+        self.bytes = !self.bytes;
+    }
+
+    // Sorts the ranges and merges those that overlap or are adjacent.
+    fn canonicalize(&mut self) {
+        self.ranges.sort_unstable();
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(self.ranges.len());
+        for &(lo, hi) in &self.ranges {
+            if let Some(last) = merged.last_mut() {
+                if char_succ(last.1).is_none_or(|next| lo <= next) {
+                    last.1 = last.1.max(hi);
+                    continue;
+                }
+            }
+            merged.push((lo, hi));
+        }
+        self.ranges = merged;
+    }
+}
+
+/// A Perl shorthand class.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Perl {
+    /// `\d`
+    Digit,
+    /// `\w`
+    Word,
+    /// `\s`
+    Space,
+}
+
+// Returns the next char after ch, skipping the surrogate range.
+fn char_succ(ch: char) -> Option<char> {
+    match ch {
+        '\u{D7FF}' => Some('\u{E000}'),
+        char::MAX => None,
+        _ => char::from_u32(ch as u32 + 1),
+    }
+}
+
+// Returns the char before ch, skipping the surrogate range. ch must not be
+// '\0'.
+fn char_pred(ch: char) -> char {
+    match ch {
+        '\u{E000}' => '\u{D7FF}',
+        _ => char::from_u32(ch as u32 - 1).unwrap(),
+    }
+}
+
+impl Class {
+    // Writes the ranges of the class in bracket syntax, without the brackets.
+    pub(crate) fn write_ranges<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        fn write_char<W: fmt::Write>(w: &mut W, ch: char) -> fmt::Result {
+            match ch {
+                '\\' | ']' | '[' | '-' | '^' => write!(w, "\\{ch}"),
+                '\n' => write!(w, "\\n"),
+                '\t' => write!(w, "\\t"),
+                '\r' => write!(w, "\\r"),
+                '\0' => write!(w, "\\0"),
+                _ if ch.is_control() => write!(w, "\\u{{{:X}}}", ch as u32),
+                _ => write!(w, "{ch}"),
+            }
+        }
+        for &(lo, hi) in &self.ranges {
+            write_char(w, lo)?;
+            if lo != hi {
+                write!(w, "-")?;
+                write_char(w, hi)?;
+            }
+        }
+        for (lo, hi) in self.byte_ranges() {
+            write!(w, "\\x{lo:02X}")?;
+            if lo != hi {
+                write!(w, "-\\x{hi:02X}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        self.write_ranges(f)?;
+        write!(f, "]")
+    }
+}
@@ -3,37 +3,191 @@
 // Use of this source code is governed by a BSD-style
 // license that can be found in the LICENSE file.
 
-use crate::{Inst, Prog, Regexp};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
 
-impl Regexp {
-    pub fn compile(&self) -> Prog {
+use crate::{utf8, Class, Inst, Prog, Regexp};
+
+/// The default maximum number of instructions in a compiled program.
+pub const DEFAULT_SIZE_LIMIT: usize = 100_000;
+
+/// A configurable regexp compiler.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Compiler {
+    size_limit: usize,
+    utf8: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompileError {
+    /// The program would have more instructions than the size limit.
+    TooBig { size: usize, limit: usize },
+    /// A class matches raw bytes in a program compiled to UTF-8, where a byte
+    /// that is not valid UTF-8 cannot be told apart from a byte within a
+    /// valid char.
+    RawBytes,
+}
+
+impl Compiler {
+    #[inline]
+    pub fn new() -> Self {
+        Compiler {
+            size_limit: DEFAULT_SIZE_LIMIT,
+            utf8: false,
+        }
+    }
+
+    /// Sets the maximum number of instructions in a compiled program, which
+    /// bounds the expansion of counted repetitions.
+    #[inline]
+    pub fn size_limit(&mut self, limit: usize) -> &mut Self {
+        self.size_limit = limit;
+        self
+    }
+
+    /// Sets whether to compile chars to automata over their UTF-8 encodings,
+    /// with [`Inst::ByteRange`], so that the program consumes one byte at a
+    /// time. This lets the DFAs partition at most 256 bytes instead of every
+    /// char, at the cost of more instructions.
+    ///
+    /// Compiling fails with [`CompileError::RawBytes`] if a class matches raw
+    /// bytes, as with `(?-u:\xFF)`. The engines skip empty matches within a
+    /// char, but [`Dfa::is_match`](crate::Dfa::is_match) does not.
+    #[inline]
+    pub fn utf8(&mut self, yes: bool) -> &mut Self {
+        self.utf8 = yes;
+        self
+    }
+
+    pub fn compile(&self, re: &Regexp) -> Result<Prog, CompileError> {
+        // The unanchored prefix .*? and the final match. The prefix skips any
+        // unit, including bytes that are not valid UTF-8, or any byte when
+        // compiled to UTF-8.
+        if self.utf8 && re.matches_raw_bytes() {
+            return Err(CompileError::RawBytes);
+        }
+        let size = re.count_insts(self.utf8).saturating_add(4);
+        if size > self.size_limit {
+            return Err(CompileError::TooBig {
+                size,
+                limit: self.size_limit,
+            });
+        }
         let mut b = ProgBuilder {
-            insts: Vec::with_capacity(self.count_insts() + 1),
-            nsub: 0,
+            insts: Vec::with_capacity(size),
+            utf8: self.utf8,
         };
-        b.push_regexp(self);
+        if self.utf8 {
+            b.insts
+                .extend([Inst::Split(3, 1), Inst::ByteRange(0x00, 0xFF), Inst::Jmp(0)]);
+        } else {
+            let mut any = Class::new();
+            any.negate();
+            any.negate_bytes();
+            b.push_star(false, &Regexp::Class(any));
+        }
+        let start = b.push_regexp(re);
         b.insts.push(Inst::Match);
-        Prog::new(b.insts, b.nsub)
+        debug_assert_eq!(b.insts.len(), size);
+        // Groups are numbered by the parser, including those that are never
+        // emitted, like under {0}.
+        let mut names = Vec::new();
+        re.collect_names(&mut names);
+        Ok(Prog::new(b.insts, start, 2 * names.len(), names, self.utf8))
     }
+}
 
-    // Counts the number of instructions needed to compile the regexp.
-    fn count_insts(&self) -> usize {
+impl Default for Compiler {
+    #[inline]
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl Regexp {
+    #[inline]
+    pub fn compile(&self) -> Result<Prog, CompileError> {
+        Compiler::new().compile(self)
+    }
+
+    // Counts the number of instructions needed to compile the regexp,
+    // saturating on overflow.
+    fn count_insts(&self, utf8: bool) -> usize {
         match self {
-            Regexp::Alt(left, right) => 2 + left.count_insts() + right.count_insts(),
-            Regexp::Cat(left, right) => left.count_insts() + right.count_insts(),
+            Regexp::Alt(left, right) => 2usize
+                .saturating_add(left.count_insts(utf8))
+                .saturating_add(right.count_insts(utf8)),
+            Regexp::Cat(left, right) => left
+                .count_insts(utf8)
+                .saturating_add(right.count_insts(utf8)),
+            Regexp::Lit(_) | Regexp::Dot(_) | Regexp::Class(_) if utf8 => {
+                utf8_insts(&consumed_class(self)).len()
+            }
             Regexp::Lit(_) => 1,
-            Regexp::Dot => 1,
-            Regexp::Paren(_, inner) => 2 + inner.count_insts(),
-            Regexp::Quest(_, inner) => 1 + inner.count_insts(),
-            Regexp::Star(_, inner) => 2 + inner.count_insts(),
-            Regexp::Plus(_, inner) => 1 + inner.count_insts(),
+            Regexp::Dot(_) => 1,
+            Regexp::Class(_) => 1,
+            Regexp::Assert(_) => 1,
+            Regexp::Paren(_, _, inner) => 2usize.saturating_add(inner.count_insts(utf8)),
+            Regexp::Quest(_, inner) => 1usize.saturating_add(inner.count_insts(utf8)),
+            Regexp::Star(_, inner) => 2usize.saturating_add(inner.count_insts(utf8)),
+            Regexp::Plus(_, inner) => 1usize.saturating_add(inner.count_insts(utf8)),
+            Regexp::Repeat(_, min, max, inner) => {
+                let n = inner.count_insts(utf8);
+                let min = *min as usize;
+                match *max {
+                    None if min == 0 => n.saturating_add(2),
+                    None => n.saturating_mul(min).saturating_add(1),
+                    Some(max) => n
+                        .saturating_mul(min)
+                        .saturating_add(n.saturating_add(1).saturating_mul(max as usize - min)),
+                }
+            }
+        }
+    }
+
+    // Reports whether any class in the regexp matches raw bytes.
+    fn matches_raw_bytes(&self) -> bool {
+        match self {
+            Regexp::Alt(left, right) | Regexp::Cat(left, right) => {
+                left.matches_raw_bytes() || right.matches_raw_bytes()
+            }
+            Regexp::Class(class) => !class.byte_ranges().is_empty(),
+            Regexp::Lit(_) | Regexp::Dot(_) | Regexp::Assert(_) => false,
+            Regexp::Paren(_, _, inner)
+            | Regexp::Quest(_, inner)
+            | Regexp::Star(_, inner)
+            | Regexp::Plus(_, inner)
+            | Regexp::Repeat(_, _, _, inner) => inner.matches_raw_bytes(),
+        }
+    }
+
+    // Records the name of each capture group at its number, with None for
+    // unnamed groups.
+    fn collect_names(&self, names: &mut Vec<Option<String>>) {
+        match self {
+            Regexp::Alt(left, right) | Regexp::Cat(left, right) => {
+                left.collect_names(names);
+                right.collect_names(names);
+            }
+            Regexp::Lit(_) | Regexp::Dot(_) | Regexp::Class(_) | Regexp::Assert(_) => {}
+            Regexp::Paren(n, name, inner) => {
+                if names.len() <= *n {
+                    names.resize(n + 1, None);
+                }
+                names[*n] = name.clone();
+                inner.collect_names(names);
+            }
+            Regexp::Quest(_, inner)
+            | Regexp::Star(_, inner)
+            | Regexp::Plus(_, inner)
+            | Regexp::Repeat(_, _, _, inner) => inner.collect_names(names),
         }
     }
 }
 
 struct ProgBuilder {
     insts: Vec<Inst>,
-    nsub: usize,
+    utf8: bool,
 }
 
 impl ProgBuilder {
@@ -52,13 +206,18 @@ impl ProgBuilder {
                 self.push_regexp(left);
                 self.push_regexp(right);
             }
+            Regexp::Lit(_) | Regexp::Dot(_) | Regexp::Class(_) if self.utf8 => {
+                self.push_utf8(&consumed_class(r));
+            }
             Regexp::Lit(ch) => self.insts.push(Inst::Char(*ch)),
-            Regexp::Dot => self.insts.push(Inst::Any),
-            Regexp::Paren(n, inner) => {
+            Regexp::Dot(true) => self.insts.push(Inst::Any),
+            Regexp::Dot(false) => self.insts.push(Inst::AnyNotNL),
+            Regexp::Class(class) => self.insts.push(Inst::Class(class.clone())),
+            Regexp::Assert(assertion) => self.insts.push(Inst::Assert(*assertion)),
+            Regexp::Paren(n, _, inner) => {
                 self.insts.push(Inst::Save(2 * n));
                 self.push_regexp(inner);
                 self.insts.push(Inst::Save(2 * n + 1));
-                self.nsub = self.nsub.max(2 * (n + 1));
             }
             Regexp::Quest(greedy, inner) => {
                 let split = self.push_split_placeholder();
@@ -69,31 +228,222 @@ impl ProgBuilder {
                     Inst::Split(self.insts.len(), x)
                 };
             }
-            Regexp::Star(greedy, inner) => {
-                let split = self.push_split_placeholder();
-                let x = self.push_regexp(inner);
-                self.insts.push(Inst::Jmp(split));
-                self.insts[split] = if *greedy {
-                    Inst::Split(x, self.insts.len())
-                } else {
-                    Inst::Split(self.insts.len(), x)
+            Regexp::Star(greedy, inner) => self.push_star(*greedy, inner),
+            Regexp::Plus(greedy, inner) => self.push_plus(*greedy, inner),
+            Regexp::Repeat(greedy, min, max, inner) => {
+                let min = *min as usize;
+                match *max {
+                    None if min == 0 => self.push_star(*greedy, inner),
+                    None => {
+                        for _ in 1..min {
+                            self.push_regexp(inner);
+                        }
+                        self.push_plus(*greedy, inner);
+                    }
+                    Some(max) => {
+                        for _ in 0..min {
+                            self.push_regexp(inner);
+                        }
+                        // Each optional copy is skipped by a split to the end,
+                        // as in x{2,4} => xx(x(x)?)?.
+                        let mut splits = Vec::with_capacity(max as usize - min);
+                        for _ in min..max as usize {
+                            let split = self.push_split_placeholder();
+                            let x = self.push_regexp(inner);
+                            splits.push((split, x));
+                        }
+                        let end = self.insts.len();
+                        for (split, x) in splits {
+                            self.insts[split] = if *greedy {
+                                Inst::Split(x, end)
+                            } else {
+                                Inst::Split(end, x)
+                            };
+                        }
+                    }
                 }
             }
-            Regexp::Plus(greedy, inner) => {
-                let x = self.push_regexp(inner);
-                self.insts.push(if *greedy {
-                    Inst::Split(x, self.insts.len() + 1)
-                } else {
-                    Inst::Split(self.insts.len() + 1, x)
-                });
-            }
         }
         pc
     }
 
+    fn push_star(&mut self, greedy: bool, inner: &Regexp) {
+        let split = self.push_split_placeholder();
+        let x = self.push_regexp(inner);
+        self.insts.push(Inst::Jmp(split));
+        self.insts[split] = if greedy {
+            Inst::Split(x, self.insts.len())
+        } else {
+            Inst::Split(self.insts.len(), x)
+        }
+    }
+
+    fn push_plus(&mut self, greedy: bool, inner: &Regexp) {
+        let x = self.push_regexp(inner);
+        self.insts.push(if greedy {
+            Inst::Split(x, self.insts.len() + 1)
+        } else {
+            Inst::Split(self.insts.len() + 1, x)
+        });
+    }
+
     fn push_split_placeholder(&mut self) -> usize {
         let pc = self.insts.len();
         self.insts.push(Inst::Split(usize::MAX, usize::MAX));
         pc
     }
+
+    fn push_utf8(&mut self, class: &Class) {
+        let base = self.insts.len();
+        self.insts
+            .extend(utf8_insts(class).into_iter().map(|inst| match inst {
+                Inst::Jmp(x) => Inst::Jmp(base + x),
+                Inst::Split(x, y) => Inst::Split(base + x, base + y),
+                inst => inst,
+            }));
+    }
+}
+
+// Returns the class of chars consumed by a literal, dot, or class.
+fn consumed_class(re: &Regexp) -> Class {
+    match re {
+        Regexp::Lit(ch) => Class::from_ranges([(*ch, *ch)]),
+        Regexp::Dot(dot_nl) => {
+            let mut class = Class::new();
+            if !dot_nl {
+                class.push('\n', '\n');
+            }
+            class.negate();
+            class
+        }
+        Regexp::Class(class) => class.clone(),
+        _ => unreachable!("{re:?} does not consume"),
+    }
+}
+
+// Compiles a class to the minimal automaton over the UTF-8 encodings of its
+// chars. The sequences of byte ranges that encode it (the utf8-ranges
+// construction) are inserted into a trie, which shares their common prefixes,
+// then equivalent states are merged from the leaves up, which shares their
+// common suffixes. The pcs are relative to the first inst and the automaton
+// ends after the last.
+fn utf8_insts(class: &Class) -> Vec<Inst> {
+    const END: usize = usize::MAX;
+    debug_assert!(class.byte_ranges().is_empty());
+    let mut seqs = Vec::new();
+    for &(lo, hi) in class.ranges() {
+        utf8::sequences(lo, hi, &mut seqs);
+    }
+    if seqs.is_empty() {
+        // An empty range never matches.
+        return vec![Inst::ByteRange(1, 0)];
+    }
+
+    // Each node of the trie is its transitions, as a byte range and the next
+    // node. The sequences are in order of their bytes, so one that shares a
+    // prefix with an earlier sequence shares it with the last transitions
+    // that were added.
+    let mut trie: Vec<Vec<(u8, u8, usize)>> = vec![Vec::new()];
+    for seq in &seqs {
+        let mut node = 0;
+        for &(lo, hi) in seq {
+            node = match trie[node].last() {
+                Some(&(l, h, next)) if (l, h) == (lo, hi) => next,
+                _ => {
+                    trie.push(Vec::new());
+                    let next = trie.len() - 1;
+                    trie[node].push((lo, hi, next));
+                    next
+                }
+            };
+        }
+    }
+
+    // Nodes come after their parents, so visiting them in reverse merges the
+    // next states of a node before the node. The leaves all merge into END
+    // and adjacent ranges to the same state merge into one.
+    let mut states = Vec::new();
+    let mut ids = HashMap::new();
+    let mut state_of = vec![END; trie.len()];
+    for node in (0..trie.len()).rev() {
+        if trie[node].is_empty() {
+            continue;
+        }
+        let mut trans: Vec<(u8, u8, usize)> = Vec::with_capacity(trie[node].len());
+        for &(lo, hi, next) in &trie[node] {
+            let next = state_of[next];
+            match trans.last_mut() {
+                Some((_, h, n)) if *n == next && *h as usize + 1 == lo as usize => *h = hi,
+                _ => trans.push((lo, hi, next)),
+            }
+        }
+        state_of[node] = *ids.entry(trans).or_insert_with_key(|trans| {
+            states.push(trans.clone());
+            states.len() - 1
+        });
+    }
+
+    // Lay out the states depth first from the root, so that the next state of
+    // the last transition of a state tends to follow it.
+    let mut order = Vec::with_capacity(states.len());
+    let mut seen = vec![false; states.len()];
+    let mut stack = vec![state_of[0]];
+    while let Some(state) = stack.pop() {
+        if !seen[state] {
+            seen[state] = true;
+            order.push(state);
+            stack.extend(states[state].iter().map(|t| t.2).filter(|&n| n != END));
+        }
+    }
+    // Each state is a chain of splits between its transitions, and each
+    // transition a byte range and a jump to its next state, unless the last
+    // transition can fall through.
+    let falls_through = |i: usize, t: usize| {
+        let trans: &Vec<(u8, u8, usize)> = &states[order[i]];
+        t + 1 == trans.len() && trans[t].2 == order.get(i + 1).copied().unwrap_or(END)
+    };
+    let mut pcs = vec![0; states.len()];
+    let mut len = 0;
+    for (i, &state) in order.iter().enumerate() {
+        pcs[state] = len;
+        let n = states[state].len();
+        len += 2 * n - 1 + (0..n).filter(|&t| !falls_through(i, t)).count();
+    }
+    let mut insts = Vec::with_capacity(len);
+    for (i, &state) in order.iter().enumerate() {
+        let trans = &states[state];
+        let mut branches = Vec::with_capacity(trans.len());
+        let mut pc = insts.len() + trans.len() - 1;
+        for t in 0..trans.len() {
+            branches.push(pc);
+            pc += if falls_through(i, t) { 1 } else { 2 };
+        }
+        for t in 0..trans.len() - 1 {
+            let y = if t + 2 < trans.len() {
+                insts.len() + 1
+            } else {
+                branches[t + 1]
+            };
+            insts.push(Inst::Split(branches[t], y));
+        }
+        for (t, &(lo, hi, next)) in trans.iter().enumerate() {
+            insts.push(Inst::ByteRange(lo, hi));
+            if !falls_through(i, t) {
+                insts.push(Inst::Jmp(if next == END { len } else { pcs[next] }));
+            }
+        }
+    }
+    debug_assert_eq!(insts.len(), len);
+    insts
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::TooBig { size, limit } => {
+                write!(f, "program of {size} instructions exceeds limit of {limit}")
+            }
+            CompileError::RawBytes => write!(f, "raw bytes cannot be compiled to UTF-8"),
+        }
+    }
 }
@@ -0,0 +1,499 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+
+use crate::engine::search_whole_chars;
+use crate::subset::{Alphabet, Closure, NUM_CTX};
+use crate::{Anchored, Engine, GaveUp, Input, Pike, Prog, Sub};
+
+/// The default maximum number of states in a compiled DFA.
+pub const DEFAULT_STATE_LIMIT: usize = 10_000;
+
+/// A configurable compiler from programs to DFAs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DfaBuilder {
+    state_limit: usize,
+}
+
+/// A DFA compiled ahead of time from a program into a dense transition table
+/// and minimized.
+///
+/// Like the lazy DFA, it only decides whether there is a match; when
+/// submatches are requested, the Pike VM is run after the DFA finds a match.
+///
+/// A DFA can be serialized with [`Dfa::to_bytes`] and loaded with
+/// [`Dfa::from_bytes`], without the pattern or program. The format is a
+/// sequence of little-endian `u32`s:
+///
+/// - the magic number `0x31646672` and the version 3,
+/// - the mask of context bits that the assertions depend on,
+/// - 1 if the program was compiled to UTF-8 and 0 otherwise,
+/// - the number of class boundaries, followed by the boundaries, where a char
+///   is its scalar value and a byte that is a [`Unit`](crate::Unit) by itself
+///   is `0x110000` plus the byte, or where every byte is itself when compiled
+///   to UTF-8,
+/// - the number of states,
+/// - the start states, indexed by anchoring and then the context of the unit
+///   before the span,
+/// - for each state, a bitmap indexed by the context of the unit after the
+///   span, of whether the program matches at the end of the span,
+/// - for each state and then each class, the next state shifted left by one,
+///   with the low bit set when the program matches before the unit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dfa {
+    alphabet: Alphabet,
+    starts: Box<[u32]>,
+    accepts: Box<[u32]>,
+    trans: Box<[u32]>,
+    // Whether each state can never lead to a match. It is derived from the
+    // transitions and not serialized.
+    dead: Box<[bool]>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DfaError {
+    /// The DFA would have more states than the state limit.
+    TooManyStates { limit: usize },
+    /// The bytes are not a serialized DFA.
+    InvalidFormat(&'static str),
+    /// The program is compiled to UTF-8 and has a Unicode word boundary,
+    /// which depends on the chars around it and not only the bytes.
+    UnicodeWordBoundary,
+}
+
+const MAGIC: u32 = u32::from_le_bytes(*b"rfd1");
+const VERSION: u32 = 3;
+
+impl DfaBuilder {
+    #[inline]
+    pub fn new() -> Self {
+        DfaBuilder {
+            state_limit: DEFAULT_STATE_LIMIT,
+        }
+    }
+
+    /// Sets the maximum number of states, before minimization, which bounds
+    /// the exponential blowup of subset construction.
+    #[inline]
+    pub fn state_limit(&mut self, limit: usize) -> &mut Self {
+        self.state_limit = limit;
+        self
+    }
+
+    pub fn build(&self, prog: &Prog) -> Result<Dfa, DfaError> {
+        let alphabet = Alphabet::new(prog);
+        if !alphabet.ctx_is_exact() {
+            return Err(DfaError::UnicodeWordBoundary);
+        }
+        let mut closure = Closure::new(prog);
+        let nclasses = alphabet.len();
+        let mut states: Vec<(Box<[usize]>, u8)> = Vec::new();
+        let mut map = HashMap::new();
+        let mut add_state = |states: &mut Vec<(Box<[usize]>, u8)>, pcs: Box<[usize]>, ctx: u8| {
+            let key = (pcs, ctx);
+            if let Some(&id) = map.get(&key) {
+                return Ok(id);
+            }
+            if states.len() >= self.state_limit {
+                return Err(DfaError::TooManyStates {
+                    limit: self.state_limit,
+                });
+            }
+            let id = states.len() as u32;
+            states.push(key.clone());
+            map.insert(key, id);
+            Ok(id)
+        };
+
+        let mut starts = vec![0; 2 * NUM_CTX];
+        for anchored in [Anchored::Yes, Anchored::No] {
+            for ctx in contexts(&alphabet) {
+                let pcs = Box::new([prog.start(anchored)]);
+                starts[start_index(anchored, ctx)] = add_state(&mut states, pcs, ctx)?;
+            }
+        }
+
+        let mut accepts = Vec::new();
+        let mut trans = Vec::new();
+        let mut s = 0;
+        while s < states.len() {
+            let (pcs, prev) = states[s].clone();
+            let mut accept = 0;
+            for next in contexts(&alphabet) {
+                let (matched, _) = closure.step(prog.insts(), &pcs, prev, next, None);
+                accept |= (matched as u32) << next;
+            }
+            accepts.push(accept);
+            for class in 0..nclasses {
+                // A class without a representative is never looked up.
+                let Some(unit) = alphabet.representative(class) else {
+                    trans.push((s as u32) << 1);
+                    continue;
+                };
+                let next = alphabet.ctx(Some(unit));
+                let (matched, pcs) = closure.step(prog.insts(), &pcs, prev, next, Some(unit));
+                let t = add_state(&mut states, pcs.into(), next)?;
+                trans.push(t << 1 | matched as u32);
+            }
+            s += 1;
+        }
+
+        let mut dfa = Dfa {
+            alphabet,
+            starts: starts.into(),
+            accepts: accepts.into(),
+            trans: trans.into(),
+            dead: Box::new([]),
+        };
+        dfa.minimize();
+        Ok(dfa)
+    }
+}
+
+impl Default for DfaBuilder {
+    #[inline]
+    fn default() -> Self {
+        DfaBuilder::new()
+    }
+}
+
+impl Dfa {
+    /// Compiles a DFA with the default state limit.
+    #[inline]
+    pub fn new(prog: &Prog) -> Result<Self, DfaError> {
+        DfaBuilder::new().build(prog)
+    }
+
+    /// Returns the number of states.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.accepts.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.accepts.is_empty()
+    }
+
+    /// Reports whether the program matches in the span of the input.
+    ///
+    /// For a program compiled to UTF-8, this includes empty matches within a
+    /// char, which [`Engine::search`] skips.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the search gives up, which can only happen when the input
+    /// has a budget. Use [`Dfa::try_is_match`] to handle it.
+    pub fn is_match(&self, input: &Input<'_>) -> bool {
+        match self.try_is_match(input) {
+            Ok(matched) => matched,
+            Err(err) => panic!("{err}; use try_is_match to handle a budget"),
+        }
+    }
+
+    /// Reports whether the program matches in the span of the input, or gives
+    /// up when the budget of the input is exhausted.
+    pub fn try_is_match(&self, input: &Input<'_>) -> Result<bool, GaveUp> {
+        let mut meter = input.get_budget().meter();
+        let haystack = input.haystack();
+        let nclasses = self.alphabet.len();
+        let prev = self.alphabet.ctx(
+            self.alphabet
+                .decode_last(&haystack[..input.start()])
+                .map(|(unit, _)| unit),
+        );
+        let mut s = self.starts[start_index(input.get_anchored(), prev)] as usize;
+        let mut offset = input.start();
+        while let Some((unit, len)) = self.alphabet.decode(&haystack[offset..input.end()]) {
+            meter.step()?;
+            offset += len;
+            let t = self.trans[s * nclasses + self.alphabet.class(unit)];
+            if t & 1 != 0 {
+                return Ok(true);
+            }
+            s = (t >> 1) as usize;
+            if self.dead[s] {
+                return Ok(false);
+            }
+        }
+        let next = self.alphabet.ctx(
+            self.alphabet
+                .decode(&haystack[input.end()..])
+                .map(|(unit, _)| unit),
+        );
+        Ok(self.accepts[s] & 1 << next != 0)
+    }
+
+    /// Serializes the DFA in the format described in the type docs.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let boundaries = self.alphabet.boundaries();
+        let mut words = Vec::with_capacity(
+            7 + boundaries.len() + self.starts.len() + self.accepts.len() + self.trans.len(),
+        );
+        words.extend([MAGIC, VERSION, self.alphabet.ctx_mask() as u32]);
+        words.push(self.alphabet.is_utf8() as u32);
+        words.push(boundaries.len() as u32);
+        words.extend_from_slice(boundaries);
+        words.push(self.len() as u32);
+        words.extend_from_slice(&self.starts);
+        words.extend_from_slice(&self.accepts);
+        words.extend_from_slice(&self.trans);
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    /// Loads a DFA serialized by [`Dfa::to_bytes`], validating it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DfaError> {
+        if !bytes.len().is_multiple_of(4) {
+            return Err(DfaError::InvalidFormat("length is not a multiple of 4"));
+        }
+        let mut words = bytes
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes(w.try_into().unwrap()));
+        let mut next = |what| words.next().ok_or(DfaError::InvalidFormat(what));
+        if next("missing magic number")? != MAGIC {
+            return Err(DfaError::InvalidFormat("bad magic number"));
+        }
+        if next("missing version")? != VERSION {
+            return Err(DfaError::InvalidFormat("unsupported version"));
+        }
+        let ctx_mask = next("missing context mask")?;
+        let utf8 = match next("missing alphabet kind")? {
+            0 => false,
+            1 => true,
+            _ => return Err(DfaError::InvalidFormat("invalid alphabet kind")),
+        };
+        let nboundaries = next("missing boundary count")? as usize;
+        let boundaries = (0..nboundaries)
+            .map(|_| next("truncated boundaries"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let alphabet = u8::try_from(ctx_mask)
+            .ok()
+            .and_then(|ctx_mask| Alphabet::from_parts(boundaries, ctx_mask, utf8))
+            .ok_or(DfaError::InvalidFormat("invalid alphabet"))?;
+        let nstates = next("missing state count")? as usize;
+        if nstates == 0 {
+            return Err(DfaError::InvalidFormat("no states"));
+        }
+        let mut read = |n: usize, what| -> Result<Box<[u32]>, DfaError> {
+            (0..n).map(|_| next(what)).collect()
+        };
+        let starts = read(2 * NUM_CTX, "truncated start states")?;
+        let accepts = read(nstates, "truncated accepting states")?;
+        let ntrans = nstates
+            .checked_mul(alphabet.len())
+            .ok_or(DfaError::InvalidFormat("too many transitions"))?;
+        let trans = read(ntrans, "truncated transitions")?;
+        if next("").is_ok() {
+            return Err(DfaError::InvalidFormat("trailing bytes"));
+        }
+        if starts.iter().any(|&s| s as usize >= nstates)
+            || trans.iter().any(|&t| (t >> 1) as usize >= nstates)
+        {
+            return Err(DfaError::InvalidFormat("state out of bounds"));
+        }
+        if accepts.iter().any(|&a| a >> NUM_CTX != 0) {
+            return Err(DfaError::InvalidFormat("invalid accepting state"));
+        }
+        let mut dfa = Dfa {
+            alphabet,
+            starts,
+            accepts,
+            trans,
+            dead: Box::new([]),
+        };
+        dfa.dead = dfa.dead_states();
+        Ok(dfa)
+    }
+
+    // Merges equivalent states with Hopcroft's partition refinement algorithm.
+    // States are first partitioned by when they match, then blocks are split
+    // until every state in a block transitions to the same block on each
+    // class.
+    fn minimize(&mut self) {
+        let n = self.len();
+        let k = self.alphabet.len();
+        let target = |s: usize, c: usize| (self.trans[s * k + c] >> 1) as usize;
+
+        // The inverse transitions, with the sources of target t on class c at
+        // inv_src[inv_off[c * n + t]..inv_off[c * n + t + 1]].
+        let mut inv_off = vec![0usize; k * n + 1];
+        for s in 0..n {
+            for c in 0..k {
+                inv_off[c * n + target(s, c) + 1] += 1;
+            }
+        }
+        for i in 1..inv_off.len() {
+            inv_off[i] += inv_off[i - 1];
+        }
+        let mut inv_src = vec![0u32; k * n];
+        let mut fill = inv_off.clone();
+        for s in 0..n {
+            for c in 0..k {
+                let i = c * n + target(s, c);
+                inv_src[fill[i]] = s as u32;
+                fill[i] += 1;
+            }
+        }
+
+        let mut blocks: Vec<Vec<u32>> = Vec::new();
+        let mut signatures = HashMap::new();
+        let mut block_of: Vec<usize> = (0..n)
+            .map(|s| {
+                let matches = (0..k).map(|c| self.trans[s * k + c] & 1 != 0);
+                let sig = (self.accepts[s], matches.collect::<Vec<_>>());
+                let b = *signatures.entry(sig).or_insert_with(|| {
+                    blocks.push(Vec::new());
+                    blocks.len() - 1
+                });
+                blocks[b].push(s as u32);
+                b
+            })
+            .collect();
+
+        let mut worklist: Vec<usize> = (0..blocks.len()).collect();
+        let mut in_work = vec![true; blocks.len()];
+        let mut marks: Vec<Vec<u32>> = vec![Vec::new(); blocks.len()];
+        let mut in_x = vec![false; n];
+        while let Some(a) = worklist.pop() {
+            in_work[a] = false;
+            let splitter = blocks[a].clone();
+            for c in 0..k {
+                let mut touched = Vec::new();
+                for &t in &splitter {
+                    let i = c * n + t as usize;
+                    for &s in &inv_src[inv_off[i]..inv_off[i + 1]] {
+                        let b = block_of[s as usize];
+                        if marks[b].is_empty() {
+                            touched.push(b);
+                        }
+                        marks[b].push(s);
+                    }
+                }
+                for b in touched {
+                    let x = mem::take(&mut marks[b]);
+                    if x.len() == blocks[b].len() {
+                        continue;
+                    }
+                    for &s in &x {
+                        in_x[s as usize] = true;
+                    }
+                    blocks[b].retain(|&s| !in_x[s as usize]);
+                    let new = blocks.len();
+                    for &s in &x {
+                        in_x[s as usize] = false;
+                        block_of[s as usize] = new;
+                    }
+                    blocks.push(x);
+                    marks.push(Vec::new());
+                    if in_work[b] {
+                        worklist.push(new);
+                        in_work.push(true);
+                    } else {
+                        let smaller = if blocks[b].len() <= blocks[new].len() {
+                            b
+                        } else {
+                            new
+                        };
+                        in_work.push(false);
+                        worklist.push(smaller);
+                        in_work[smaller] = true;
+                    }
+                }
+            }
+        }
+
+        // Number the blocks in order of their first state.
+        let mut ids = vec![u32::MAX; blocks.len()];
+        let mut reps = Vec::with_capacity(blocks.len());
+        for (s, &b) in block_of.iter().enumerate() {
+            if ids[b] == u32::MAX {
+                ids[b] = reps.len() as u32;
+                reps.push(s);
+            }
+        }
+        let remap = |t: u32| ids[block_of[(t >> 1) as usize]] << 1 | (t & 1);
+        let trans = reps
+            .iter()
+            .flat_map(|&s| self.trans[s * k..(s + 1) * k].iter().map(|&t| remap(t)))
+            .collect();
+        self.accepts = reps.iter().map(|&s| self.accepts[s]).collect();
+        self.starts = self
+            .starts
+            .iter()
+            .map(|&s| ids[block_of[s as usize]])
+            .collect();
+        self.trans = trans;
+        self.dead = self.dead_states();
+    }
+
+    // Finds the states that loop to themselves on every class without
+    // matching.
+    fn dead_states(&self) -> Box<[bool]> {
+        let k = self.alphabet.len();
+        (0..self.len())
+            .map(|s| {
+                self.accepts[s] == 0
+                    && self.trans[s * k..(s + 1) * k]
+                        .iter()
+                        .all(|&t| t == (s as u32) << 1)
+            })
+            .collect()
+    }
+}
+
+impl Engine for Dfa {
+    /// Searches with the DFA, which must have been built from prog.
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            sub.reset();
+            if !self.try_is_match(input)? {
+                return Ok(false);
+            }
+            if sub.is_empty() {
+                return Ok(true);
+            }
+            Pike.try_search(prog, input, sub)
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "dfa"
+    }
+}
+
+// Returns the contexts that can occur for the alphabet.
+fn contexts(alphabet: &Alphabet) -> impl Iterator<Item = u8> + '_ {
+    (0..NUM_CTX as u8).filter(|&ctx| ctx & !alphabet.ctx_mask() == 0)
+}
+
+#[inline]
+fn start_index(anchored: Anchored, ctx: u8) -> usize {
+    let a = match anchored {
+        Anchored::Yes => 0,
+        Anchored::No => 1,
+    };
+    a * NUM_CTX + ctx as usize
+}
+
+impl error::Error for DfaError {}
+
+impl Display for DfaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DfaError::TooManyStates { limit } => {
+                write!(f, "DFA exceeds limit of {limit} states")
+            }
+            DfaError::InvalidFormat(msg) => write!(f, "invalid serialized DFA: {msg}"),
+            DfaError::UnicodeWordBoundary => {
+                write!(f, "DFA on UTF-8 bytes cannot have a Unicode word boundary")
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::fmt::Debug;
+
+use crate::backtrack::Visited;
+use crate::regex::unwrap_search;
+use crate::{
+    BacktrackError, GaveUp, Input, Prog, RecursionError, RecursiveConfig, Sub, Unit,
+    DEFAULT_VISITED_CAPACITY, VM,
+};
+
+/// A matching engine, which searches for the leftmost-first match of a
+/// program in an input.
+///
+/// Every engine has the same contract for submatches: `sub` is reset before
+/// the search, and on a match it holds the start and end offsets of each
+/// capture group that participated, for as many slots as `sub` has. On no
+/// match, it is left reset. Engines that do not track submatches (see
+/// [`Engine::captures`]) only fill slots 0 and 1 with the overall match.
+///
+/// A program compiled to UTF-8 (see [`Compiler::utf8`](crate::Compiler::utf8))
+/// consumes one byte at a time, but no engine reports an empty match within a
+/// char, where the program over units could not have matched.
+///
+/// Every engine counts its work against the budget of the input (see
+/// [`Input::budget`]) and gives up when it is exhausted, in which case `sub`
+/// is left reset.
+pub trait Engine: Debug + Send + Sync {
+    /// Searches the span of the input and reports whether it matched, or
+    /// gives up when the budget of the input is exhausted.
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp>;
+
+    /// Searches the span of the input and reports whether it matched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the search gives up, which can only happen when the input
+    /// has a budget or a recursive engine exceeds its limits. Use
+    /// [`Engine::try_search`] to handle it.
+    #[inline]
+    fn search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> bool {
+        unwrap_search(self.try_search(prog, input, sub))
+    }
+
+    /// Returns a short name for the engine.
+    fn name(&self) -> &'static str;
+
+    /// Reports whether the engine records submatches other than the overall
+    /// match.
+    #[inline]
+    fn captures(&self) -> bool {
+        true
+    }
+}
+
+impl<E: Engine + ?Sized> Engine for &E {
+    #[inline]
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        (**self).try_search(prog, input, sub)
+    }
+
+    #[inline]
+    fn search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> bool {
+        (**self).search(prog, input, sub)
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    #[inline]
+    fn captures(&self) -> bool {
+        (**self).captures()
+    }
+}
+
+/// A recursive backtracking engine.
+///
+/// The recursive engines memoize the (pc, offset) pairs they have tried (see
+/// [`RecursiveConfig::memoize`]), so they run in O(insts × len) time instead
+/// of exponential time on patterns like `(a|a)*c`. They give up when the memo
+/// would exceed [`DEFAULT_VISITED_CAPACITY`] or they exceed their default
+/// limits, rather than searching with another engine, so use
+/// [`Engine::try_search`] to handle long spans.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Recursive;
+
+/// A backtracking engine that recurses only at splits and saves. Like
+/// [`Recursive`], it memoizes and gives up when it recurses deeper than
+/// [`DEFAULT_DEPTH_LIMIT`](crate::DEFAULT_DEPTH_LIMIT).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RecursiveLoop;
+
+/// A backtracking engine like [`RecursiveLoop`], with an explicit stack of
+/// jobs instead of recursion. Like [`Recursive`], it memoizes and gives up,
+/// but only when its stack exceeds
+/// [`DEFAULT_STACK_LIMIT`](crate::DEFAULT_STACK_LIMIT) jobs, so it is not
+/// limited by the depth of the native stack.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RecursiveStack;
+
+/// A backtracking engine with an explicit stack of threads, which visits each
+/// instruction at each position at most once. Spans that are too long for its
+/// visited set are searched with the Pike VM instead.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Backtrack;
+
+/// A Thompson NFA simulation, which runs in linear time but reports only the
+/// span of the overall match.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Thompson;
+
+/// A Pike VM, which runs in linear time and records submatches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Pike;
+
+/// The engines that run the program directly on a VM, in order from the
+/// simplest to the most capable.
+pub const ENGINES: [&dyn Engine; 6] = [
+    &Recursive,
+    &RecursiveLoop,
+    &RecursiveStack,
+    &Backtrack,
+    &Thompson,
+    &Pike,
+];
+
+// Searches with the search function, skipping the empty matches within a char
+// that a program compiled to UTF-8 can find (see
+// Prog::matches_empty_within_char), by searching again from the end of the
+// char. The chars are decoded from the start of the span, like the engines do
+// for a program over units.
+pub(crate) fn search_whole_chars(
+    prog: &Prog,
+    input: &Input<'_>,
+    sub: &mut Sub,
+    mut search: impl FnMut(&Input<'_>, &mut Sub) -> Result<bool, GaveUp>,
+) -> Result<bool, GaveUp> {
+    if !prog.matches_empty_within_char() {
+        return search(input, sub);
+    }
+    if sub.is_empty() {
+        // The overall match is needed to tell whether it is empty.
+        return search_whole_chars(prog, input, &mut Sub::new(2), search);
+    }
+    let haystack = &input.haystack()[..input.end()];
+    let mut input = input.clone();
+    // The end of the last unit that starts before the match.
+    let mut unit_end = input.start();
+    while search(&input, sub)? {
+        let Some((start, end)) = sub.group(0) else {
+            return Ok(true);
+        };
+        while unit_end < start {
+            unit_end += Unit::decode(&haystack[unit_end..]).unwrap().1;
+        }
+        if start != end || start == unit_end {
+            return Ok(true);
+        }
+        input = input.span(unit_end..haystack.len());
+    }
+    Ok(false)
+}
+
+// Searches with a recursive matcher that memoizes, or gives up when the memo
+// would be too large or the matcher exceeds its limits.
+fn search_recursive<'i, 's>(
+    prog: &'i Prog,
+    input: &Input<'s>,
+    sub: &mut Sub,
+    matcher: impl FnOnce(&mut VM<'i, 's>, &mut Sub, &RecursiveConfig) -> Result<bool, RecursionError>,
+) -> Result<bool, GaveUp> {
+    sub.reset();
+    let len = input.end() - input.start();
+    let ninsts = prog.insts().len();
+    if Visited::size(ninsts, len) > DEFAULT_VISITED_CAPACITY {
+        return Err(GaveUp::HaystackTooLarge {
+            len,
+            limit: (DEFAULT_VISITED_CAPACITY * 8 / ninsts.max(1)).saturating_sub(1),
+        });
+    }
+    let mut config = RecursiveConfig::new();
+    config.memoize(true);
+    let mut vm = VM::with_input(prog, input, false);
+    matcher(&mut vm, sub, &config).map_err(|err| {
+        sub.reset();
+        err.into()
+    })
+}
+
+impl Engine for Recursive {
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            search_recursive(prog, input, sub, VM::match_recursive_with)
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "recursive"
+    }
+}
+
+impl Engine for RecursiveLoop {
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            search_recursive(prog, input, sub, VM::match_recursive_loop_with)
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "recursiveloop"
+    }
+}
+
+impl Engine for RecursiveStack {
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            search_recursive(prog, input, sub, VM::match_recursive_stack)
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "recursivestack"
+    }
+}
+
+impl Engine for Backtrack {
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            sub.reset();
+            match VM::with_input(prog, input, false).match_backtrack(sub) {
+                Ok(matched) => Ok(matched),
+                Err(BacktrackError::GaveUp(err)) => Err(err),
+                Err(BacktrackError::HaystackTooLarge { .. }) => Pike.try_search(prog, input, sub),
+            }
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "backtrack"
+    }
+}
+
+impl Engine for Thompson {
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            sub.reset();
+            VM::with_input(prog, input, false).match_thompsonvm(sub)
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "thompson"
+    }
+
+    #[inline]
+    fn captures(&self) -> bool {
+        false
+    }
+}
+
+impl Engine for Pike {
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            sub.reset();
+            VM::with_input(prog, input, false).match_pikevm(sub)
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "pike"
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+use lalrpop_util::ParseError;
+
+use crate::lex::Token;
+use crate::CompileError;
+
+/// An error from parsing a pattern, with the span in the pattern where it
+/// occurred.
+///
+/// The `Display` impl renders a one-line message like
+/// `regex: error at 4: missing )`. The alternate form (`{:#}`) also prints the
+/// pattern with a caret under the error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    span: Range<usize>,
+    pattern: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A group without a closing `)`.
+    MissingParen,
+    /// A `)` without an opening group.
+    UnmatchedParen,
+    /// A class without a closing `]`.
+    MissingBracket,
+    /// A repetition operator without an expression to repeat.
+    NothingToRepeat,
+    /// An empty pattern, alternative, or group.
+    MissingExpression,
+    /// A `\` at the end of the pattern.
+    TrailingBackslash,
+    /// A `\` followed by a char that does not form an escape.
+    UnknownEscape(char),
+    /// A `\x` or `\u` escape with malformed hex digits or an invalid code
+    /// point.
+    InvalidEscape,
+    /// A class range with its start greater than its end.
+    InvalidRange,
+    /// A `(?` that does not begin a valid group.
+    InvalidGroup,
+    /// A group name that is empty, contains chars other than word chars, or
+    /// starts with a digit.
+    InvalidGroupName,
+    /// A group name that is used by more than one group.
+    DuplicateGroupName(String),
+    /// A flag in a flag group that is not recognized.
+    UnknownFlag(char),
+    /// A counted repetition with its minimum greater than its maximum or a
+    /// count that overflows.
+    InvalidRepeat,
+    /// A token that is not valid at its position.
+    UnexpectedToken,
+    /// A pattern that compiles to more instructions than the size limit.
+    TooBig { size: usize, limit: usize },
+    /// A class that matches raw bytes in a program compiled to UTF-8.
+    RawBytes,
+}
+
+impl Error {
+    #[inline]
+    pub(crate) fn new(kind: ErrorKind, span: Range<usize>) -> Self {
+        Error {
+            kind,
+            span,
+            pattern: String::new(),
+        }
+    }
+
+    // Converts an error from the generated parser, classifying syntax errors
+    // by the offending token and the tokens that were expected instead.
+    pub(crate) fn from_parse(err: ParseError<usize, Token, Error>, pattern: &str) -> Self {
+        let expects = |expected: &[String], tok: &str| expected.iter().any(|e| e == tok);
+        let mut err = match err {
+            ParseError::User { error } => error,
+            ParseError::InvalidToken { location } => {
+                Error::new(ErrorKind::UnexpectedToken, location..location)
+            }
+            ParseError::UnrecognizedEof { location, expected } => {
+                let kind = if expects(&expected, r#"")""#) {
+                    ErrorKind::MissingParen
+                } else if expects(&expected, r#""]""#) {
+                    ErrorKind::MissingBracket
+                } else {
+                    ErrorKind::MissingExpression
+                };
+                Error::new(kind, location..location)
+            }
+            ParseError::UnrecognizedToken {
+                token: (start, tok, end),
+                expected,
+            } => {
+                let kind = match tok {
+                    Token::Star | Token::Plus | Token::Quest | Token::Repeat(..) => {
+                        ErrorKind::NothingToRepeat
+                    }
+                    Token::ParenR if expects(&expected, r#""|""#) => ErrorKind::UnmatchedParen,
+                    Token::ParenR | Token::Alt => ErrorKind::MissingExpression,
+                    _ => ErrorKind::UnexpectedToken,
+                };
+                Error::new(kind, start..end)
+            }
+            ParseError::ExtraToken {
+                token: (start, tok, end),
+            } => {
+                let kind = match tok {
+                    Token::ParenR => ErrorKind::UnmatchedParen,
+                    _ => ErrorKind::UnexpectedToken,
+                };
+                Error::new(kind, start..end)
+            }
+        };
+        err.pattern = pattern.to_owned();
+        err
+    }
+
+    // Converts an error from compiling a parsed pattern, which spans the whole
+    // pattern.
+    pub(crate) fn from_compile(err: CompileError, pattern: &str) -> Self {
+        let kind = match err {
+            CompileError::TooBig { size, limit } => ErrorKind::TooBig { size, limit },
+            CompileError::RawBytes => ErrorKind::RawBytes,
+        };
+        Error {
+            kind,
+            span: 0..pattern.len(),
+            pattern: pattern.to_owned(),
+        }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Returns the byte range in the pattern where the error occurred.
+    #[inline]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    #[inline]
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "regex: error at {}: {}", self.span.start, self.kind)?;
+        if f.alternate() {
+            // Columns are counted in chars, which matches the display width
+            // for most patterns.
+            let col = self
+                .pattern
+                .get(..self.span.start)
+                .map_or(0, |s| s.chars().count());
+            let width = self
+                .pattern
+                .get(self.span.clone())
+                .map_or(0, |s| s.chars().count());
+            write!(
+                f,
+                "\n    {}\n    {:col$}{}",
+                self.pattern,
+                "",
+                "^".repeat(width.max(1))
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::MissingParen => write!(f, "missing )"),
+            ErrorKind::UnmatchedParen => write!(f, "unexpected )"),
+            ErrorKind::MissingBracket => write!(f, "missing ]"),
+            ErrorKind::NothingToRepeat => write!(f, "missing argument to repetition operator"),
+            ErrorKind::MissingExpression => write!(f, "missing expression"),
+            ErrorKind::TrailingBackslash => write!(f, "trailing \\"),
+            ErrorKind::UnknownEscape(ch) => write!(f, "unknown escape \\{ch}"),
+            ErrorKind::InvalidEscape => write!(f, "invalid escape sequence"),
+            ErrorKind::InvalidRange => write!(f, "invalid character class range"),
+            ErrorKind::InvalidGroup => write!(f, "invalid group"),
+            ErrorKind::InvalidGroupName => write!(f, "invalid capture group name"),
+            ErrorKind::DuplicateGroupName(name) => {
+                write!(f, "duplicate capture group name {name}")
+            }
+            ErrorKind::UnknownFlag(ch) => write!(f, "unknown flag {ch}"),
+            ErrorKind::InvalidRepeat => write!(f, "invalid repeat count"),
+            ErrorKind::UnexpectedToken => write!(f, "unexpected token"),
+            ErrorKind::TooBig { size, limit } => {
+                write!(f, "program of {size} instructions exceeds limit of {limit}")
+            }
+            ErrorKind::RawBytes => write!(f, "raw bytes cannot be compiled to UTF-8"),
+        }
+    }
+}
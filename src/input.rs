@@ -0,0 +1,107 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::ops::Range;
+
+use crate::Budget;
+
+/// The parameters of a search: the haystack, the span of it to search,
+/// whether the match must start at the beginning of the span, and the budget
+/// of work that the search may do.
+///
+/// Only the span is searched, but assertions see the whole haystack, so `\b`
+/// or `^` at the edges of the span depend on the surrounding text. The
+/// haystack need not be valid UTF-8 (see [`Unit`](crate::Unit)).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Input<'h> {
+    haystack: &'h [u8],
+    span: Range<usize>,
+    anchored: Anchored,
+    budget: Budget,
+}
+
+/// Whether a search is anchored to the start of its span.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Anchored {
+    /// The match must start at the beginning of the span.
+    Yes,
+    /// The match may start anywhere in the span.
+    #[default]
+    No,
+}
+
+impl<'h> Input<'h> {
+    /// Creates an unanchored search over the whole haystack.
+    #[inline]
+    pub fn new<H: ?Sized + AsRef<[u8]>>(haystack: &'h H) -> Self {
+        let haystack = haystack.as_ref();
+        Input {
+            haystack,
+            span: 0..haystack.len(),
+            anchored: Anchored::No,
+            budget: Budget::new(),
+        }
+    }
+
+    /// Sets the span of the haystack to search.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the span is out of bounds.
+    #[inline]
+    pub fn span(mut self, span: Range<usize>) -> Self {
+        assert!(
+            span.start <= span.end && span.end <= self.haystack.len(),
+            "invalid span {span:?} for haystack of length {}",
+            self.haystack.len(),
+        );
+        self.span = span;
+        self
+    }
+
+    #[inline]
+    pub fn anchored(mut self, anchored: Anchored) -> Self {
+        self.anchored = anchored;
+        self
+    }
+
+    /// Sets the budget of the search. A search that exhausts it gives up,
+    /// which is reported by [`Engine::try_search`](crate::Engine::try_search).
+    #[inline]
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    #[inline]
+    pub fn haystack(&self) -> &'h [u8] {
+        self.haystack
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.span.start
+    }
+
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.span.end
+    }
+
+    #[inline]
+    pub fn get_anchored(&self) -> Anchored {
+        self.anchored
+    }
+
+    #[inline]
+    pub fn get_budget(&self) -> &Budget {
+        &self.budget
+    }
+}
@@ -0,0 +1,254 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Mutex;
+
+use crate::engine::search_whole_chars;
+use crate::subset::{Alphabet, Closure};
+use crate::{Engine, GaveUp, Input, Pike, Prog, Sub, Unit};
+
+/// The default maximum number of bytes used by the state cache of a lazy DFA.
+pub const DEFAULT_CACHE_CAPACITY: usize = 2 << 20;
+
+/// A DFA that is built lazily from the program during the search, by subset
+/// construction, and cached between searches.
+///
+/// Each unit of the haystack costs one transition lookup once its state has
+/// been built. The DFA only decides whether there is a match; when submatches
+/// are requested, the Pike VM is run after the DFA finds a match. When the
+/// cache fills up, it is flushed and rebuilt, and if that happens so often
+/// that little progress is made between flushes, the search falls back to the
+/// Pike VM. It also falls back for a program compiled to UTF-8 with a Unicode
+/// word boundary.
+#[derive(Debug)]
+pub struct LazyDfa {
+    cache: Mutex<Option<Cache>>,
+    capacity: usize,
+}
+
+impl LazyDfa {
+    #[inline]
+    pub fn new() -> Self {
+        LazyDfa::with_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Creates a lazy DFA whose cache uses at most approximately the given
+    /// number of bytes.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        LazyDfa {
+            cache: Mutex::new(None),
+            capacity,
+        }
+    }
+
+    // Reports whether the program matches in the span of the input, or None
+    // if the DFA cannot decide it, or gives up when the budget of the input
+    // is exhausted. The cache is reused when it was built for the same
+    // program, as identified by its id, and a temporary cache is used when it
+    // is held by another search.
+    pub(crate) fn is_match(&self, prog: &Prog, input: &Input<'_>) -> Result<Option<bool>, GaveUp> {
+        match self.cache.try_lock() {
+            Ok(mut cache) => {
+                if cache
+                    .as_ref()
+                    .is_none_or(|cache| cache.prog_id != prog.id())
+                {
+                    *cache = Some(Cache::new(prog, self.capacity));
+                }
+                cache.as_mut().unwrap().is_match(prog, input)
+            }
+            Err(_) => Cache::new(prog, self.capacity).is_match(prog, input),
+        }
+    }
+}
+
+impl Default for LazyDfa {
+    #[inline]
+    fn default() -> Self {
+        LazyDfa::new()
+    }
+}
+
+impl Clone for LazyDfa {
+    // The cache is not shared with the clone.
+    #[inline]
+    fn clone(&self) -> Self {
+        LazyDfa::with_capacity(self.capacity)
+    }
+}
+
+impl Engine for LazyDfa {
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            sub.reset();
+            match self.is_match(prog, input)? {
+                Some(false) => Ok(false),
+                Some(true) if sub.is_empty() => Ok(true),
+                _ => Pike.try_search(prog, input, sub),
+            }
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "lazydfa"
+    }
+}
+
+// A transition that has not been computed yet. Computed transitions store the
+// next state id shifted left by one, with the low bit set when the program
+// matches before the char is consumed.
+const UNKNOWN: u32 = u32::MAX;
+
+// A cache is considered to be thrashing when it is flushed after fewer than
+// this many chars per state.
+const MIN_CHARS_PER_STATE: usize = 10;
+
+#[derive(Clone, Debug)]
+struct Cache {
+    // The id of the program that the states were built from.
+    prog_id: u64,
+    alphabet: Alphabet,
+    states: Vec<State>,
+    map: HashMap<(Box<[usize]>, u8), u32>,
+    capacity: usize,
+    size: usize,
+    // The number of chars consumed since the cache was last flushed.
+    chars: usize,
+    // The number of times the cache has been flushed.
+    flushes: usize,
+    closure: Closure,
+}
+
+#[derive(Clone, Debug)]
+struct State {
+    // The pcs of the threads before following empty transitions.
+    pcs: Box<[usize]>,
+    // The context of the unit before the position.
+    ctx: u8,
+    trans: Box<[u32]>,
+}
+
+impl Cache {
+    fn new(prog: &Prog, capacity: usize) -> Self {
+        Cache {
+            prog_id: prog.id(),
+            alphabet: Alphabet::new(prog),
+            closure: Closure::new(prog),
+            states: Vec::new(),
+            map: HashMap::new(),
+            capacity,
+            size: 0,
+            chars: 0,
+            flushes: 0,
+        }
+    }
+
+    fn is_match(&mut self, prog: &Prog, input: &Input<'_>) -> Result<Option<bool>, GaveUp> {
+        if !self.alphabet.ctx_is_exact() {
+            return Ok(None);
+        }
+        let mut meter = input.get_budget().meter();
+        let haystack = input.haystack();
+        let ctx = self.alphabet.ctx(
+            self.alphabet
+                .decode_last(&haystack[..input.start()])
+                .map(|(unit, _)| unit),
+        );
+        let start = prog.start(input.get_anchored());
+        let Some(mut s) = self.add_state(Box::new([start]), ctx) else {
+            return Ok(None);
+        };
+        let mut offset = input.start();
+        while let Some((unit, len)) = self.alphabet.decode(&haystack[offset..input.end()]) {
+            meter.step()?;
+            offset += len;
+            let class = self.alphabet.class(unit);
+            let mut t = self.states[s as usize].trans[class];
+            if t == UNKNOWN {
+                match self.compute(prog, s, class, unit) {
+                    Some(next) => t = next,
+                    None => return Ok(None),
+                }
+            }
+            if t & 1 != 0 {
+                return Ok(Some(true));
+            }
+            s = t >> 1;
+            if self.states[s as usize].pcs.is_empty() {
+                return Ok(Some(false));
+            }
+            self.chars += 1;
+        }
+        let next = self.alphabet.ctx(
+            self.alphabet
+                .decode(&haystack[input.end()..])
+                .map(|(unit, _)| unit),
+        );
+        let (matched, _) = self.step(prog, s, next, None);
+        Ok(Some(matched))
+    }
+
+    // Computes and caches the transition from state s on the unit, which is
+    // in the given class.
+    fn compute(&mut self, prog: &Prog, s: u32, class: usize, unit: Unit) -> Option<u32> {
+        let ctx = self.alphabet.ctx(Some(unit));
+        let (matched, pcs) = self.step(prog, s, ctx, Some(unit));
+        let flushes = self.flushes;
+        let next = self.add_state(pcs.into(), ctx)?;
+        let t = next << 1 | matched as u32;
+        // s is no longer valid if the cache was flushed.
+        if self.flushes == flushes {
+            self.states[s as usize].trans[class] = t;
+        }
+        Some(t)
+    }
+
+    // Follows the empty transitions from state s, with next as the context of
+    // the unit after the position, then consumes the unit.
+    #[inline]
+    fn step(&mut self, prog: &Prog, s: u32, next: u8, unit: Option<Unit>) -> (bool, Vec<usize>) {
+        let state = &self.states[s as usize];
+        self.closure
+            .step(prog.insts(), &state.pcs, state.ctx, next, unit)
+    }
+
+    // Returns the id of the state with the pcs and context, adding it if it is
+    // not cached. The cache is flushed when it is full, or None is returned
+    // when it is thrashing.
+    fn add_state(&mut self, pcs: Box<[usize]>, ctx: u8) -> Option<u32> {
+        let key = (pcs, ctx);
+        if let Some(&id) = self.map.get(&key) {
+            return Some(id);
+        }
+        let nclasses = self.alphabet.len();
+        let cost = nclasses * mem::size_of::<u32>()
+            + 2 * key.0.len() * mem::size_of::<usize>()
+            + mem::size_of::<State>()
+            + mem::size_of::<(Box<[usize]>, u8, u32)>();
+        if self.size + cost > self.capacity {
+            if self.chars < MIN_CHARS_PER_STATE * self.states.len() || cost > self.capacity {
+                return None;
+            }
+            self.states.clear();
+            self.map.clear();
+            self.size = 0;
+            self.chars = 0;
+            self.flushes += 1;
+        }
+        let id = self.states.len() as u32;
+        self.states.push(State {
+            pcs: key.0.clone(),
+            ctx,
+            trans: vec![UNKNOWN; nclasses].into(),
+        });
+        self.map.insert(key, id);
+        self.size += cost;
+        Some(id)
+    }
+}
@@ -3,53 +3,450 @@
 // Use of this source code is governed by a BSD-style
 // license that can be found in the LICENSE file.
 
-use std::convert::Infallible;
+use std::collections::HashSet;
 use std::fmt::{self, Display, Formatter, Write};
 use std::str::CharIndices;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+use crate::{Assertion, Class, Error, ErrorKind, Perl};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Token {
     Alt,
     Star,
     Plus,
     Quest,
+    Repeat(u32, Option<u32>),
     ParenL,
+    NamedParenL(String),
+    GroupL,
     ParenR,
-    Colon,
-    Dot,
+    Dot(/*dot_nl*/ bool),
+    ClassL(Flags),
+    ClassNegL(Flags),
+    ClassR,
+    Range,
     Char(char),
+    Byte(u8),
+    Class(Class),
+    Assert(Assertion),
+}
+
+/// Flags that control how a pattern is lexed. They are set for the whole
+/// pattern by `Parser` and within a group by `(?flags)` or `(?flags:...)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Flags {
+    /// `i`: letters match both their upper and lower case.
+    pub case_insensitive: bool,
+    /// `s`: `.` matches `\n`.
+    pub dot_nl: bool,
+    /// `m`: `^` and `$` match at the start and end of lines.
+    pub multi_line: bool,
+    /// `x`: whitespace is ignored and `#` begins a comment.
+    pub verbose: bool,
+    /// `u`: Perl classes, word boundaries, and case folding use their Unicode
+    /// definitions. When unset, `.` and negated classes also match bytes that
+    /// are not valid UTF-8, and `\x80` to `\xFF` match raw bytes.
+    pub unicode: bool,
+}
+
+impl Flags {
+    #[inline]
+    pub fn new() -> Self {
+        Flags {
+            case_insensitive: false,
+            dot_nl: false,
+            multi_line: false,
+            verbose: false,
+            unicode: true,
+        }
+    }
+}
+
+impl Default for Flags {
+    #[inline]
+    fn default() -> Self {
+        Flags::new()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Lexer<'a> {
     chars: CharIndices<'a>,
+    class: Option<ClassState>,
+    flags: Flags,
+    // The flags to restore at the end of each enclosing group.
+    groups: Vec<Flags>,
+    names: HashSet<String>,
+}
+
+// The position of the lexer within a bracketed class.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ClassState {
+    // Directly after `[` or `[^`, where `]` is literal.
+    Start,
+    // After a char or byte, which may begin a range.
+    Char,
+    // After `-` in a range.
+    Range,
+    // After the end of a range.
+    RangeEnd,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(s: &'a str) -> Self {
+    pub fn new(s: &'a str, flags: Flags) -> Self {
         Lexer {
             chars: s.char_indices(),
+            class: None,
+            flags,
+            groups: Vec::new(),
+            names: HashSet::new(),
+        }
+    }
+
+    // Returns an error spanning from start to the current offset.
+    fn error(&self, kind: ErrorKind, start: usize) -> Error {
+        Error::new(kind, start..self.offset())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.clone().next().map(|(_, ch)| ch)
+    }
+
+    fn offset(&self) -> usize {
+        self.chars.offset()
+    }
+
+    // Lexes an escape sequence, with the backslash at offset start already
+    // consumed.
+    fn lex_escape(&mut self, start: usize) -> Result<Token, Error> {
+        let (_, ch) = self
+            .chars
+            .next()
+            .ok_or(self.error(ErrorKind::TrailingBackslash, start))?;
+        let perl = match ch {
+            'd' | 'D' => Some(Perl::Digit),
+            'w' | 'W' => Some(Perl::Word),
+            's' | 'S' => Some(Perl::Space),
+            _ => None,
+        };
+        let unicode = self.flags.unicode;
+        match ch {
+            'A' => return Ok(Token::Assert(Assertion::StartText)),
+            'z' => return Ok(Token::Assert(Assertion::EndText)),
+            'b' => return Ok(Token::Assert(Assertion::WordBoundary(unicode))),
+            'B' => return Ok(Token::Assert(Assertion::NotWordBoundary(unicode))),
+            _ => {}
+        }
+        if let Some(perl) = perl {
+            let mut class = Class::perl(perl, unicode);
+            if ch.is_ascii_uppercase() {
+                class.negate();
+                if !unicode {
+                    class.negate_bytes();
+                }
+            }
+            return Ok(Token::Class(class));
+        }
+        let lit = self.lex_escape_char(start, ch)?;
+        if ch == 'x' && !unicode && !lit.is_ascii() {
+            return Ok(Token::Byte(lit as u8));
+        }
+        Ok(Token::Char(lit))
+    }
+
+    fn lex_escape_char(&mut self, start: usize, ch: char) -> Result<char, Error> {
+        match ch {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            'x' => {
+                let hi = self.lex_hex_digit(start)?;
+                let lo = self.lex_hex_digit(start)?;
+                Ok(char::from(hi << 4 | lo))
+            }
+            'u' => {
+                if self.chars.next().map(|(_, ch)| ch) != Some('{') {
+                    return Err(self.error(ErrorKind::InvalidEscape, start));
+                }
+                let mut value = 0u32;
+                let mut digits = 0;
+                while self.peek() != Some('}') {
+                    if digits == 6 {
+                        return Err(self.error(ErrorKind::InvalidEscape, start));
+                    }
+                    value = value << 4 | u32::from(self.lex_hex_digit(start)?);
+                    digits += 1;
+                }
+                self.chars.next();
+                if digits == 0 {
+                    return Err(self.error(ErrorKind::InvalidEscape, start));
+                }
+                char::from_u32(value).ok_or(self.error(ErrorKind::InvalidEscape, start))
+            }
+            ch if ch.is_ascii_punctuation() || ch == ' ' => Ok(ch),
+            ch => Err(self.error(ErrorKind::UnknownEscape(ch), start)),
+        }
+    }
+
+    // Lexes the start of a group, with the `(` at offset start already
+    // consumed. Flag groups of the form `(?flags)` set the flags for the rest
+    // of the enclosing group and produce no token.
+    fn lex_group(&mut self, start: usize) -> Result<Option<Token>, Error> {
+        if self.peek() != Some('?') {
+            self.groups.push(self.flags);
+            return Ok(Some(Token::ParenL));
+        }
+        self.chars.next();
+        if let Some(name) = self.lex_group_name(start)? {
+            self.groups.push(self.flags);
+            return Ok(Some(Token::NamedParenL(name)));
+        }
+        let mut flags = self.flags;
+        let mut negated = false;
+        let mut empty = true;
+        loop {
+            let (i, ch) = self
+                .chars
+                .next()
+                .ok_or(self.error(ErrorKind::InvalidGroup, start))?;
+            let flag = match ch {
+                ':' if !(negated && empty) => {
+                    self.groups.push(self.flags);
+                    self.flags = flags;
+                    return Ok(Some(Token::GroupL));
+                }
+                ')' if !empty => {
+                    self.flags = flags;
+                    return Ok(None);
+                }
+                '-' if !negated => {
+                    negated = true;
+                    empty = true;
+                    continue;
+                }
+                'i' => &mut flags.case_insensitive,
+                's' => &mut flags.dot_nl,
+                'm' => &mut flags.multi_line,
+                'x' => &mut flags.verbose,
+                'u' => &mut flags.unicode,
+                ':' | ')' | '-' => return Err(self.error(ErrorKind::InvalidGroup, start)),
+                _ => return Err(self.error(ErrorKind::UnknownFlag(ch), i)),
+            };
+            *flag = !negated;
+            empty = false;
+        }
+    }
+
+    // Lexes the name of a named group of the form `(?P<name>` or `(?<name>`,
+    // with the `(?` at offset start already consumed. If the following text
+    // does not start a named group, the lexer is not advanced.
+    fn lex_group_name(&mut self, start: usize) -> Result<Option<String>, Error> {
+        let rest = self.chars.as_str();
+        let Some(rest) = rest.strip_prefix("P<").or_else(|| rest.strip_prefix('<')) else {
+            return Ok(None);
+        };
+        if rest.starts_with(['=', '!']) {
+            return Err(self.error(ErrorKind::InvalidGroup, start));
+        }
+        let len = rest
+            .find('>')
+            .ok_or(self.error(ErrorKind::InvalidGroupName, start))?;
+        let name = &rest[..len];
+        let is_valid = name
+            .chars()
+            .next()
+            .is_some_and(|ch| ch == '_' || ch.is_alphabetic())
+            && name.chars().all(|ch| ch == '_' || ch.is_alphanumeric());
+        if !is_valid {
+            return Err(self.error(ErrorKind::InvalidGroupName, start));
+        }
+        if !self.names.insert(name.to_owned()) {
+            return Err(self.error(ErrorKind::DuplicateGroupName(name.to_owned()), start));
+        }
+        let end = self.offset() + (self.chars.as_str().len() - rest.len()) + len + 1;
+        while self.offset() < end {
+            self.chars.next();
+        }
+        Ok(Some(name.to_owned()))
+    }
+
+    // Returns the token for a literal char outside of a class, which is a
+    // class of its case variants when case-insensitive.
+    fn literal(&self, ch: char) -> Token {
+        if self.flags.case_insensitive {
+            let mut class = Class::from_ranges([(ch, ch)]);
+            class.case_fold(self.flags.unicode);
+            if class.ranges() != [(ch, ch)] {
+                return Token::Class(class);
+            }
+        }
+        Token::Char(ch)
+    }
+
+    // Skips whitespace and comments in verbose mode.
+    fn skip_verbose(&mut self) {
+        while let Some(ch) = self.peek() {
+            if ch == '#' {
+                while self.chars.next().is_some_and(|(_, ch)| ch != '\n') {}
+            } else if ch.is_whitespace() {
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // Lexes a counted repetition of the form `{n}`, `{n,}`, or `{n,m}`, with
+    // the `{` at offset start already consumed. If the following text does not
+    // have that form, the lexer is not advanced and `{` is a literal.
+    fn lex_repeat(&mut self, start: usize) -> Result<Option<Token>, Error> {
+        let saved = self.chars.clone();
+        let Some(min) = self.lex_count(start)? else {
+            self.chars = saved;
+            return Ok(None);
+        };
+        let max = match self.chars.next() {
+            Some((_, '}')) => return Ok(Some(Token::Repeat(min, Some(min)))),
+            Some((_, ',')) => self.lex_count(start)?,
+            _ => {
+                self.chars = saved;
+                return Ok(None);
+            }
+        };
+        if self.chars.next().map(|(_, ch)| ch) != Some('}') {
+            self.chars = saved;
+            return Ok(None);
+        }
+        if max.is_some_and(|max| min > max) {
+            return Err(self.error(ErrorKind::InvalidRepeat, start));
+        }
+        Ok(Some(Token::Repeat(min, max)))
+    }
+
+    fn lex_count(&mut self, start: usize) -> Result<Option<u32>, Error> {
+        let mut count: Option<u32> = None;
+        while let Some(digit) = self.peek().and_then(|ch| ch.to_digit(10)) {
+            self.chars.next();
+            count = count
+                .unwrap_or(0)
+                .checked_mul(10)
+                .and_then(|count| count.checked_add(digit))
+                .map(Some)
+                .ok_or(self.error(ErrorKind::InvalidRepeat, start))?;
+        }
+        Ok(count)
+    }
+
+    fn next_in_class(
+        &mut self,
+        i: usize,
+        ch: char,
+        state: ClassState,
+    ) -> Result<(usize, Token, usize), Error> {
+        let tok = match ch {
+            ']' if state != ClassState::Start => {
+                self.class = None;
+                Token::ClassR
+            }
+            '-' if state == ClassState::Char && !matches!(self.peek(), Some(']') | None) => {
+                self.class = Some(ClassState::Range);
+                Token::Range
+            }
+            _ => {
+                let tok = if ch == '\\' {
+                    self.lex_escape(i)?
+                } else {
+                    Token::Char(ch)
+                };
+                self.class = Some(match tok {
+                    Token::Char(_) | Token::Byte(_) if state != ClassState::Range => {
+                        ClassState::Char
+                    }
+                    _ => ClassState::RangeEnd,
+                });
+                tok
+            }
+        };
+        Ok((i, tok, self.offset()))
+    }
+
+    fn lex_hex_digit(&mut self, start: usize) -> Result<u8, Error> {
+        match self.chars.next() {
+            Some((_, ch)) => match ch.to_digit(16) {
+                Some(digit) => Ok(digit as u8),
+                None => Err(self.error(ErrorKind::InvalidEscape, start)),
+            },
+            None => Err(self.error(ErrorKind::InvalidEscape, start)),
         }
     }
 }
 
 impl Iterator for Lexer<'_> {
-    type Item = Result<(usize, Token, usize), Infallible>;
+    type Item = Result<(usize, Token, usize), Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (i, ch) = self.chars.next()?;
-        let tok = match ch {
-            '|' => Token::Alt,
-            '*' => Token::Star,
-            '+' => Token::Plus,
-            '?' => Token::Quest,
-            '(' => Token::ParenL,
-            ')' => Token::ParenR,
-            ':' => Token::Colon,
-            '.' => Token::Dot,
-            ch => Token::Char(ch),
-        };
-        Some(Ok((i, tok, i + ch.len_utf8())))
+        loop {
+            if self.class.is_none() && self.flags.verbose {
+                self.skip_verbose();
+            }
+            let (i, ch) = self.chars.next()?;
+            if let Some(state) = self.class {
+                return Some(self.next_in_class(i, ch, state));
+            }
+            let tok = match ch {
+                '|' => Token::Alt,
+                '*' => Token::Star,
+                '+' => Token::Plus,
+                '?' => Token::Quest,
+                '{' => match self.lex_repeat(i) {
+                    Ok(Some(tok)) => tok,
+                    Ok(None) => Token::Char('{'),
+                    Err(err) => return Some(Err(err)),
+                },
+                '(' => match self.lex_group(i) {
+                    Ok(Some(tok)) => tok,
+                    Ok(None) => continue,
+                    Err(err) => return Some(Err(err)),
+                },
+                ')' => {
+                    if let Some(flags) = self.groups.pop() {
+                        self.flags = flags;
+                    }
+                    Token::ParenR
+                }
+                '.' if self.flags.unicode => Token::Dot(self.flags.dot_nl),
+                '.' => {
+                    let mut class = Class::new();
+                    if !self.flags.dot_nl {
+                        class.push('\n', '\n');
+                    }
+                    class.negate();
+                    class.negate_bytes();
+                    Token::Class(class)
+                }
+                '^' if self.flags.multi_line => Token::Assert(Assertion::StartLine),
+                '$' if self.flags.multi_line => Token::Assert(Assertion::EndLine),
+                '^' => Token::Assert(Assertion::StartText),
+                '$' => Token::Assert(Assertion::EndText),
+                '[' => {
+                    self.class = Some(ClassState::Start);
+                    if self.peek() == Some('^') {
+                        self.chars.next();
+                        Token::ClassNegL(self.flags)
+                    } else {
+                        Token::ClassL(self.flags)
+                    }
+                }
+                '\\' => match self.lex_escape(i) {
+                    Ok(Token::Char(ch)) => self.literal(ch),
+                    Ok(tok) => tok,
+                    Err(err) => return Some(Err(err)),
+                },
+                ch => self.literal(ch),
+            };
+            return Some(Ok((i, tok, self.offset())));
+        }
     }
 }
 
@@ -60,11 +457,26 @@ impl Display for Token {
             Token::Star => '*',
             Token::Plus => '+',
             Token::Quest => '?',
+            Token::Repeat(min, max) => {
+                return match max {
+                    Some(max) if max == min => write!(f, "{{{min}}}"),
+                    Some(max) => write!(f, "{{{min},{max}}}"),
+                    None => write!(f, "{{{min},}}"),
+                };
+            }
             Token::ParenL => '(',
+            Token::NamedParenL(name) => return write!(f, "(?P<{name}>"),
+            Token::GroupL => return f.write_str("(?:"),
             Token::ParenR => ')',
-            Token::Colon => ':',
-            Token::Dot => '.',
+            Token::Dot(_) => '.',
+            Token::ClassL(_) => '[',
+            Token::ClassNegL(_) => return f.write_str("[^"),
+            Token::ClassR => ']',
+            Token::Range => '-',
             Token::Char(ch) => *ch,
+            Token::Byte(b) => return write!(f, "\\x{b:02X}"),
+            Token::Class(class) => return write!(f, "{class}"),
+            Token::Assert(assertion) => return write!(f, "{assertion}"),
         })
     }
 }
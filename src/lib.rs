@@ -7,12 +7,48 @@
 extern crate lalrpop_util;
 
 mod backtrack;
+mod budget;
+pub mod bytes;
+mod class;
 mod compile;
+mod dfa;
+mod engine;
+mod error;
+mod input;
+mod lazy;
 mod lex;
-lalrpop_mod!(parse);
+mod meta;
+mod onepass;
+lalrpop_mod!(
+    #[allow(clippy::all)]
+    parse
+);
 mod pike;
+mod print;
 mod recursive;
+mod regex;
 mod regexp;
+mod replace;
+mod subset;
 mod thompson;
+mod unicode;
+mod utf8;
 
+pub use backtrack::{BacktrackError, DEFAULT_VISITED_CAPACITY};
+pub use budget::{Budget, GaveUp};
+pub use class::{Class, Perl};
+pub use compile::{CompileError, Compiler, DEFAULT_SIZE_LIMIT};
+pub use dfa::{Dfa, DfaBuilder, DfaError, DEFAULT_STATE_LIMIT};
+pub use engine::{
+    Backtrack, Engine, Pike, Recursive, RecursiveLoop, RecursiveStack, Thompson, ENGINES,
+};
+pub use error::{Error, ErrorKind};
+pub use input::{Anchored, Input};
+pub use lazy::{LazyDfa, DEFAULT_CACHE_CAPACITY};
+pub use meta::Meta;
+pub use onepass::OnePass;
+pub use recursive::{RecursionError, RecursiveConfig, DEFAULT_DEPTH_LIMIT, DEFAULT_STACK_LIMIT};
+pub use regex::{CaptureMatches, Captures, Match, Matches, Regex, Split, SplitN};
 pub use regexp::*;
+pub use replace::Replacer;
+pub use utf8::Unit;
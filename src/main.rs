@@ -6,7 +6,7 @@
 use std::env;
 use std::process::exit;
 
-use re1::{Prog, Regexp, Sub, VM};
+use re1::{Input, Regexp, Sub, ENGINES};
 
 fn main() {
     let mut args = env::args();
@@ -18,42 +18,31 @@ fn main() {
         }
     };
     let re = match Regexp::parse(&pattern) {
-        Ok(re) => re.unanchored(),
+        Ok(re) => Regexp::Paren(0, None, re),
         Err(err) => {
-            eprintln!("parse: {}", err);
+            eprintln!("{err:#}");
             exit(1);
         }
     };
     println!("{re}\n");
-    let prog = re.compile();
+    let prog = match re.compile() {
+        Ok(prog) => prog,
+        Err(err) => {
+            eprintln!("compile: {}", err);
+            exit(1);
+        }
+    };
     print!("{prog}");
     let mut sub = Sub::new(prog.nsub());
     for (i, s) in args.enumerate() {
         println!("\n#{i} {s}");
-        regexp_match("recursive", VM::match_recursive, &prog, &s, &mut sub);
-        regexp_match(
-            "recursiveloop",
-            VM::match_recursive_loop,
-            &prog,
-            &s,
-            &mut sub,
-        );
-        regexp_match("backtrack", VM::match_backtrack, &prog, &s, &mut sub);
-        regexp_match("thompson", VM::match_thompsonvm, &prog, &s, &mut sub);
-        regexp_match("pike", VM::match_pikevm, &prog, &s, &mut sub);
-    }
-}
-
-fn regexp_match<'i, 's, F>(label: &str, matches: F, prog: &'i Prog, s: &'s str, sub: &mut Sub)
-where
-    F: FnOnce(&mut VM<'i, 's>, &mut Sub) -> bool,
-{
-    let mut vm = VM::new(&prog, &s, false);
-    sub.reset();
-    print!("{label} ");
-    if matches(&mut vm, sub) {
-        println!("match {sub}");
-    } else {
-        println!("-no match-");
+        for engine in ENGINES {
+            print!("{} ", engine.name());
+            if engine.search(&prog, &Input::new(&s), &mut sub) {
+                println!("match {sub}");
+            } else {
+                println!("-no match-");
+            }
+        }
     }
 }
@@ -0,0 +1,66 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use crate::engine::search_whole_chars;
+use crate::{Backtrack, Engine, GaveUp, Input, LazyDfa, OnePass, Pike, Prog, Sub, Thompson};
+
+/// An engine that picks a matcher for each search, based on the program, the
+/// input, and whether submatches are requested.
+///
+/// Every search first runs a lazy DFA, which answers `is_match` by itself and
+/// rules out haystacks without a match. When there is a match, searches that
+/// only need the overall match use the Thompson NFA. When submatches are
+/// requested, one-pass programs use the one-pass engine, spans short enough
+/// for the visited set of the backtracker use it, and everything else uses the
+/// Pike VM. Every strategy runs in linear time in the haystack, since the
+/// backtracker visits each instruction at each position at most once and a DFA
+/// whose cache thrashes falls back to the Pike VM. So the program does not
+/// need to be inspected for patterns that make backtracking exponential, like
+/// alternation under repetition in `(a|a)*c`: the visited set bounds the
+/// backtracker to the same O(insts × len) as the Pike VM. Every strategy
+/// gives up when the budget of the input is exhausted.
+#[derive(Clone, Debug, Default)]
+pub struct Meta {
+    dfa: LazyDfa,
+}
+
+impl Meta {
+    #[inline]
+    pub fn new() -> Self {
+        Meta::default()
+    }
+}
+
+impl Engine for Meta {
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            sub.reset();
+            match self.dfa.is_match(prog, input)? {
+                Some(false) => return Ok(false),
+                Some(true) if sub.is_empty() => return Ok(true),
+                Some(true) => {}
+                None if sub.is_empty() => return Pike.try_search(prog, input, sub),
+                None => {}
+            }
+            if sub.len() <= 2 {
+                return Thompson.try_search(prog, input, sub);
+            }
+            if prog
+                .one_pass()
+                .is_some_and(|table| table.supports(input.get_anchored()))
+            {
+                return OnePass.try_search(prog, input, sub);
+            }
+            // The visited set keeps the backtracker linear even for alternation
+            // under repetition, and it is faster than the Pike VM when it fits.
+            Backtrack.try_search(prog, input, sub)
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "meta"
+    }
+}
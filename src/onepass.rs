@@ -0,0 +1,246 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::collections::HashMap;
+
+use crate::engine::search_whole_chars;
+use crate::subset::Alphabet;
+use crate::{Anchored, Assertion, Engine, GaveUp, Input, Inst, Pike, Prog, Sub, Unit};
+
+/// An engine for one-pass programs, which resolves submatches in a single
+/// forward scan with one thread.
+///
+/// A program is one-pass when, at every position, the next char determines
+/// which path through the splits to take, as in `key=(\w+);val=(\d+)`. This is
+/// detected when the program is compiled (see [`Prog::is_one_pass`]). Only
+/// anchored searches can be one-pass, so unanchored searches are run with it
+/// only when every match must start with `^`. Everything else falls back to
+/// the Pike VM.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct OnePass;
+
+// The maximum number of transitions in a one-pass table.
+const MAX_TABLE_SIZE: usize = 1 << 20;
+
+// A table of the deterministic paths from each position in a one-pass
+// program. A node is a pc where a thread can be before following empty
+// transitions: the anchored start or the pc after a char is consumed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct OnePassTable {
+    alphabet: Alphabet,
+    nodes: Vec<Node>,
+    actions: Vec<Action>,
+    // Whether every path from the start asserts the start of the text, so that
+    // an unanchored search can only match where an anchored one does.
+    anchored_start: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Node {
+    // The action for each char class, indexing actions, or NONE.
+    trans: Box<[u32]>,
+    // The empty path to a match.
+    matched: Option<Path>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Action {
+    // The node after consuming the char.
+    next: usize,
+    // The empty path before consuming the char.
+    path: Path,
+    // Whether the action takes priority over the match of its node.
+    before_match: bool,
+}
+
+// The empty transitions along a path: the slots to save and the assertions
+// that must hold.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Path {
+    saves: Vec<usize>,
+    asserts: Vec<Assertion>,
+}
+
+const NONE: u32 = u32::MAX;
+
+impl OnePassTable {
+    // Builds the table for the anchored program, or None if it is not
+    // one-pass: a char can be consumed by two paths from the same position,
+    // two paths lead to a match, or two paths meet at the same pc.
+    pub(crate) fn new(prog: &Prog) -> Option<Self> {
+        let alphabet = Alphabet::new(prog);
+        let insts = prog.insts();
+        let start = prog.start(Anchored::Yes);
+        let mut node_ids = HashMap::from([(start, 0)]);
+        let mut pcs = vec![start];
+        let mut nodes = Vec::new();
+        let mut actions = Vec::new();
+        let mut visited = vec![false; insts.len()];
+        let mut stack = Vec::new();
+        while let Some(&pc) = pcs.get(nodes.len()) {
+            if (nodes.len() + 1).saturating_mul(alphabet.len()) > MAX_TABLE_SIZE {
+                return None;
+            }
+            let mut node = Node {
+                trans: vec![NONE; alphabet.len()].into(),
+                matched: None,
+            };
+            visited.fill(false);
+            stack.push((pc, Path::default()));
+            while let Some((pc, mut path)) = stack.pop() {
+                if visited[pc] {
+                    return None;
+                }
+                visited[pc] = true;
+                match insts[pc] {
+                    Inst::Jmp(x) => stack.push((x, path)),
+                    Inst::Split(x, y) => {
+                        stack.push((y, path.clone()));
+                        stack.push((x, path));
+                    }
+                    Inst::Save(n) => {
+                        path.saves.push(n);
+                        stack.push((pc + 1, path));
+                    }
+                    Inst::Assert(assertion) => {
+                        path.asserts.push(assertion);
+                        stack.push((pc + 1, path));
+                    }
+                    Inst::Match => {
+                        if node.matched.is_some() {
+                            return None;
+                        }
+                        node.matched = Some(path);
+                    }
+                    ref inst => {
+                        let next = *node_ids.entry(pc + 1).or_insert_with(|| {
+                            pcs.push(pc + 1);
+                            pcs.len() - 1
+                        });
+                        let id = actions.len() as u32;
+                        actions.push(Action {
+                            next,
+                            path,
+                            before_match: node.matched.is_none(),
+                        });
+                        for class in 0..alphabet.len() {
+                            let consumes = alphabet
+                                .representative(class)
+                                .is_some_and(|unit| consumes(inst, unit));
+                            if consumes {
+                                if node.trans[class] != NONE {
+                                    return None;
+                                }
+                                node.trans[class] = id;
+                            }
+                        }
+                    }
+                }
+            }
+            nodes.push(node);
+        }
+
+        let starts_text = |path: &Path| path.asserts.contains(&Assertion::StartText);
+        let anchored_start = nodes[0].matched.as_ref().is_none_or(starts_text)
+            && nodes[0]
+                .trans
+                .iter()
+                .filter(|&&a| a != NONE)
+                .all(|&a| starts_text(&actions[a as usize].path));
+        Some(OnePassTable {
+            alphabet,
+            nodes,
+            actions,
+            anchored_start,
+        })
+    }
+
+    // Reports whether a search with the anchoring can be run in one pass.
+    #[inline]
+    pub(crate) fn supports(&self, anchored: Anchored) -> bool {
+        anchored == Anchored::Yes || self.anchored_start
+    }
+}
+
+impl Engine for OnePass {
+    fn try_search(&self, prog: &Prog, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        search_whole_chars(prog, input, sub, |input, sub| {
+            sub.reset();
+            let table = match prog.one_pass() {
+                Some(table) if table.supports(input.get_anchored()) => table,
+                _ => return Pike.try_search(prog, input, sub),
+            };
+            let mut meter = input.get_budget().meter();
+            let haystack = input.haystack();
+            let mut caps = Sub::new(sub.len());
+            let mut matched = false;
+            let mut node = &table.nodes[0];
+            let mut offset = input.start();
+            loop {
+                if let Err(err) = meter.step() {
+                    sub.reset();
+                    return Err(err);
+                }
+                let unit = table.alphabet.decode(&haystack[offset..input.end()]);
+                let action = unit
+                    .map(|(unit, _)| node.trans[table.alphabet.class(unit)])
+                    .filter(|&a| a != NONE)
+                    .map(|a| &table.actions[a as usize])
+                    .filter(|action| action.path.holds(haystack, offset));
+                if let Some(path) = &node.matched {
+                    if path.holds(haystack, offset) {
+                        sub.clone_from(&caps);
+                        path.save(sub, offset);
+                        matched = true;
+                        // The match wins over lower-priority paths, but a
+                        // higher-priority path may still match later.
+                        if action.is_none_or(|action| !action.before_match) {
+                            return Ok(true);
+                        }
+                    }
+                }
+                let Some(action) = action else {
+                    return Ok(matched);
+                };
+                action.path.save(&mut caps, offset);
+                offset += unit.unwrap().1;
+                node = &table.nodes[action.next];
+            }
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> &'static str {
+        "onepass"
+    }
+}
+
+impl Path {
+    #[inline]
+    fn holds(&self, haystack: &[u8], offset: usize) -> bool {
+        self.asserts.iter().all(|a| a.matches(haystack, offset))
+    }
+
+    #[inline]
+    fn save(&self, sub: &mut Sub, offset: usize) {
+        for &n in &self.saves {
+            if n < sub.len() {
+                sub.set(n, offset);
+            }
+        }
+    }
+}
+
+// Reports whether the inst consumes the unit.
+fn consumes(inst: &Inst, unit: Unit) -> bool {
+    match *inst {
+        Inst::Char(ch) => unit == Unit::Char(ch),
+        Inst::Any => matches!(unit, Unit::Char(_)),
+        Inst::AnyNotNL => matches!(unit, Unit::Char(ch) if ch != '\n'),
+        Inst::Class(ref class) => class.matches(unit),
+        Inst::ByteRange(lo, hi) => matches!(unit, Unit::Byte(b) if lo <= b && b <= hi),
+        _ => false,
+    }
+}
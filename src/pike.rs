@@ -6,7 +6,7 @@
 use std::mem;
 use std::rc::Rc;
 
-use crate::{Inst, Sub, VM};
+use crate::{GaveUp, Inst, Sub, Unit, VM};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct Thread {
@@ -21,43 +21,96 @@ impl Thread {
 }
 
 impl VM<'_, '_> {
-    pub fn match_pikevm(&mut self, sub_out: &mut Sub) -> bool {
+    pub fn match_pikevm(&mut self, sub_out: &mut Sub) -> Result<bool, GaveUp> {
+        let mut meter = self.budget.meter();
         let mut curr_threads = Vec::new();
         let mut next_threads = Vec::new();
         // visited replaces global gen (generation) in the original
         let mut visited = vec![false; self.insts.len()];
 
         sub_out.reset();
-        let t = Thread::new(0, Rc::new(Sub::new(sub_out.len())));
-        add_thread(&mut curr_threads, t, &mut visited, self.insts, 0);
+        let t = Thread::new(self.pc, Rc::new(Sub::new(sub_out.len())));
+        add_thread(
+            &mut curr_threads,
+            t,
+            &mut visited,
+            self.insts,
+            self.haystack(),
+            self.offset,
+        );
 
         let mut matched = None;
         loop {
-            let ch = self.next_char();
-            if curr_threads.len() == 0 {
+            let unit = self.next_unit();
+            if curr_threads.is_empty() {
                 break;
             }
             visited.fill(false);
             for t in curr_threads.drain(..) {
+                meter.step()?;
                 let pc = t.pc;
                 match self.insts[pc] {
-                    Inst::Char(ch1) => {
-                        if ch == Some(ch1) {
-                            let t = Thread::new(pc + 1, t.sub);
-                            add_thread(&mut next_threads, t, &mut visited, self.insts, self.offset);
-                        }
+                    Inst::Char(ch) if unit == Some(Unit::Char(ch)) => {
+                        let t = Thread::new(pc + 1, t.sub);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            self.haystack(),
+                            self.offset,
+                        );
                     }
-                    Inst::Any => {
-                        if ch.is_some() {
-                            let t = Thread::new(pc + 1, t.sub);
-                            add_thread(&mut next_threads, t, &mut visited, self.insts, self.offset);
-                        }
+                    Inst::Any if matches!(unit, Some(Unit::Char(_))) => {
+                        let t = Thread::new(pc + 1, t.sub);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            self.haystack(),
+                            self.offset,
+                        );
+                    }
+                    Inst::AnyNotNL if matches!(unit, Some(Unit::Char(ch)) if ch != '\n') => {
+                        let t = Thread::new(pc + 1, t.sub);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            self.haystack(),
+                            self.offset,
+                        );
+                    }
+                    Inst::Class(ref class) if unit.is_some_and(|unit| class.matches(unit)) => {
+                        let t = Thread::new(pc + 1, t.sub);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            self.haystack(),
+                            self.offset,
+                        );
+                    }
+                    Inst::ByteRange(lo, hi) if matches!(unit, Some(Unit::Byte(b)) if lo <= b && b <= hi) =>
+                    {
+                        let t = Thread::new(pc + 1, t.sub);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            self.haystack(),
+                            self.offset,
+                        );
                     }
                     Inst::Match => {
                         matched = Some(t.sub);
                         break;
                     }
-                    // Jmp, Split, Save handled in add_thread, so that
+                    // Jmp, Split, Save, Assert handled in add_thread, so that
                     // machine execution matches what a backtracker would do.
                     // This is discussed (but not shown as code) in
                     // Regular Expression Matching: the Virtual Machine Approach.
@@ -66,20 +119,27 @@ impl VM<'_, '_> {
             }
             mem::swap(&mut curr_threads, &mut next_threads);
             next_threads.clear();
-            if ch.is_none() {
+            if unit.is_none() {
                 break;
             }
         }
         if let Some(sub) = matched {
             sub_out.clone_from(&sub);
-            true
+            Ok(true)
         } else {
-            false
+            Ok(false)
         }
     }
 }
 
-fn add_thread(l: &mut Vec<Thread>, t: Thread, visited: &mut [bool], insts: &[Inst], offset: usize) {
+fn add_thread(
+    l: &mut Vec<Thread>,
+    t: Thread,
+    visited: &mut [bool],
+    insts: &[Inst],
+    s: &[u8],
+    offset: usize,
+) {
     let pc = t.pc;
     if visited[pc] {
         return; // already on list
@@ -87,14 +147,19 @@ fn add_thread(l: &mut Vec<Thread>, t: Thread, visited: &mut [bool], insts: &[Ins
     visited[pc] = true;
 
     match &insts[pc] {
-        Inst::Jmp(x) => add_thread(l, Thread::new(*x, t.sub), visited, insts, offset),
+        Inst::Jmp(x) => add_thread(l, Thread::new(*x, t.sub), visited, insts, s, offset),
         Inst::Split(x, y) => {
-            add_thread(l, Thread::new(*x, t.sub.clone()), visited, insts, offset);
-            add_thread(l, Thread::new(*y, t.sub), visited, insts, offset);
+            add_thread(l, Thread::new(*x, t.sub.clone()), visited, insts, s, offset);
+            add_thread(l, Thread::new(*y, t.sub), visited, insts, s, offset);
         }
         Inst::Save(n) => {
             let sub = t.sub.update(*n, offset);
-            add_thread(l, Thread::new(pc + 1, sub), visited, insts, offset);
+            add_thread(l, Thread::new(pc + 1, sub), visited, insts, s, offset);
+        }
+        Inst::Assert(assertion) => {
+            if assertion.matches(s, offset) {
+                add_thread(l, Thread::new(pc + 1, t.sub), visited, insts, s, offset);
+            }
         }
         _ => l.push(t),
     }
@@ -0,0 +1,151 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::fmt::{self, Write};
+
+use crate::{Assertion, Class, Perl, Regexp};
+
+// Precedence levels, from loosest to tightest binding. A regexp printed where
+// a tighter level is required is wrapped in a non-capturing group.
+const PREC_ALT: u8 = 0;
+const PREC_CAT: u8 = 1;
+const PREC_REPEAT: u8 = 2;
+const PREC_ATOM: u8 = 3;
+
+impl Regexp {
+    /// Prints the regexp in pattern syntax, with the fewest groups needed for
+    /// it to parse back to an equal regexp with the default parser flags.
+    pub fn to_pattern(&self) -> String {
+        let mut s = String::new();
+        write_regexp(&mut s, self, PREC_ALT).unwrap();
+        s
+    }
+
+    fn prec(&self) -> u8 {
+        match self {
+            Regexp::Alt(..) => PREC_ALT,
+            Regexp::Cat(..) => PREC_CAT,
+            Regexp::Quest(..) | Regexp::Star(..) | Regexp::Plus(..) | Regexp::Repeat(..) => {
+                PREC_REPEAT
+            }
+            Regexp::Lit(_)
+            | Regexp::Dot(_)
+            | Regexp::Class(_)
+            | Regexp::Assert(_)
+            | Regexp::Paren(..) => PREC_ATOM,
+        }
+    }
+}
+
+fn write_regexp(w: &mut String, re: &Regexp, prec: u8) -> fmt::Result {
+    if re.prec() < prec {
+        w.push_str("(?:");
+        write_regexp(w, re, PREC_ALT)?;
+        w.push(')');
+        return Ok(());
+    }
+    match re {
+        // Alt and Cat are left-associative, so only the left side may be the
+        // same operator without a group.
+        Regexp::Alt(left, right) => {
+            write_regexp(w, left, PREC_ALT)?;
+            w.push('|');
+            write_regexp(w, right, PREC_CAT)
+        }
+        Regexp::Cat(left, right) => {
+            write_regexp(w, left, PREC_CAT)?;
+            write_regexp(w, right, PREC_REPEAT)
+        }
+        Regexp::Lit(ch) => write_lit(w, *ch),
+        Regexp::Dot(false) => write!(w, "."),
+        Regexp::Dot(true) => write!(w, "(?s:.)"),
+        Regexp::Class(class) => write_class(w, class),
+        Regexp::Assert(Assertion::StartText) => write!(w, "^"),
+        Regexp::Assert(Assertion::EndText) => write!(w, "$"),
+        Regexp::Assert(assertion) => write!(w, "{assertion}"),
+        Regexp::Paren(_, name, inner) => {
+            match name {
+                Some(name) => write!(w, "(?P<{name}>")?,
+                None => w.push('('),
+            }
+            write_regexp(w, inner, PREC_ALT)?;
+            w.push(')');
+            Ok(())
+        }
+        Regexp::Quest(greedy, inner) => write_repeat(w, inner, "?", *greedy),
+        Regexp::Star(greedy, inner) => write_repeat(w, inner, "*", *greedy),
+        Regexp::Plus(greedy, inner) => write_repeat(w, inner, "+", *greedy),
+        Regexp::Repeat(greedy, min, max, inner) => {
+            let op = match max {
+                Some(max) if max == min => format!("{{{min}}}"),
+                Some(max) => format!("{{{min},{max}}}"),
+                None => format!("{{{min},}}"),
+            };
+            write_repeat(w, inner, &op, *greedy)
+        }
+    }
+}
+
+fn write_repeat(w: &mut String, inner: &Regexp, op: &str, greedy: bool) -> fmt::Result {
+    write_regexp(w, inner, PREC_ATOM)?;
+    w.push_str(op);
+    if !greedy {
+        w.push('?');
+    }
+    Ok(())
+}
+
+fn write_lit(w: &mut String, ch: char) -> fmt::Result {
+    match ch {
+        '\\' | '|' | '*' | '+' | '?' | '(' | ')' | '.' | '[' | '{' | '^' | '$' => {
+            write!(w, "\\{ch}")
+        }
+        '\n' => write!(w, "\\n"),
+        '\t' => write!(w, "\\t"),
+        '\r' => write!(w, "\\r"),
+        '\0' => write!(w, "\\0"),
+        _ if ch.is_control() => write!(w, "\\u{{{:X}}}", ch as u32),
+        _ => write!(w, "{ch}"),
+    }
+}
+
+fn write_class(w: &mut String, class: &Class) -> fmt::Result {
+    // Bytes can only be written with Unicode disabled, where a negated class
+    // also negates the bytes.
+    let has_bytes = !class.byte_ranges().is_empty();
+    if has_bytes {
+        w.push_str("(?-u:");
+    } else {
+        for (perl, name) in [(Perl::Digit, 'd'), (Perl::Word, 'w'), (Perl::Space, 's')] {
+            let mut perl_class = Class::perl(perl, true);
+            if *class == perl_class {
+                return write!(w, "\\{name}");
+            }
+            perl_class.negate();
+            if *class == perl_class {
+                return write!(w, "\\{}", name.to_ascii_uppercase());
+            }
+        }
+    }
+    let mut negated = class.clone();
+    negated.negate();
+    if has_bytes {
+        negated.negate_bytes();
+    }
+    let len = |class: &Class| class.ranges().len() + class.byte_ranges().len();
+    // An empty class has no bracket syntax, so it is written as the negation
+    // of the full class.
+    if len(class) == 0 || len(&negated) != 0 && len(&negated) < len(class) {
+        write!(w, "[^")?;
+        negated.write_ranges(w)?;
+        write!(w, "]")?;
+    } else {
+        write!(w, "{class}")?;
+    }
+    if has_bytes {
+        w.push(')');
+    }
+    Ok(())
+}
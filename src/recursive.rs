@@ -3,80 +3,311 @@
 // Use of this source code is governed by a BSD-style
 // license that can be found in the LICENSE file.
 
-use crate::{Inst, Sub, VM};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+
+use crate::backtrack::Visited;
+use crate::budget::Meter;
+use crate::{GaveUp, Inst, Sub, Unit, VM};
+
+/// The default maximum depth of the recursive matchers.
+pub const DEFAULT_DEPTH_LIMIT: usize = 1000;
+
+/// The default maximum number of pending jobs of
+/// [`VM::match_recursive_stack`].
+pub const DEFAULT_STACK_LIMIT: usize = 1 << 20;
+
+/// Limits for the recursive matchers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RecursiveConfig {
+    depth_limit: usize,
+    stack_limit: usize,
+    memoize: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecursionError {
+    /// The matcher recursed deeper than the depth limit.
+    TooDeep { limit: usize },
+    /// The explicit stack had more pending jobs than the stack limit.
+    StackTooLarge { limit: usize },
+    /// The search exhausted its budget.
+    GaveUp(GaveUp),
+}
+
+impl RecursiveConfig {
+    #[inline]
+    pub fn new() -> Self {
+        RecursiveConfig {
+            depth_limit: DEFAULT_DEPTH_LIMIT,
+            stack_limit: DEFAULT_STACK_LIMIT,
+            memoize: false,
+        }
+    }
+
+    /// Sets the maximum depth, which is the number of nested calls of the
+    /// recursive matchers. The default keeps them well within the stack of a
+    /// spawned thread.
+    #[inline]
+    pub fn depth_limit(&mut self, limit: usize) -> &mut Self {
+        self.depth_limit = limit;
+        self
+    }
+
+    /// Sets the maximum number of pending jobs of
+    /// [`VM::match_recursive_stack`]. Its stack is on the heap, so the default
+    /// is much larger than the depth limit and only bounds its memory.
+    #[inline]
+    pub fn stack_limit(&mut self, limit: usize) -> &mut Self {
+        self.stack_limit = limit;
+        self
+    }
+
+    /// Sets whether to remember each (pc, offset) pair that has been tried, so
+    /// that it fails immediately when reached again. This bounds the time by
+    /// the number of instructions times the length of the span, at the cost
+    /// of one bit per pair.
+    #[inline]
+    pub fn memoize(&mut self, yes: bool) -> &mut Self {
+        self.memoize = yes;
+        self
+    }
+}
+
+impl Default for RecursiveConfig {
+    #[inline]
+    fn default() -> Self {
+        RecursiveConfig::new()
+    }
+}
+
+// The state shared by the calls of a recursive matcher.
+struct Limits {
+    depth_limit: usize,
+    stack_limit: usize,
+    memo: Option<Visited>,
+    meter: Meter,
+}
+
+impl Limits {
+    fn new(vm: &VM<'_, '_>, config: &RecursiveConfig) -> Self {
+        Limits {
+            depth_limit: config.depth_limit,
+            stack_limit: config.stack_limit,
+            memo: config
+                .memoize
+                .then(|| Visited::new(vm.insts.len(), vm.start, vm.end)),
+            meter: vm.budget.meter(),
+        }
+    }
+
+    #[inline]
+    fn check_depth(&self, depth: usize) -> Result<(), RecursionError> {
+        if depth > self.depth_limit {
+            return Err(RecursionError::TooDeep {
+                limit: self.depth_limit,
+            });
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn check_stack(&self, len: usize) -> Result<(), RecursionError> {
+        if len > self.stack_limit {
+            return Err(RecursionError::StackTooLarge {
+                limit: self.stack_limit,
+            });
+        }
+        Ok(())
+    }
+
+    // Counts a step at the pair and reports whether it has already been
+    // tried.
+    #[inline]
+    fn tried(&mut self, pc: usize, offset: usize) -> Result<bool, GaveUp> {
+        self.meter.step()?;
+        Ok(self
+            .memo
+            .as_mut()
+            .is_some_and(|memo| !memo.insert(pc, offset)))
+    }
+}
+
+// A pending job for the explicit-stack matcher.
+enum Job {
+    // Try the thread at the pc and offset.
+    Run { pc: usize, offset: usize },
+    // Restore a slot when backtracking past its save.
+    Restore { n: usize, old: usize },
+}
 
 impl VM<'_, '_> {
-    pub fn match_recursive(&mut self, sub: &mut Sub) -> bool {
+    /// Matches by recursing at every instruction, with the default limits.
+    #[inline]
+    pub fn match_recursive(&mut self, sub: &mut Sub) -> Result<bool, RecursionError> {
+        self.match_recursive_with(sub, &RecursiveConfig::new())
+    }
+
+    pub fn match_recursive_with(
+        &mut self,
+        sub: &mut Sub,
+        config: &RecursiveConfig,
+    ) -> Result<bool, RecursionError> {
+        let mut limits = Limits::new(self, config);
+        self.recursive(sub, &mut limits, 0)
+    }
+
+    fn recursive(
+        &mut self,
+        sub: &mut Sub,
+        limits: &mut Limits,
+        depth: usize,
+    ) -> Result<bool, RecursionError> {
+        limits.check_depth(depth)?;
+        let depth = depth + 1;
+        if limits.tried(self.pc, self.offset)? {
+            return Ok(false);
+        }
         let inst = match self.next_inst() {
             Some(inst) => inst,
-            None => return false,
+            None => return Ok(false),
         };
         match *inst {
             Inst::Char(ch) => {
-                if let Some(ch1) = self.next_char() {
-                    ch == ch1 && self.match_recursive(sub)
+                if self.next_unit() == Some(Unit::Char(ch)) {
+                    self.recursive(sub, limits, depth)
                 } else {
-                    false
+                    Ok(false)
                 }
             }
             Inst::Any => {
-                if self.next_char().is_some() {
-                    self.match_recursive(sub)
+                if matches!(self.next_unit(), Some(Unit::Char(_))) {
+                    self.recursive(sub, limits, depth)
+                } else {
+                    Ok(false)
+                }
+            }
+            Inst::AnyNotNL => {
+                if matches!(self.next_unit(), Some(Unit::Char(ch)) if ch != '\n') {
+                    self.recursive(sub, limits, depth)
+                } else {
+                    Ok(false)
+                }
+            }
+            Inst::Class(ref class) => {
+                if self.next_unit().is_some_and(|unit| class.matches(unit)) {
+                    self.recursive(sub, limits, depth)
+                } else {
+                    Ok(false)
+                }
+            }
+            Inst::ByteRange(lo, hi) => {
+                if matches!(self.next_unit(), Some(Unit::Byte(b)) if lo <= b && b <= hi) {
+                    self.recursive(sub, limits, depth)
                 } else {
-                    false
+                    Ok(false)
                 }
             }
-            Inst::Match => true,
+            Inst::Assert(assertion) => {
+                Ok(self.check(assertion) && self.recursive(sub, limits, depth)?)
+            }
+            Inst::Match => Ok(true),
             Inst::Jmp(x) => {
                 self.pc = x;
-                self.match_recursive(sub)
+                self.recursive(sub, limits, depth)
             }
             Inst::Split(x, y) => {
                 self.pc = x;
-                if self.clone().match_recursive(sub) {
-                    return true;
+                if self.clone().recursive(sub, limits, depth)? {
+                    return Ok(true);
                 }
                 self.pc = y;
-                self.match_recursive(sub)
+                self.recursive(sub, limits, depth)
             }
             Inst::Save(n) => {
                 if n >= sub.len() {
-                    return self.match_recursive(sub);
+                    return self.recursive(sub, limits, depth);
                 }
                 let old = sub.get(n);
                 sub.set(n, self.offset);
-                if self.match_recursive(sub) {
-                    return true;
+                if self.recursive(sub, limits, depth)? {
+                    return Ok(true);
                 }
                 sub.set(n, old);
-                false
+                Ok(false)
             }
         }
     }
 
-    pub fn match_recursive_loop(&mut self, sub: &mut Sub) -> bool {
+    /// Matches by looping over instructions and recursing only at splits and
+    /// saves, with the default limits.
+    #[inline]
+    pub fn match_recursive_loop(&mut self, sub: &mut Sub) -> Result<bool, RecursionError> {
+        self.match_recursive_loop_with(sub, &RecursiveConfig::new())
+    }
+
+    pub fn match_recursive_loop_with(
+        &mut self,
+        sub: &mut Sub,
+        config: &RecursiveConfig,
+    ) -> Result<bool, RecursionError> {
+        let mut limits = Limits::new(self, config);
+        self.recursive_loop(sub, &mut limits, 0)
+    }
+
+    fn recursive_loop(
+        &mut self,
+        sub: &mut Sub,
+        limits: &mut Limits,
+        depth: usize,
+    ) -> Result<bool, RecursionError> {
+        limits.check_depth(depth)?;
+        let depth = depth + 1;
         loop {
+            if limits.tried(self.pc, self.offset)? {
+                return Ok(false);
+            }
             let inst = match self.next_inst() {
                 Some(inst) => inst,
-                None => return false,
+                None => return Ok(false),
             };
             match *inst {
                 Inst::Char(ch) => {
-                    if self.next_char() != Some(ch) {
-                        return false;
+                    if self.next_unit() != Some(Unit::Char(ch)) {
+                        return Ok(false);
                     }
                 }
                 Inst::Any => {
-                    if self.next_char().is_none() {
-                        return false;
+                    if !matches!(self.next_unit(), Some(Unit::Char(_))) {
+                        return Ok(false);
+                    }
+                }
+                Inst::AnyNotNL => {
+                    if !matches!(self.next_unit(), Some(Unit::Char(ch)) if ch != '\n') {
+                        return Ok(false);
+                    }
+                }
+                Inst::Class(ref class) => {
+                    if !self.next_unit().is_some_and(|unit| class.matches(unit)) {
+                        return Ok(false);
                     }
                 }
-                Inst::Match => return true,
+                Inst::ByteRange(lo, hi) => {
+                    if !matches!(self.next_unit(), Some(Unit::Byte(b)) if lo <= b && b <= hi) {
+                        return Ok(false);
+                    }
+                }
+                Inst::Assert(assertion) => {
+                    if !self.check(assertion) {
+                        return Ok(false);
+                    }
+                }
+                Inst::Match => return Ok(true),
                 Inst::Jmp(x) => self.pc = x,
                 Inst::Split(x, y) => {
                     self.pc = x;
-                    if self.clone().match_recursive_loop(sub) {
-                        return true;
+                    if self.clone().recursive_loop(sub, limits, depth)? {
+                        return Ok(true);
                     }
                     self.pc = y;
                 }
@@ -86,13 +317,134 @@ impl VM<'_, '_> {
                     }
                     let old = sub.get(n);
                     sub.set(n, self.offset);
-                    if self.match_recursive_loop(sub) {
-                        return true;
+                    if self.recursive_loop(sub, limits, depth)? {
+                        return Ok(true);
                     }
                     sub.set(n, old);
-                    return false;
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    /// Matches like [`VM::match_recursive_loop`], but with an explicit stack
+    /// of jobs instead of recursion, so that the depth is not bounded by the
+    /// native stack. The stack is bounded by the stack limit instead of the
+    /// depth limit.
+    pub fn match_recursive_stack(
+        &mut self,
+        sub: &mut Sub,
+        config: &RecursiveConfig,
+    ) -> Result<bool, RecursionError> {
+        let mut limits = Limits::new(self, config);
+        let mut jobs = vec![Job::Run {
+            pc: self.pc,
+            offset: self.offset,
+        }];
+        while let Some(job) = jobs.pop() {
+            match job {
+                Job::Run { pc, offset } => {
+                    self.pc = pc;
+                    self.offset = offset;
+                }
+                Job::Restore { n, old } => {
+                    sub.set(n, old);
+                    continue;
+                }
+            }
+            loop {
+                if limits.tried(self.pc, self.offset)? {
+                    break;
+                }
+                let inst = match self.next_inst() {
+                    Some(inst) => inst,
+                    None => break,
+                };
+                match *inst {
+                    Inst::Char(ch) => {
+                        if self.next_unit() != Some(Unit::Char(ch)) {
+                            break;
+                        }
+                    }
+                    Inst::Any => {
+                        if !matches!(self.next_unit(), Some(Unit::Char(_))) {
+                            break;
+                        }
+                    }
+                    Inst::AnyNotNL => {
+                        if !matches!(self.next_unit(), Some(Unit::Char(ch)) if ch != '\n') {
+                            break;
+                        }
+                    }
+                    Inst::Class(ref class) => {
+                        if !self.next_unit().is_some_and(|unit| class.matches(unit)) {
+                            break;
+                        }
+                    }
+                    Inst::ByteRange(lo, hi) => {
+                        if !matches!(self.next_unit(), Some(Unit::Byte(b)) if lo <= b && b <= hi) {
+                            break;
+                        }
+                    }
+                    Inst::Assert(assertion) => {
+                        if !self.check(assertion) {
+                            break;
+                        }
+                    }
+                    Inst::Match => return Ok(true),
+                    Inst::Jmp(x) => self.pc = x,
+                    Inst::Split(x, y) => {
+                        limits.check_stack(jobs.len() + 1)?;
+                        jobs.push(Job::Run {
+                            pc: y,
+                            offset: self.offset,
+                        });
+                        self.pc = x;
+                    }
+                    Inst::Save(n) => {
+                        if n < sub.len() {
+                            limits.check_stack(jobs.len() + 1)?;
+                            jobs.push(Job::Restore { n, old: sub.get(n) });
+                            sub.set(n, self.offset);
+                        }
+                    }
                 }
             }
         }
+        Ok(false)
+    }
+}
+
+impl From<GaveUp> for RecursionError {
+    #[inline]
+    fn from(err: GaveUp) -> Self {
+        RecursionError::GaveUp(err)
+    }
+}
+
+impl From<RecursionError> for GaveUp {
+    #[inline]
+    fn from(err: RecursionError) -> Self {
+        match err {
+            RecursionError::TooDeep { limit } => GaveUp::DepthLimit { limit },
+            RecursionError::StackTooLarge { limit } => GaveUp::StackLimit { limit },
+            RecursionError::GaveUp(err) => err,
+        }
+    }
+}
+
+impl error::Error for RecursionError {}
+
+impl Display for RecursionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RecursionError::TooDeep { limit } => {
+                write!(f, "recursion exceeds depth limit of {limit}")
+            }
+            RecursionError::StackTooLarge { limit } => {
+                write!(f, "backtracking stack exceeds limit of {limit} jobs")
+            }
+            RecursionError::GaveUp(err) => write!(f, "{err}"),
+        }
     }
 }
@@ -0,0 +1,610 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::ops::{Index, Range};
+use std::str;
+use std::sync::Arc;
+
+use crate::{Engine, Error, GaveUp, Input, Meta, Prog, Regexp, Sub, Unit};
+
+/// A compiled regular expression, which finds the leftmost-first match in a
+/// haystack.
+///
+/// To search haystacks that are not valid UTF-8, use
+/// [`bytes::Regex`](crate::bytes::Regex).
+#[derive(Clone, Debug)]
+pub struct Regex {
+    pattern: String,
+    prog: Prog,
+    names: Arc<[Option<String>]>,
+    engine: Arc<dyn Engine>,
+}
+
+/// A haystack that matches are sliced from: a `str`, or bytes for
+/// [`bytes::Regex`](crate::bytes::Regex).
+pub trait Haystack: Index<Range<usize>, Output = Self> + Debug {
+    fn as_bytes(&self) -> &[u8];
+}
+
+/// A single match of a regex in a haystack.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Match<'h, H: ?Sized = str> {
+    haystack: &'h H,
+    start: usize,
+    end: usize,
+}
+
+/// An iterator over all non-overlapping matches in a haystack.
+#[derive(Debug)]
+pub struct Matches<'r, 'h, H: ?Sized = str> {
+    haystack: &'h H,
+    searcher: Searcher<'r, 'h>,
+}
+
+/// An iterator over the capture groups of all non-overlapping matches in a
+/// haystack.
+#[derive(Debug)]
+pub struct CaptureMatches<'r, 'h, H: ?Sized = str> {
+    haystack: &'h H,
+    searcher: Searcher<'r, 'h>,
+}
+
+/// An iterator over the substrings of a haystack between matches.
+#[derive(Clone, Debug)]
+pub struct Split<'r, 'h> {
+    matches: Matches<'r, 'h>,
+    // The start of the next substring, or None when all have been yielded.
+    last: Option<usize>,
+}
+
+/// An iterator over at most a limited number of substrings of a haystack
+/// between matches. The last substring is the rest of the haystack.
+#[derive(Clone, Debug)]
+pub struct SplitN<'r, 'h> {
+    split: Split<'r, 'h>,
+    limit: usize,
+}
+
+// Resumes searching after each match. An empty match that ends where the
+// previous match ended is skipped, so that every position yields at most one
+// match.
+#[derive(Clone, Debug)]
+struct Searcher<'r, 'h> {
+    re: &'r Regex,
+    haystack: &'h [u8],
+    // The offset to resume searching at, or None when the haystack is
+    // exhausted.
+    start: Option<usize>,
+    last_end: Option<usize>,
+}
+
+/// The capture groups of a single match of a regex in a haystack. Group 0 is
+/// the whole match.
+#[derive(Debug)]
+pub struct Captures<'h, H: ?Sized = str> {
+    haystack: &'h H,
+    sub: Sub,
+    names: Arc<[Option<String>]>,
+}
+
+impl Regex {
+    /// Parses and compiles a pattern with the default parser and compiler
+    /// options.
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        let re = Regexp::parse(pattern)?;
+        let prog = Regexp::Paren(0, None, re)
+            .compile()
+            .map_err(|err| Error::from_compile(err, pattern))?;
+        Ok(Regex {
+            pattern: pattern.to_owned(),
+            names: prog.names().into(),
+            prog,
+            engine: Arc::new(Meta::new()),
+        })
+    }
+
+    /// Sets the engine used for searching, which is [`Meta`] by default.
+    #[inline]
+    pub fn with_engine<E: Engine + 'static>(mut self, engine: E) -> Self {
+        self.engine = Arc::new(engine);
+        self
+    }
+
+    #[inline]
+    pub fn engine(&self) -> &dyn Engine {
+        &*self.engine
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    #[inline]
+    pub fn prog(&self) -> &Prog {
+        &self.prog
+    }
+
+    /// Returns the number of capture groups, including group 0.
+    #[inline]
+    pub fn captures_len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns the names of the capture groups, indexed by group number.
+    #[inline]
+    pub fn capture_names(&self) -> impl Iterator<Item = Option<&str>> {
+        self.names.iter().map(|name| name.as_deref())
+    }
+
+    /// Reports whether the regex matches anywhere in the haystack.
+    #[inline]
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.search_sub(&Input::new(haystack), &mut Sub::new(0))
+    }
+
+    /// Returns the leftmost-first match in the haystack.
+    #[inline]
+    pub fn find<'h>(&self, haystack: &'h str) -> Option<Match<'h>> {
+        unwrap_search(self.try_search_in(haystack, &Input::new(haystack)))
+    }
+
+    /// Returns the capture groups of the leftmost-first match in the haystack.
+    #[inline]
+    pub fn captures<'h>(&self, haystack: &'h str) -> Option<Captures<'h>> {
+        unwrap_search(self.try_search_captures_in(haystack, &Input::new(haystack)))
+    }
+
+    /// Returns the leftmost-first match in the span of the input.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the haystack is not valid UTF-8 or the span of the input does
+    /// not start and end on char boundaries, or if the search gives up, which
+    /// can only happen when the input has a budget.
+    #[inline]
+    pub fn search<'h>(&self, input: &Input<'h>) -> Option<Match<'h>> {
+        unwrap_search(self.try_search(input))
+    }
+
+    /// Returns the capture groups of the leftmost-first match in the span of
+    /// the input.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the haystack is not valid UTF-8 or the span of the input does
+    /// not start and end on char boundaries, or if the search gives up, which
+    /// can only happen when the input has a budget.
+    #[inline]
+    pub fn search_captures<'h>(&self, input: &Input<'h>) -> Option<Captures<'h>> {
+        unwrap_search(self.try_search_captures(input))
+    }
+
+    /// Returns the leftmost-first match in the span of the input, or gives up
+    /// when the budget of the input is exhausted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the haystack is not valid UTF-8 or the span of the input does
+    /// not start and end on char boundaries.
+    #[inline]
+    pub fn try_search<'h>(&self, input: &Input<'h>) -> Result<Option<Match<'h>>, GaveUp> {
+        self.try_search_in(haystack_str(input), input)
+    }
+
+    /// Returns the capture groups of the leftmost-first match in the span of
+    /// the input, or gives up when the budget of the input is exhausted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the haystack is not valid UTF-8 or the span of the input does
+    /// not start and end on char boundaries.
+    #[inline]
+    pub fn try_search_captures<'h>(
+        &self,
+        input: &Input<'h>,
+    ) -> Result<Option<Captures<'h>>, GaveUp> {
+        self.try_search_captures_in(haystack_str(input), input)
+    }
+
+    /// Iterates over all non-overlapping matches in the haystack.
+    #[inline]
+    pub fn find_iter<'r, 'h>(&'r self, haystack: &'h str) -> Matches<'r, 'h> {
+        Matches::new(self, haystack)
+    }
+
+    /// Iterates over the capture groups of all non-overlapping matches in the
+    /// haystack.
+    #[inline]
+    pub fn captures_iter<'r, 'h>(&'r self, haystack: &'h str) -> CaptureMatches<'r, 'h> {
+        CaptureMatches::new(self, haystack)
+    }
+
+    /// Iterates over the substrings of the haystack separated by matches. A
+    /// match at the start or end of the haystack yields an empty substring
+    /// there.
+    #[inline]
+    pub fn split<'r, 'h>(&'r self, haystack: &'h str) -> Split<'r, 'h> {
+        Split {
+            matches: self.find_iter(haystack),
+            last: Some(0),
+        }
+    }
+
+    /// Iterates over at most limit substrings of the haystack separated by
+    /// matches, with the last being the unsplit rest of the haystack.
+    #[inline]
+    pub fn splitn<'r, 'h>(&'r self, haystack: &'h str, limit: usize) -> SplitN<'r, 'h> {
+        SplitN {
+            split: self.split(haystack),
+            limit,
+        }
+    }
+
+    // Searches the input, whose haystack is the given haystack.
+    pub(crate) fn try_search_in<'h, H: Haystack + ?Sized>(
+        &self,
+        haystack: &'h H,
+        input: &Input<'h>,
+    ) -> Result<Option<Match<'h, H>>, GaveUp> {
+        let mut sub = Sub::new(2);
+        if !self.try_search_sub(input, &mut sub)? {
+            return Ok(None);
+        }
+        Ok(sub
+            .group(0)
+            .map(|(start, end)| Match::new(haystack, start, end)))
+    }
+
+    // Searches the input for captures, whose haystack is the given haystack.
+    pub(crate) fn try_search_captures_in<'h, H: Haystack + ?Sized>(
+        &self,
+        haystack: &'h H,
+        input: &Input<'h>,
+    ) -> Result<Option<Captures<'h, H>>, GaveUp> {
+        let mut sub = Sub::new(self.prog.nsub());
+        if !self.try_search_sub(input, &mut sub)? {
+            return Ok(None);
+        }
+        Ok(Some(self.new_captures(haystack, sub)))
+    }
+
+    #[inline]
+    pub(crate) fn search_sub(&self, input: &Input<'_>, sub: &mut Sub) -> bool {
+        self.engine.search(&self.prog, input, sub)
+    }
+
+    #[inline]
+    pub(crate) fn try_search_sub(&self, input: &Input<'_>, sub: &mut Sub) -> Result<bool, GaveUp> {
+        self.engine.try_search(&self.prog, input, sub)
+    }
+
+    #[inline]
+    fn new_captures<'h, H: ?Sized>(&self, haystack: &'h H, sub: Sub) -> Captures<'h, H> {
+        Captures {
+            haystack,
+            sub,
+            names: self.names.clone(),
+        }
+    }
+}
+
+// Returns the result of a search that can only give up when the input has a
+// budget.
+#[inline]
+pub(crate) fn unwrap_search<T>(result: Result<T, GaveUp>) -> T {
+    result.unwrap_or_else(|err| panic!("{err}; use try_search to handle a budget"))
+}
+
+// Returns the haystack of the input as a str, checking that the span is on
+// char boundaries, so that matches can be sliced from it.
+fn haystack_str<'h>(input: &Input<'h>) -> &'h str {
+    let haystack = str::from_utf8(input.haystack()).expect("haystack is not valid UTF-8");
+    let span = input.get_span();
+    assert!(
+        haystack.is_char_boundary(span.start) && haystack.is_char_boundary(span.end),
+        "span {span:?} is not on char boundaries of the haystack",
+    );
+    haystack
+}
+
+impl<'r, 'h> Searcher<'r, 'h> {
+    #[inline]
+    fn new(re: &'r Regex, haystack: &'h [u8]) -> Self {
+        Searcher {
+            re,
+            haystack,
+            start: Some(0),
+            last_end: None,
+        }
+    }
+
+    // Finds the next match and returns its span, with the submatches in sub.
+    fn next(&mut self, sub: &mut Sub) -> Option<(usize, usize)> {
+        loop {
+            let start = self.start?;
+            let input = Input::new(self.haystack).span(start..self.haystack.len());
+            if !self.re.search_sub(&input, sub) {
+                self.start = None;
+                return None;
+            }
+            let Some((s, e)) = sub.group(0) else {
+                self.start = None;
+                return None;
+            };
+            // Advance past an empty match by one unit, so the next search
+            // makes progress without splitting a UTF-8 sequence.
+            let next = if s == e {
+                Unit::decode(&self.haystack[e..]).map(|(_, len)| e + len)
+            } else {
+                Some(e)
+            };
+            self.start = next;
+            if s == e && self.last_end == Some(e) {
+                continue;
+            }
+            self.last_end = Some(e);
+            return Some((s, e));
+        }
+    }
+}
+
+impl<'r, 'h, H: Haystack + ?Sized> Matches<'r, 'h, H> {
+    #[inline]
+    pub(crate) fn new(re: &'r Regex, haystack: &'h H) -> Self {
+        Matches {
+            haystack,
+            searcher: Searcher::new(re, haystack.as_bytes()),
+        }
+    }
+}
+
+impl<'r, 'h, H: Haystack + ?Sized> CaptureMatches<'r, 'h, H> {
+    #[inline]
+    pub(crate) fn new(re: &'r Regex, haystack: &'h H) -> Self {
+        CaptureMatches {
+            haystack,
+            searcher: Searcher::new(re, haystack.as_bytes()),
+        }
+    }
+}
+
+impl<H: ?Sized> Clone for Matches<'_, '_, H> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Matches {
+            haystack: self.haystack,
+            searcher: self.searcher.clone(),
+        }
+    }
+}
+
+impl<H: ?Sized> Clone for CaptureMatches<'_, '_, H> {
+    #[inline]
+    fn clone(&self) -> Self {
+        CaptureMatches {
+            haystack: self.haystack,
+            searcher: self.searcher.clone(),
+        }
+    }
+}
+
+impl<'r, 'h, H: ?Sized> Iterator for Matches<'r, 'h, H> {
+    type Item = Match<'h, H>;
+
+    fn next(&mut self) -> Option<Match<'h, H>> {
+        let mut sub = Sub::new(2);
+        let (start, end) = self.searcher.next(&mut sub)?;
+        Some(Match::new(self.haystack, start, end))
+    }
+}
+
+impl<'r, 'h, H: ?Sized> Iterator for CaptureMatches<'r, 'h, H> {
+    type Item = Captures<'h, H>;
+
+    fn next(&mut self) -> Option<Captures<'h, H>> {
+        let mut sub = Sub::new(self.searcher.re.prog.nsub());
+        self.searcher.next(&mut sub)?;
+        Some(self.searcher.re.new_captures(self.haystack, sub))
+    }
+}
+
+impl<'r, 'h> Iterator for Split<'r, 'h> {
+    type Item = &'h str;
+
+    fn next(&mut self) -> Option<&'h str> {
+        let last = self.last?;
+        let haystack = self.matches.haystack;
+        match self.matches.next() {
+            Some(m) => {
+                self.last = Some(m.end());
+                Some(&haystack[last..m.start()])
+            }
+            None => {
+                self.last = None;
+                Some(&haystack[last..])
+            }
+        }
+    }
+}
+
+impl<'r, 'h> Iterator for SplitN<'r, 'h> {
+    type Item = &'h str;
+
+    fn next(&mut self) -> Option<&'h str> {
+        if self.limit == 0 {
+            return None;
+        }
+        self.limit -= 1;
+        if self.limit == 0 {
+            let last = self.split.last.take()?;
+            return Some(&self.split.matches.haystack[last..]);
+        }
+        self.split.next()
+    }
+}
+
+impl<'h, H: ?Sized> Match<'h, H> {
+    #[inline]
+    fn new(haystack: &'h H, start: usize, end: usize) -> Self {
+        Match {
+            haystack,
+            start,
+            end,
+        }
+    }
+
+    /// Returns the byte offset of the start of the match.
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the byte offset of the end of the match.
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+impl<'h> Match<'h> {
+    /// Returns the matched text.
+    #[inline]
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.start..self.end]
+    }
+}
+
+impl<'h> Match<'h, [u8]> {
+    /// Returns the matched bytes.
+    #[inline]
+    pub fn as_bytes(&self) -> &'h [u8] {
+        &self.haystack[self.start..self.end]
+    }
+}
+
+impl<H: ?Sized> Clone for Match<'_, H> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<H: ?Sized> Copy for Match<'_, H> {}
+
+impl<'h, H: ?Sized> Captures<'h, H> {
+    /// Returns the match for capture group i, or None if the group did not
+    /// participate in the match.
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<Match<'h, H>> {
+        if i >= self.len() {
+            return None;
+        }
+        let (start, end) = self.sub.group(i)?;
+        Some(Match::new(self.haystack, start, end))
+    }
+
+    /// Returns the match for the capture group with the given name, or None if
+    /// there is no such group or it did not participate in the match.
+    pub fn name(&self, name: &str) -> Option<Match<'h, H>> {
+        let i = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        self.get(i)
+    }
+
+    /// Returns the number of capture groups, including group 0.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Iterates over all capture groups in order, with None for groups that
+    /// did not participate in the match.
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match<'h, H>>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+}
+
+impl<H: ?Sized> Clone for Captures<'_, H> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Captures {
+            haystack: self.haystack,
+            sub: self.sub.clone(),
+            names: self.names.clone(),
+        }
+    }
+}
+
+impl<'h, H: Haystack + ?Sized> Index<usize> for Captures<'h, H> {
+    type Output = H;
+
+    /// Returns the text of capture group i.
+    ///
+    /// # Panics
+    ///
+    /// Panics if group i did not participate in the match.
+    fn index(&self, i: usize) -> &H {
+        self.get(i)
+            .map(|m| &self.haystack[m.range()])
+            .unwrap_or_else(|| panic!("no group at index {i}"))
+    }
+}
+
+impl<'h, H: Haystack + ?Sized> Index<&str> for Captures<'h, H> {
+    type Output = H;
+
+    /// Returns the text of the capture group with the given name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no such group or it did not participate in the
+    /// match.
+    fn index(&self, name: &str) -> &H {
+        self.name(name)
+            .map(|m| &self.haystack[m.range()])
+            .unwrap_or_else(|| panic!("no group named {name}"))
+    }
+}
+
+impl Haystack for str {
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl Haystack for [u8] {
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Display for Regex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
@@ -3,37 +3,118 @@
 // Use of this source code is governed by a BSD-style
 // license that can be found in the LICENSE file.
 
-use std::convert::Infallible;
 use std::fmt::{self, Display, Formatter};
+use std::mem;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use lalrpop_util::ParseError;
-
-use crate::lex::{Lexer, Token};
+use crate::lex::{Flags, Lexer};
+use crate::onepass::OnePassTable;
 use crate::parse::RegexpParser;
+use crate::{unicode, Anchored, Budget, Class, Error, Input, Unit};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Regexp {
     Alt(Box<Regexp>, Box<Regexp>),
     Cat(Box<Regexp>, Box<Regexp>),
     Lit(char),
-    Dot,
-    Paren(usize, Box<Regexp>),
+    Dot(/*dot_nl*/ bool),
+    Class(Class),
+    Assert(Assertion),
+    Paren(usize, /*name*/ Option<String>, Box<Regexp>),
     Quest(/*greedy*/ bool, Box<Regexp>),
     Star(/*greedy*/ bool, Box<Regexp>),
     Plus(/*greedy*/ bool, Box<Regexp>),
+    Repeat(
+        /*greedy*/ bool,
+        /*min*/ u32,
+        /*max*/ Option<u32>,
+        Box<Regexp>,
+    ),
 }
 
-impl Regexp {
-    pub fn parse(s: &str) -> Result<Box<Regexp>, ParseError<usize, Token, Infallible>> {
-        let mut re = RegexpParser::new().parse(Lexer::new(s))?;
+/// A configurable regexp parser.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Parser {
+    flags: Flags,
+}
+
+impl Parser {
+    #[inline]
+    pub fn new() -> Self {
+        Parser {
+            flags: Flags::new(),
+        }
+    }
+
+    /// Sets whether letters match both their upper and lower case, as with
+    /// the `i` flag. Disabled by default.
+    #[inline]
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut Self {
+        self.flags.case_insensitive = yes;
+        self
+    }
+
+    /// Sets whether `.` matches `\n`, as with the `s` flag. Disabled by
+    /// default.
+    #[inline]
+    pub fn dot_matches_new_line(&mut self, yes: bool) -> &mut Self {
+        self.flags.dot_nl = yes;
+        self
+    }
+
+    /// Sets whether `^` and `$` match at the start and end of lines, as with
+    /// the `m` flag. Disabled by default.
+    #[inline]
+    pub fn multi_line(&mut self, yes: bool) -> &mut Self {
+        self.flags.multi_line = yes;
+        self
+    }
+
+    /// Sets whether whitespace is ignored and `#` begins a comment, as with
+    /// the `x` flag. Disabled by default.
+    #[inline]
+    pub fn verbose(&mut self, yes: bool) -> &mut Self {
+        self.flags.verbose = yes;
+        self
+    }
+
+    /// Sets whether the Perl classes `\d`, `\w`, and `\s`, word boundaries,
+    /// and case folding use their Unicode definitions or ASCII-only
+    /// definitions. Enabled by default.
+    #[inline]
+    pub fn unicode(&mut self, yes: bool) -> &mut Self {
+        self.flags.unicode = yes;
+        self
+    }
+
+    pub fn parse(&self, s: &str) -> Result<Box<Regexp>, Error> {
+        let mut re = RegexpParser::new()
+            .parse(Lexer::new(s, self.flags))
+            .map_err(|err| Error::from_parse(err, s))?;
         re.number_parens(1);
         Ok(re)
     }
+}
 
+impl Default for Parser {
+    #[inline]
+    fn default() -> Self {
+        Parser::new()
+    }
+}
+
+impl Regexp {
+    #[inline]
+    pub fn parse(s: &str) -> Result<Box<Regexp>, Error> {
+        Parser::new().parse(s)
+    }
+
+    /// Wraps the regexp in group 0 preceded by `.*?`, so that even an anchored
+    /// search of the compiled program is unanchored.
     pub fn unanchored(self: Box<Self>) -> Box<Self> {
-        let paren = Box::new(Regexp::Paren(0, self));
-        let dot_star = Box::new(Regexp::Star(false, Box::new(Regexp::Dot)));
+        let paren = Box::new(Regexp::Paren(0, None, self));
+        let dot_star = Box::new(Regexp::Star(false, Box::new(Regexp::Dot(true))));
         Box::new(Regexp::Cat(dot_star, paren))
     }
 
@@ -43,17 +124,18 @@ impl Regexp {
                 next = left.number_parens(next);
                 right.number_parens(next)
             }
-            Regexp::Lit(_) | Regexp::Dot => next,
-            Regexp::Paren(id, inner) => {
+            Regexp::Lit(_) | Regexp::Dot(_) | Regexp::Class(_) | Regexp::Assert(_) => next,
+            Regexp::Paren(id, _, inner) => {
                 if *id == usize::MAX {
                     *id = next;
                     next += 1;
                 }
                 inner.number_parens(next)
             }
-            Regexp::Quest(_, inner) | Regexp::Star(_, inner) | Regexp::Plus(_, inner) => {
-                inner.number_parens(next)
-            }
+            Regexp::Quest(_, inner)
+            | Regexp::Star(_, inner)
+            | Regexp::Plus(_, inner)
+            | Regexp::Repeat(_, _, _, inner) => inner.number_parens(next),
         }
     }
 }
@@ -65,18 +147,112 @@ pub enum Inst {
     Jmp(usize),
     Split(usize, usize),
     Any,
+    AnyNotNL,
+    Class(Class),
+    /// A byte from lo to hi, in a program compiled to UTF-8.
+    ByteRange(u8, u8),
+    Assert(Assertion),
     Save(usize),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// A zero-width assertion.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Assertion {
+    /// Matches at the start of the haystack (`^` or `\A`).
+    StartText,
+    /// Matches at the end of the haystack (`$` or `\z`).
+    EndText,
+    /// Matches at the start of a line (`^` in multi-line mode).
+    StartLine,
+    /// Matches at the end of a line (`$` in multi-line mode).
+    EndLine,
+    /// Matches between a word char and a non-word char (`\b`).
+    WordBoundary(/*unicode*/ bool),
+    /// Matches between two word chars or two non-word chars (`\B`).
+    NotWordBoundary(/*unicode*/ bool),
+}
+
+impl Assertion {
+    /// Reports whether the assertion holds at the byte offset in s.
+    pub fn matches(self, s: &[u8], offset: usize) -> bool {
+        match self {
+            Assertion::StartText => offset == 0,
+            Assertion::EndText => offset == s.len(),
+            Assertion::StartLine => offset == 0 || s[offset - 1] == b'\n',
+            Assertion::EndLine => offset == s.len() || s[offset] == b'\n',
+            Assertion::WordBoundary(unicode) => is_word_boundary(s, offset, unicode),
+            Assertion::NotWordBoundary(unicode) => !is_word_boundary(s, offset, unicode),
+        }
+    }
+}
+
+fn is_word_boundary(s: &[u8], offset: usize, unicode: bool) -> bool {
+    let is_word = |unit: Option<Unit>| {
+        unit.and_then(Unit::as_char)
+            .is_some_and(|ch| unicode::is_word_char(ch, unicode))
+    };
+    is_word(unit_before(s, offset)) != is_word(unit_after(s, offset))
+}
+
+#[inline]
+fn unit_before(s: &[u8], offset: usize) -> Option<Unit> {
+    Unit::decode_last(&s[..offset]).map(|(unit, _)| unit)
+}
+
+#[inline]
+fn unit_after(s: &[u8], offset: usize) -> Option<Unit> {
+    Unit::decode(&s[offset..]).map(|(unit, _)| unit)
+}
+
+/// A compiled program. It begins with a `.*?` loop at pc 0 for unanchored
+/// searches, followed by the regexp itself, where anchored searches begin.
+#[derive(Clone, Debug)]
 pub struct Prog {
+    // Identifies the program for caches, and is shared by its clones.
+    id: u64,
     insts: Vec<Inst>,
+    start: usize,
     nsub: usize,
+    names: Vec<Option<String>>,
+    utf8: bool,
+    // Whether an unanchored search can find an empty match within a char,
+    // which is derived from the instructions.
+    empty_within_char: bool,
+    one_pass: Option<Box<OnePassTable>>,
 }
 
 impl Prog {
-    pub(crate) fn new(insts: Vec<Inst>, nsub: usize) -> Self {
-        Prog { insts, nsub }
+    pub(crate) fn new(
+        insts: Vec<Inst>,
+        start: usize,
+        nsub: usize,
+        names: Vec<Option<String>>,
+        utf8: bool,
+    ) -> Self {
+        debug_assert_eq!(names.len(), nsub / 2);
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let mut prog = Prog {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            insts,
+            start,
+            nsub,
+            names,
+            utf8,
+            empty_within_char: false,
+            one_pass: None,
+        };
+        prog.empty_within_char = prog.utf8 && prog.matches_empty_at_assertion();
+        prog.one_pass = OnePassTable::new(&prog).map(Box::new);
+        prog
+    }
+
+    /// Returns the pc where a search with the given anchoring begins.
+    #[inline]
+    pub fn start(&self, anchored: Anchored) -> usize {
+        match anchored {
+            Anchored::Yes => self.start,
+            Anchored::No => 0,
+        }
     }
 
     #[inline]
@@ -93,30 +269,150 @@ impl Prog {
     pub fn nsub(&self) -> usize {
         self.nsub
     }
+
+    /// Returns the names of the capture groups, indexed by group number.
+    #[inline]
+    pub fn names(&self) -> &[Option<String>] {
+        &self.names
+    }
+
+    /// Returns the number of the capture group with the given name.
+    pub fn group_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n.as_deref() == Some(name))
+    }
+
+    /// Reports whether the program was compiled to UTF-8 (see
+    /// [`Compiler::utf8`](crate::Compiler::utf8)), so that it consumes one
+    /// byte at a time.
+    #[inline]
+    pub fn is_utf8(&self) -> bool {
+        self.utf8
+    }
+
+    /// Reports whether the program is one-pass, so that anchored searches can
+    /// be run with [`OnePass`](crate::OnePass).
+    #[inline]
+    pub fn is_one_pass(&self) -> bool {
+        self.one_pass.is_some()
+    }
+
+    #[inline]
+    pub(crate) fn one_pass(&self) -> Option<&OnePassTable> {
+        self.one_pass.as_deref()
+    }
+
+    // Reports whether an unanchored search can find an empty match within a
+    // char. The prefix of a program compiled to UTF-8 consumes one byte at a
+    // time, so a match can start within a char, but only an empty match that
+    // depends on an assertion can end there too: without an assertion, an
+    // empty match would already start at the beginning of the span.
+    #[inline]
+    pub(crate) fn matches_empty_within_char(&self) -> bool {
+        self.empty_within_char
+    }
+
+    // Reports whether the program can match without consuming and contains
+    // an assertion, which is a cheap superset of the programs whose empty
+    // matches depend on an assertion.
+    fn matches_empty_at_assertion(&self) -> bool {
+        if !self
+            .insts
+            .iter()
+            .any(|inst| matches!(inst, Inst::Assert(_)))
+        {
+            return false;
+        }
+        let mut visited = vec![false; self.insts.len()];
+        let mut stack = vec![self.start];
+        while let Some(pc) = stack.pop() {
+            if mem::replace(&mut visited[pc], true) {
+                continue;
+            }
+            match self.insts[pc] {
+                Inst::Match => return true,
+                Inst::Jmp(x) => stack.push(x),
+                Inst::Split(x, y) => stack.extend([y, x]),
+                Inst::Assert(_) | Inst::Save(_) => stack.push(pc + 1),
+                _ => {}
+            }
+        }
+        false
+    }
+
+    // Returns an id that is unique to the program and its clones, so caches
+    // can cheaply tell whether they were built for it.
+    #[inline]
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
 }
 
+// Programs are compared by their contents, not their ids.
+impl PartialEq for Prog {
+    fn eq(&self, other: &Self) -> bool {
+        self.insts == other.insts
+            && self.start == other.start
+            && self.nsub == other.nsub
+            && self.names == other.names
+            && self.utf8 == other.utf8
+    }
+}
+
+impl Eq for Prog {}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct VM<'i, 's> {
     pub insts: &'i [Inst],
     pub pc: usize,
-    s: &'s str,
+    s: &'s [u8],
     pub offset: usize,
+    // The pc and offset that the search begins at, restored by reset.
+    start_pc: usize,
+    pub(crate) start: usize,
+    // The end of the searched span. Chars are not consumed past it, but
+    // assertions see the whole haystack.
+    pub(crate) end: usize,
+    pub(crate) budget: Budget,
+    // Whether every byte is a unit by itself.
+    utf8: bool,
     pub debug: bool,
 }
 
 impl<'i, 's> VM<'i, 's> {
+    /// Creates a VM for an anchored search of the whole haystack.
     #[inline]
     pub fn new(prog: &'i Prog, s: &'s str, debug: bool) -> Self {
+        VM::with_input(prog, &Input::new(s).anchored(Anchored::Yes), debug)
+    }
+
+    /// Creates a VM for a search of the input's span with its anchoring and
+    /// budget.
+    #[inline]
+    pub fn with_input(prog: &'i Prog, input: &Input<'s>, debug: bool) -> Self {
+        let start_pc = prog.start(input.get_anchored());
         VM {
             insts: &prog.insts,
-            pc: 0,
-            s,
-            offset: 0,
+            pc: start_pc,
+            s: input.haystack(),
+            offset: input.start(),
+            start_pc,
+            start: input.start(),
+            end: input.end(),
+            budget: input.get_budget().clone(),
+            utf8: prog.utf8,
             debug,
         }
     }
 
-    pub fn next_inst(&mut self) -> Option<&Inst> {
+    /// Sets the budget for the match methods, which give up when it is
+    /// exhausted.
+    #[inline]
+    pub fn budget(&mut self, budget: Budget) -> &mut Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn next_inst(&mut self) -> Option<&'i Inst> {
         let inst = self.insts.get(self.pc);
         if self.debug {
             println!("{self}");
@@ -127,23 +423,40 @@ impl<'i, 's> VM<'i, 's> {
         inst
     }
 
+    /// Consumes the unit at the current offset, which is a byte when the
+    /// program was compiled to UTF-8.
     #[inline]
-    pub fn next_char(&mut self) -> Option<char> {
-        let mut chars = self.s[self.offset..].chars();
-        let ch = chars.next();
-        self.offset = self.s.len() - chars.as_str().len();
-        ch
+    pub fn next_unit(&mut self) -> Option<Unit> {
+        let rest = &self.s[self.offset..self.end];
+        let (unit, len) = if self.utf8 {
+            (Unit::Byte(*rest.first()?), 1)
+        } else {
+            Unit::decode(rest)?
+        };
+        self.offset += len;
+        Some(unit)
     }
 
     #[inline]
-    pub fn inst(&self) -> Option<&Inst> {
+    pub fn haystack(&self) -> &'s [u8] {
+        self.s
+    }
+
+    /// Reports whether the assertion holds at the current offset.
+    #[inline]
+    pub fn check(&self, assertion: Assertion) -> bool {
+        assertion.matches(self.s, self.offset)
+    }
+
+    #[inline]
+    pub fn inst(&self) -> Option<&'i Inst> {
         self.insts.get(self.pc)
     }
 
     #[inline]
     pub fn reset(&mut self) {
-        self.pc = 0;
-        self.offset = 0;
+        self.pc = self.start_pc;
+        self.offset = self.start;
     }
 }
 
@@ -155,7 +468,7 @@ pub struct Sub {
 impl Sub {
     #[inline]
     pub fn new(nsub: usize) -> Self {
-        debug_assert!(nsub % 2 == 0);
+        debug_assert!(nsub.is_multiple_of(2));
         Sub {
             sub: vec![usize::MAX; nsub].into(),
         }
@@ -173,7 +486,7 @@ impl Sub {
 
     #[inline]
     pub fn update(mut self: Rc<Self>, n: usize, offset: usize) -> Rc<Self> {
-        if n < self.sub.len() {
+        if n >= self.sub.len() {
             return self;
         }
         let sub = Rc::make_mut(&mut self);
@@ -181,11 +494,32 @@ impl Sub {
         self
     }
 
+    /// Returns the start and end offsets of capture group n, if it matched.
+    #[inline]
+    pub fn group(&self, n: usize) -> Option<(usize, usize)> {
+        match (self.sub.get(2 * n)?, self.sub.get(2 * n + 1)?) {
+            (&usize::MAX, _) | (_, &usize::MAX) => None,
+            (&start, &end) => Some((start, end)),
+        }
+    }
+
+    /// Returns the start and end offsets of the capture group with the given
+    /// name in prog, if it matched.
+    #[inline]
+    pub fn name(&self, prog: &Prog, name: &str) -> Option<(usize, usize)> {
+        self.group(prog.group_index(name)?)
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.sub.len()
     }
 
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.sub.is_empty()
+    }
+
     #[inline]
     pub fn reset(&mut self) {
         self.sub.fill(usize::MAX);
@@ -198,14 +532,28 @@ impl Display for Regexp {
             Regexp::Alt(left, right) => write!(f, "Alt({left}, {right})"),
             Regexp::Cat(left, right) => write!(f, "Cat({left}, {right})"),
             Regexp::Lit(ch) => write!(f, "Lit({ch})"),
-            Regexp::Dot => write!(f, "Dot"),
-            Regexp::Paren(n, inner) => write!(f, "Paren({n}, {inner})"),
+            Regexp::Dot(false) => write!(f, "Dot"),
+            Regexp::Dot(true) => write!(f, "DotNL"),
+            Regexp::Class(class) => write!(f, "Class({class})"),
+            Regexp::Assert(assertion) => write!(f, "Assert({assertion})"),
+            Regexp::Paren(n, None, inner) => write!(f, "Paren({n}, {inner})"),
+            Regexp::Paren(n, Some(name), inner) => write!(f, "Paren({n}, {name}, {inner})"),
             Regexp::Quest(true, inner) => write!(f, "Quest({inner})"),
             Regexp::Quest(false, inner) => write!(f, "NgQuest({inner})"),
             Regexp::Star(true, inner) => write!(f, "Star({inner})"),
             Regexp::Star(false, inner) => write!(f, "NgStar({inner})"),
             Regexp::Plus(true, inner) => write!(f, "Plus({inner})"),
             Regexp::Plus(false, inner) => write!(f, "NgPlus({inner})"),
+            Regexp::Repeat(greedy, min, max, inner) => {
+                if !greedy {
+                    write!(f, "Ng")?;
+                }
+                match max {
+                    Some(max) if max == min => write!(f, "Repeat{{{min}}}({inner})"),
+                    Some(max) => write!(f, "Repeat{{{min},{max}}}({inner})"),
+                    None => write!(f, "Repeat{{{min},}}({inner})"),
+                }
+            }
         }
     }
 }
@@ -218,11 +566,30 @@ impl Display for Inst {
             Inst::Jmp(x) => write!(f, "jmp {x}"),
             Inst::Split(x, y) => write!(f, "split {x}, {y}"),
             Inst::Any => write!(f, "any"),
+            Inst::AnyNotNL => write!(f, "anynotnl"),
+            Inst::Class(class) => write!(f, "class {class}"),
+            Inst::ByteRange(lo, hi) => write!(f, "byte {lo:02x}-{hi:02x}"),
+            Inst::Assert(assertion) => write!(f, "assert {assertion}"),
             Inst::Save(n) => write!(f, "save {n}"),
         }
     }
 }
 
+impl Display for Assertion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Assertion::StartText => "\\A",
+            Assertion::EndText => "\\z",
+            Assertion::StartLine => "(?m:^)",
+            Assertion::EndLine => "(?m:$)",
+            Assertion::WordBoundary(true) => "\\b",
+            Assertion::NotWordBoundary(true) => "\\B",
+            Assertion::WordBoundary(false) => "(?-u:\\b)",
+            Assertion::NotWordBoundary(false) => "(?-u:\\B)",
+        })
+    }
+}
+
 impl Display for Prog {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for (pc, inst) in self.insts.iter().enumerate() {
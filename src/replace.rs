@@ -0,0 +1,154 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::borrow::Cow;
+
+use crate::{Captures, Regex};
+
+/// A replacement for matches of a regex.
+///
+/// It is implemented for template strings, which are expanded with
+/// [`Captures::expand`], and for closures that receive the captures and return
+/// the replacement text.
+pub trait Replacer {
+    /// Appends the replacement for one match to dst.
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String);
+}
+
+impl Replacer for &str {
+    #[inline]
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+}
+
+impl Replacer for String {
+    #[inline]
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+}
+
+impl Replacer for &String {
+    #[inline]
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+}
+
+impl<F, T> Replacer for F
+where
+    F: FnMut(&Captures<'_>) -> T,
+    T: AsRef<str>,
+{
+    #[inline]
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str(self(caps).as_ref());
+    }
+}
+
+impl Regex {
+    /// Replaces the leftmost-first match in the haystack.
+    #[inline]
+    pub fn replace<'h, R: Replacer>(&self, haystack: &'h str, rep: R) -> Cow<'h, str> {
+        self.replacen(haystack, 1, rep)
+    }
+
+    /// Replaces all non-overlapping matches in the haystack.
+    #[inline]
+    pub fn replace_all<'h, R: Replacer>(&self, haystack: &'h str, rep: R) -> Cow<'h, str> {
+        self.replacen(haystack, 0, rep)
+    }
+
+    /// Replaces at most limit non-overlapping matches in the haystack, or all
+    /// of them when limit is 0. The haystack is borrowed when nothing matched.
+    pub fn replacen<'h, R: Replacer>(
+        &self,
+        haystack: &'h str,
+        limit: usize,
+        mut rep: R,
+    ) -> Cow<'h, str> {
+        let mut caps_iter = self.captures_iter(haystack);
+        let Some(first) = caps_iter.next() else {
+            return Cow::Borrowed(haystack);
+        };
+        let mut dst = String::with_capacity(haystack.len());
+        let mut last = 0;
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        for caps in [first].into_iter().chain(caps_iter).take(limit) {
+            let m = caps.get(0).unwrap();
+            dst.push_str(&haystack[last..m.start()]);
+            rep.replace_append(&caps, &mut dst);
+            last = m.end();
+        }
+        dst.push_str(&haystack[last..]);
+        Cow::Owned(dst)
+    }
+}
+
+impl Captures<'_> {
+    /// Expands a template string with the text of capture groups and appends
+    /// it to dst.
+    ///
+    /// `$N` and `${N}` are replaced with group N, `${name}` with the group
+    /// with that name, and `$$` with a literal `$`. A group that does not
+    /// exist or did not participate in the match is replaced with the empty
+    /// string. A `$` that does not begin a reference is kept as is.
+    pub fn expand(&self, template: &str, dst: &mut String) {
+        let mut rest = template;
+        while let Some(i) = rest.find('$') {
+            dst.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+            if let Some(r) = rest.strip_prefix('$') {
+                dst.push('$');
+                rest = r;
+                continue;
+            }
+            let Some((group, r)) = parse_group_ref(rest) else {
+                dst.push('$');
+                continue;
+            };
+            let m = match group {
+                GroupRef::Number(n) => self.get(n),
+                GroupRef::Name(name) => self.name(name),
+            };
+            if let Some(m) = m {
+                dst.push_str(m.as_str());
+            }
+            rest = r;
+        }
+        dst.push_str(rest);
+    }
+}
+
+enum GroupRef<'a> {
+    Number(usize),
+    Name(&'a str),
+}
+
+// Parses a group reference after a `$`, returning it and the rest of the
+// template.
+fn parse_group_ref(s: &str) -> Option<(GroupRef<'_>, &str)> {
+    if let Some(s) = s.strip_prefix('{') {
+        let end = s.find('}')?;
+        let (name, rest) = (&s[..end], &s[end + 1..]);
+        if name.is_empty() {
+            return None;
+        }
+        let group = if name.bytes().all(|b| b.is_ascii_digit()) {
+            GroupRef::Number(name.parse().unwrap_or(usize::MAX))
+        } else {
+            GroupRef::Name(name)
+        };
+        Some((group, rest))
+    } else {
+        let end = s.bytes().take_while(u8::is_ascii_digit).count();
+        if end == 0 {
+            return None;
+        }
+        let n = s[..end].parse().unwrap_or(usize::MAX);
+        Some((GroupRef::Number(n), &s[end..]))
+    }
+}
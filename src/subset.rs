@@ -0,0 +1,341 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+// Subset construction of DFA states from a program, shared by the lazy and
+// compiled DFAs.
+//
+// A DFA state is a set of pcs before following empty transitions, along with
+// context bits for the unit before the position, which the assertions depend
+// on. A transition on a unit follows the empty transitions, with the unit as
+// the context after the position, then consumes the unit.
+
+use std::mem;
+
+use crate::{unicode, Assertion, Inst, Prog, Unit};
+
+// Context bits of the chars around a position.
+const CTX_TEXT: u8 = 1 << 0; // at the start or end of the haystack
+const CTX_NL: u8 = 1 << 1; // a newline
+const CTX_WORD: u8 = 1 << 2; // a Unicode word char
+const CTX_ASCII_WORD: u8 = 1 << 3; // an ASCII word char
+
+// The number of distinct values of the context bits.
+pub(crate) const NUM_CTX: usize = 1 << 4;
+
+// The first key of a byte unit. The key of a char is its scalar value and the
+// key of a byte b is BYTE_KEY + b, so the keys of all units are ordered. In a
+// program compiled to UTF-8, every unit is a byte and its key is the byte.
+const BYTE_KEY: u32 = 0x110000;
+
+// The units partitioned into equivalence classes, such that units in the same
+// class are indistinguishable to the program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Alphabet {
+    // The sorted keys that start every class but the first, which starts at
+    // '\0'. Chars and bytes are always in separate classes.
+    boundaries: Vec<u32>,
+    // The context bits that the assertions in the program depend on.
+    ctx_mask: u8,
+    // Whether the program was compiled to UTF-8.
+    utf8: bool,
+}
+
+impl Alphabet {
+    pub(crate) fn new(prog: &Prog) -> Self {
+        let utf8 = prog.is_utf8();
+        let mut boundaries = Vec::new();
+        if !utf8 {
+            boundaries.push(BYTE_KEY);
+        }
+        let mut push_range = |lo: u32, hi: u32| {
+            boundaries.push(lo);
+            boundaries.push(hi + 1);
+        };
+        let mut ctx_mask = 0;
+        for inst in prog.insts() {
+            match *inst {
+                Inst::Char(ch) => push_range(ch as u32, ch as u32),
+                Inst::Class(ref class) => {
+                    for &(lo, hi) in class.ranges() {
+                        push_range(lo as u32, hi as u32);
+                    }
+                    for (lo, hi) in class.byte_ranges() {
+                        push_range(BYTE_KEY + lo as u32, BYTE_KEY + hi as u32);
+                    }
+                }
+                Inst::ByteRange(lo, hi) if lo <= hi => push_range(lo as u32, hi as u32),
+                Inst::AnyNotNL => push_range('\n' as u32, '\n' as u32),
+                Inst::Assert(assertion) => ctx_mask |= ctx_bits(assertion),
+                _ => {}
+            }
+        }
+        // The keys of ASCII chars and bytes are the same.
+        if ctx_mask & CTX_NL != 0 {
+            push_range('\n' as u32, '\n' as u32);
+        }
+        if ctx_mask & CTX_WORD != 0 && !utf8 {
+            for &(lo, hi) in unicode::word().ranges() {
+                push_range(lo as u32, hi as u32);
+            }
+        }
+        if ctx_mask & CTX_ASCII_WORD != 0 {
+            for (lo, hi) in [('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')] {
+                push_range(lo as u32, hi as u32);
+            }
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        // The first class already starts at '\0', and the last byte ends a
+        // program compiled to UTF-8.
+        boundaries.retain(|&b| b != 0 && (!utf8 || b <= 0xFF));
+        Alphabet {
+            boundaries,
+            ctx_mask,
+            utf8,
+        }
+    }
+
+    // Creates an alphabet from its parts, or None if the boundaries are not
+    // strictly increasing and nonzero, do not separate chars from bytes or
+    // are not bytes for a program compiled to UTF-8, or the mask has unknown
+    // bits.
+    pub(crate) fn from_parts(boundaries: Vec<u32>, ctx_mask: u8, utf8: bool) -> Option<Self> {
+        let valid = boundaries.first().is_none_or(|&b| b > 0)
+            && boundaries.windows(2).all(|w| w[0] < w[1])
+            && if utf8 {
+                boundaries.last().is_none_or(|&b| b <= 0xFF)
+            } else {
+                boundaries.binary_search(&BYTE_KEY).is_ok()
+            }
+            && (ctx_mask as usize) < NUM_CTX;
+        let alphabet = Alphabet {
+            boundaries,
+            ctx_mask,
+            utf8,
+        };
+        (valid && alphabet.ctx_is_exact()).then_some(alphabet)
+    }
+
+    #[inline]
+    pub(crate) fn boundaries(&self) -> &[u32] {
+        &self.boundaries
+    }
+
+    #[inline]
+    pub(crate) fn ctx_mask(&self) -> u8 {
+        self.ctx_mask
+    }
+
+    #[inline]
+    pub(crate) fn is_utf8(&self) -> bool {
+        self.utf8
+    }
+
+    // Reports whether the context bits of every unit are determined by its
+    // class. They are not for Unicode word boundaries in a program compiled to
+    // UTF-8, since a byte of an encoding is not a char.
+    #[inline]
+    pub(crate) fn ctx_is_exact(&self) -> bool {
+        !self.utf8 || self.ctx_mask & CTX_WORD == 0
+    }
+
+    // Returns the number of classes.
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.boundaries.len() + 1
+    }
+
+    #[inline]
+    pub(crate) fn class(&self, unit: Unit) -> usize {
+        let key = match unit {
+            Unit::Char(ch) => ch as u32,
+            Unit::Byte(b) if self.utf8 => b as u32,
+            Unit::Byte(b) => BYTE_KEY + b as u32,
+        };
+        self.boundaries.partition_point(|&b| b <= key)
+    }
+
+    // Returns a unit in the class, or None if it only contains surrogates or
+    // bytes that are never a unit by themselves.
+    pub(crate) fn representative(&self, class: usize) -> Option<Unit> {
+        let start = if class == 0 {
+            0
+        } else {
+            self.boundaries[class - 1]
+        };
+        let end = self.boundaries.get(class).copied().unwrap_or(u32::MAX);
+        if self.utf8 {
+            return Some(Unit::Byte(start as u8));
+        }
+        if start >= BYTE_KEY {
+            // Bytes below 0x80 are always chars.
+            let key = start.max(BYTE_KEY + 0x80);
+            return (key < end && key <= BYTE_KEY + 0xFF)
+                .then(|| Unit::Byte((key - BYTE_KEY) as u8));
+        }
+        let ch = char::from_u32(start).or_else(|| char::from_u32(start.max(0xE000)))?;
+        ((ch as u32) < end).then_some(Unit::Char(ch))
+    }
+
+    // Decodes the unit at the start of the bytes, with its length.
+    #[inline]
+    pub(crate) fn decode(&self, bytes: &[u8]) -> Option<(Unit, usize)> {
+        if self.utf8 {
+            return bytes.first().map(|&b| (Unit::Byte(b), 1));
+        }
+        Unit::decode(bytes)
+    }
+
+    // Decodes the unit at the end of the bytes, with its length.
+    #[inline]
+    pub(crate) fn decode_last(&self, bytes: &[u8]) -> Option<(Unit, usize)> {
+        if self.utf8 {
+            return bytes.last().map(|&b| (Unit::Byte(b), 1));
+        }
+        Unit::decode_last(bytes)
+    }
+
+    // Returns the context bits of a unit that matter to the program, where
+    // None is the start or end of the haystack.
+    #[inline]
+    pub(crate) fn ctx(&self, unit: Option<Unit>) -> u8 {
+        self.ctx_mask & ctx_of(unit)
+    }
+}
+
+// Follows empty transitions with reusable buffers.
+#[derive(Clone, Debug)]
+pub(crate) struct Closure {
+    visited: Vec<bool>,
+    stack: Vec<usize>,
+}
+
+impl Closure {
+    #[inline]
+    pub(crate) fn new(prog: &Prog) -> Self {
+        Closure {
+            visited: vec![false; prog.insts().len()],
+            stack: Vec::new(),
+        }
+    }
+
+    // Follows the empty transitions from the pcs, with prev and next as the
+    // context of the units before and after the position, then consumes the
+    // unit. Returns whether the program matches at the position and the sorted
+    // pcs after consuming the unit.
+    pub(crate) fn step(
+        &mut self,
+        insts: &[Inst],
+        pcs: &[usize],
+        prev: u8,
+        next: u8,
+        unit: Option<Unit>,
+    ) -> (bool, Vec<usize>) {
+        self.visited.fill(false);
+        self.stack.clear();
+        self.stack.extend(pcs.iter().rev());
+        let mut matched = false;
+        let mut next_pcs = Vec::new();
+        while let Some(pc) = self.stack.pop() {
+            if mem::replace(&mut self.visited[pc], true) {
+                continue;
+            }
+            match insts[pc] {
+                Inst::Char(ch1) => {
+                    if unit == Some(Unit::Char(ch1)) {
+                        next_pcs.push(pc + 1);
+                    }
+                }
+                Inst::Any => {
+                    if matches!(unit, Some(Unit::Char(_))) {
+                        next_pcs.push(pc + 1);
+                    }
+                }
+                Inst::AnyNotNL => {
+                    if matches!(unit, Some(Unit::Char(ch)) if ch != '\n') {
+                        next_pcs.push(pc + 1);
+                    }
+                }
+                Inst::Class(ref class) => {
+                    if unit.is_some_and(|unit| class.matches(unit)) {
+                        next_pcs.push(pc + 1);
+                    }
+                }
+                Inst::ByteRange(lo, hi) => {
+                    if matches!(unit, Some(Unit::Byte(b)) if lo <= b && b <= hi) {
+                        next_pcs.push(pc + 1);
+                    }
+                }
+                Inst::Assert(assertion) => {
+                    if assertion_holds(assertion, prev, next) {
+                        self.stack.push(pc + 1);
+                    }
+                }
+                Inst::Match => matched = true,
+                Inst::Jmp(x) => self.stack.push(x),
+                Inst::Split(x, y) => {
+                    self.stack.push(y);
+                    self.stack.push(x);
+                }
+                Inst::Save(_) => self.stack.push(pc + 1),
+            }
+        }
+        next_pcs.sort_unstable();
+        next_pcs.dedup();
+        (matched, next_pcs)
+    }
+}
+
+// Returns the context bits that the assertion depends on.
+fn ctx_bits(assertion: Assertion) -> u8 {
+    match assertion {
+        Assertion::StartText | Assertion::EndText => CTX_TEXT,
+        Assertion::StartLine | Assertion::EndLine => CTX_TEXT | CTX_NL,
+        Assertion::WordBoundary(true) | Assertion::NotWordBoundary(true) => CTX_WORD,
+        Assertion::WordBoundary(false) | Assertion::NotWordBoundary(false) => CTX_ASCII_WORD,
+    }
+}
+
+// Returns the context bits of a unit, where None is the start or end of the
+// haystack. A byte other than ASCII is neither a newline nor a word char.
+fn ctx_of(unit: Option<Unit>) -> u8 {
+    match unit {
+        None => CTX_TEXT,
+        Some(Unit::Byte(b)) if b.is_ascii() => ctx_of(Some(Unit::Char(b as char))),
+        Some(Unit::Byte(_)) => 0,
+        Some(Unit::Char(ch)) => {
+            let mut ctx = 0;
+            if ch == '\n' {
+                ctx |= CTX_NL;
+            }
+            if unicode::is_word_char(ch, true) {
+                ctx |= CTX_WORD;
+            }
+            if unicode::is_word_char(ch, false) {
+                ctx |= CTX_ASCII_WORD;
+            }
+            ctx
+        }
+    }
+}
+
+// Reports whether the assertion holds between units with the context bits
+// prev and next.
+fn assertion_holds(assertion: Assertion, prev: u8, next: u8) -> bool {
+    match assertion {
+        Assertion::StartText => prev & CTX_TEXT != 0,
+        Assertion::EndText => next & CTX_TEXT != 0,
+        Assertion::StartLine => prev & (CTX_TEXT | CTX_NL) != 0,
+        Assertion::EndLine => next & (CTX_TEXT | CTX_NL) != 0,
+        Assertion::WordBoundary(true) => (prev & CTX_WORD != 0) != (next & CTX_WORD != 0),
+        Assertion::NotWordBoundary(true) => (prev & CTX_WORD != 0) == (next & CTX_WORD != 0),
+        Assertion::WordBoundary(false) => {
+            (prev & CTX_ASCII_WORD != 0) != (next & CTX_ASCII_WORD != 0)
+        }
+        Assertion::NotWordBoundary(false) => {
+            (prev & CTX_ASCII_WORD != 0) == (next & CTX_ASCII_WORD != 0)
+        }
+    }
+}
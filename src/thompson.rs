@@ -5,63 +5,119 @@
 
 use std::mem;
 
-use crate::{Inst, Sub, VM};
+use crate::{GaveUp, Inst, Sub, Unit, VM};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Thread {
     pc: usize,
+    // Only the start of the overall match is tracked, which is recorded by
+    // Save(0), rather than the full set of submatches. It is None until
+    // Save(0) runs, which it never does in a program without group 0.
+    start: Option<usize>,
 }
 
 impl Thread {
-    fn new(pc: usize) -> Self {
-        Thread { pc }
+    fn new(pc: usize, start: Option<usize>) -> Self {
+        Thread { pc, start }
     }
 }
 
 impl VM<'_, '_> {
-    pub fn match_thompsonvm(&mut self, sub_out: &mut Sub) -> bool {
+    pub fn match_thompsonvm(&mut self, sub_out: &mut Sub) -> Result<bool, GaveUp> {
+        let mut meter = self.budget.meter();
         let mut curr_threads = Vec::new();
         let mut next_threads = Vec::new();
         // visited replaces global gen (generation) in the original
         let mut visited = vec![false; self.insts.len()];
-        add_thread(&mut curr_threads, Thread::new(0), &mut visited, self.insts);
+        let s = self.haystack();
+        add_thread(
+            &mut curr_threads,
+            Thread::new(self.pc, None),
+            &mut visited,
+            self.insts,
+            s,
+            self.offset,
+        );
 
         sub_out.reset();
-        if sub_out.len() >= 1 {
-            sub_out.set(0, 0);
-        }
 
         let mut matched = false;
         loop {
             let offset = self.offset;
-            let ch = self.next_char();
-            if curr_threads.len() == 0 {
+            let unit = self.next_unit();
+            if curr_threads.is_empty() {
                 break;
             }
             visited.fill(false);
             for t in curr_threads.drain(..) {
+                meter.step()?;
                 let pc = t.pc;
                 match self.insts[pc] {
-                    Inst::Char(ch1) => {
-                        if ch == Some(ch1) {
-                            let t = Thread::new(pc + 1);
-                            add_thread(&mut next_threads, t, &mut visited, self.insts);
-                        }
+                    Inst::Char(ch) if unit == Some(Unit::Char(ch)) => {
+                        let t = Thread::new(pc + 1, t.start);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            s,
+                            self.offset,
+                        );
                     }
-                    Inst::Any => {
-                        if ch.is_some() {
-                            let t = Thread::new(pc + 1);
-                            add_thread(&mut next_threads, t, &mut visited, self.insts);
-                        }
+                    Inst::Any if matches!(unit, Some(Unit::Char(_))) => {
+                        let t = Thread::new(pc + 1, t.start);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            s,
+                            self.offset,
+                        );
+                    }
+                    Inst::AnyNotNL if matches!(unit, Some(Unit::Char(ch)) if ch != '\n') => {
+                        let t = Thread::new(pc + 1, t.start);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            s,
+                            self.offset,
+                        );
+                    }
+                    Inst::Class(ref class) if unit.is_some_and(|unit| class.matches(unit)) => {
+                        let t = Thread::new(pc + 1, t.start);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            s,
+                            self.offset,
+                        );
+                    }
+                    Inst::ByteRange(lo, hi) if matches!(unit, Some(Unit::Byte(b)) if lo <= b && b <= hi) =>
+                    {
+                        let t = Thread::new(pc + 1, t.start);
+                        add_thread(
+                            &mut next_threads,
+                            t,
+                            &mut visited,
+                            self.insts,
+                            s,
+                            self.offset,
+                        );
                     }
                     Inst::Match => {
-                        if sub_out.len() >= 2 {
+                        if let Some(start) = t.start.filter(|_| sub_out.len() >= 2) {
+                            sub_out.set(0, start);
                             sub_out.set(1, offset);
                         }
                         matched = true;
                         break;
                     }
-                    // Jmp, Split, Save handled in add_thread, so that
+                    // Jmp, Split, Save, Assert handled in add_thread, so that
                     // machine execution matches what a backtracker would do.
                     // This is discussed (but not shown as code) in
                     // Regular Expression Matching: the Virtual Machine Approach.
@@ -70,15 +126,22 @@ impl VM<'_, '_> {
             }
             mem::swap(&mut curr_threads, &mut next_threads);
             next_threads.clear();
-            if ch.is_none() {
+            if unit.is_none() {
                 break;
             }
         }
-        matched
+        Ok(matched)
     }
 }
 
-fn add_thread(l: &mut Vec<Thread>, t: Thread, visited: &mut [bool], insts: &[Inst]) {
+fn add_thread(
+    l: &mut Vec<Thread>,
+    t: Thread,
+    visited: &mut [bool],
+    insts: &[Inst],
+    s: &[u8],
+    offset: usize,
+) {
     let pc = t.pc;
     if visited[pc] {
         return; // already on list
@@ -87,12 +150,18 @@ fn add_thread(l: &mut Vec<Thread>, t: Thread, visited: &mut [bool], insts: &[Ins
     l.push(t);
 
     match &insts[pc] {
-        Inst::Jmp(x) => add_thread(l, Thread::new(*x), visited, insts),
+        Inst::Jmp(x) => add_thread(l, Thread::new(*x, t.start), visited, insts, s, offset),
         Inst::Split(x, y) => {
-            add_thread(l, Thread::new(*x), visited, insts);
-            add_thread(l, Thread::new(*y), visited, insts);
+            add_thread(l, Thread::new(*x, t.start), visited, insts, s, offset);
+            add_thread(l, Thread::new(*y, t.start), visited, insts, s, offset);
+        }
+        Inst::Save(n) => {
+            let start = if *n == 0 { Some(offset) } else { t.start };
+            add_thread(l, Thread::new(pc + 1, start), visited, insts, s, offset);
+        }
+        Inst::Assert(assertion) if assertion.matches(s, offset) => {
+            add_thread(l, Thread::new(pc + 1, t.start), visited, insts, s, offset);
         }
-        Inst::Save(_) => add_thread(l, Thread::new(pc + 1), visited, insts),
         _ => {}
     }
 }
@@ -0,0 +1,3981 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::sync::OnceLock;
+
+use crate::Class;
+
+/// Returns the class of Unicode decimal digits (general category Nd).
+pub fn decimal_number() -> &'static Class {
+    static CLASS: OnceLock<Class> = OnceLock::new();
+    CLASS.get_or_init(|| Class::from_ranges(DECIMAL_NUMBER.iter().copied()))
+}
+
+/// Returns the class of Unicode whitespace (the `White_Space` property).
+pub fn white_space() -> &'static Class {
+    static CLASS: OnceLock<Class> = OnceLock::new();
+    CLASS.get_or_init(|| Class::from_ranges(WHITE_SPACE.iter().copied()))
+}
+
+/// Returns the class of Unicode word characters, which are the alphabetic and
+/// numeric chars, as defined by `char::is_alphanumeric`, and `_`.
+pub fn word() -> &'static Class {
+    static CLASS: OnceLock<Class> = OnceLock::new();
+    CLASS.get_or_init(|| Class::from_ranges(WORD.iter().copied()))
+}
+
+/// Returns the simple case folding orbits, which are the sets of chars that
+/// are equivalent under case-insensitive matching. Each char with case
+/// variants is paired with the other chars in its orbit, sorted by char.
+#[inline]
+pub fn case_orbits() -> &'static [(char, &'static [char])] {
+    CASE_ORBITS
+}
+
+/// Reports whether ch is a word char, as matched by `\w`.
+#[inline]
+pub fn is_word_char(ch: char, unicode: bool) -> bool {
+    if ch.is_ascii() || !unicode {
+        ch.is_ascii_alphanumeric() || ch == '_'
+    } else {
+        word().contains(ch)
+    }
+}
+
+// Unicode 17.0.0 general category Nd.
+const DECIMAL_NUMBER: &[(char, char)] = &[
+    ('\u{30}', '\u{39}'),
+    ('\u{660}', '\u{669}'),
+    ('\u{6F0}', '\u{6F9}'),
+    ('\u{7C0}', '\u{7C9}'),
+    ('\u{966}', '\u{96F}'),
+    ('\u{9E6}', '\u{9EF}'),
+    ('\u{A66}', '\u{A6F}'),
+    ('\u{AE6}', '\u{AEF}'),
+    ('\u{B66}', '\u{B6F}'),
+    ('\u{BE6}', '\u{BEF}'),
+    ('\u{C66}', '\u{C6F}'),
+    ('\u{CE6}', '\u{CEF}'),
+    ('\u{D66}', '\u{D6F}'),
+    ('\u{DE6}', '\u{DEF}'),
+    ('\u{E50}', '\u{E59}'),
+    ('\u{ED0}', '\u{ED9}'),
+    ('\u{F20}', '\u{F29}'),
+    ('\u{1040}', '\u{1049}'),
+    ('\u{1090}', '\u{1099}'),
+    ('\u{17E0}', '\u{17E9}'),
+    ('\u{1810}', '\u{1819}'),
+    ('\u{1946}', '\u{194F}'),
+    ('\u{19D0}', '\u{19D9}'),
+    ('\u{1A80}', '\u{1A89}'),
+    ('\u{1A90}', '\u{1A99}'),
+    ('\u{1B50}', '\u{1B59}'),
+    ('\u{1BB0}', '\u{1BB9}'),
+    ('\u{1C40}', '\u{1C49}'),
+    ('\u{1C50}', '\u{1C59}'),
+    ('\u{A620}', '\u{A629}'),
+    ('\u{A8D0}', '\u{A8D9}'),
+    ('\u{A900}', '\u{A909}'),
+    ('\u{A9D0}', '\u{A9D9}'),
+    ('\u{A9F0}', '\u{A9F9}'),
+    ('\u{AA50}', '\u{AA59}'),
+    ('\u{ABF0}', '\u{ABF9}'),
+    ('\u{FF10}', '\u{FF19}'),
+    ('\u{104A0}', '\u{104A9}'),
+    ('\u{10D30}', '\u{10D39}'),
+    ('\u{10D40}', '\u{10D49}'),
+    ('\u{11066}', '\u{1106F}'),
+    ('\u{110F0}', '\u{110F9}'),
+    ('\u{11136}', '\u{1113F}'),
+    ('\u{111D0}', '\u{111D9}'),
+    ('\u{112F0}', '\u{112F9}'),
+    ('\u{11450}', '\u{11459}'),
+    ('\u{114D0}', '\u{114D9}'),
+    ('\u{11650}', '\u{11659}'),
+    ('\u{116C0}', '\u{116C9}'),
+    ('\u{116D0}', '\u{116E3}'),
+    ('\u{11730}', '\u{11739}'),
+    ('\u{118E0}', '\u{118E9}'),
+    ('\u{11950}', '\u{11959}'),
+    ('\u{11BF0}', '\u{11BF9}'),
+    ('\u{11C50}', '\u{11C59}'),
+    ('\u{11D50}', '\u{11D59}'),
+    ('\u{11DA0}', '\u{11DA9}'),
+    ('\u{11DE0}', '\u{11DE9}'),
+    ('\u{11F50}', '\u{11F59}'),
+    ('\u{16130}', '\u{16139}'),
+    ('\u{16A60}', '\u{16A69}'),
+    ('\u{16AC0}', '\u{16AC9}'),
+    ('\u{16B50}', '\u{16B59}'),
+    ('\u{16D70}', '\u{16D79}'),
+    ('\u{1CCF0}', '\u{1CCF9}'),
+    ('\u{1D7CE}', '\u{1D7FF}'),
+    ('\u{1E140}', '\u{1E149}'),
+    ('\u{1E2F0}', '\u{1E2F9}'),
+    ('\u{1E4F0}', '\u{1E4F9}'),
+    ('\u{1E5F1}', '\u{1E5FA}'),
+    ('\u{1E950}', '\u{1E959}'),
+    ('\u{1FBF0}', '\u{1FBF9}'),
+];
+
+// Unicode 17.0.0 White_Space property, as `char::is_whitespace`.
+const WHITE_SPACE: &[(char, char)] = &[
+    ('\u{9}', '\u{D}'),
+    ('\u{20}', '\u{20}'),
+    ('\u{85}', '\u{85}'),
+    ('\u{A0}', '\u{A0}'),
+    ('\u{1680}', '\u{1680}'),
+    ('\u{2000}', '\u{200A}'),
+    ('\u{2028}', '\u{2029}'),
+    ('\u{202F}', '\u{202F}'),
+    ('\u{205F}', '\u{205F}'),
+    ('\u{3000}', '\u{3000}'),
+];
+
+// Unicode 17.0.0 Alphabetic and Numeric chars, as `char::is_alphanumeric`,
+// and `_`.
+const WORD: &[(char, char)] = &[
+    ('\u{30}', '\u{39}'),
+    ('\u{41}', '\u{5A}'),
+    ('\u{5F}', '\u{5F}'),
+    ('\u{61}', '\u{7A}'),
+    ('\u{AA}', '\u{AA}'),
+    ('\u{B2}', '\u{B3}'),
+    ('\u{B5}', '\u{B5}'),
+    ('\u{B9}', '\u{BA}'),
+    ('\u{BC}', '\u{BE}'),
+    ('\u{C0}', '\u{D6}'),
+    ('\u{D8}', '\u{F6}'),
+    ('\u{F8}', '\u{2C1}'),
+    ('\u{2C6}', '\u{2D1}'),
+    ('\u{2E0}', '\u{2E4}'),
+    ('\u{2EC}', '\u{2EC}'),
+    ('\u{2EE}', '\u{2EE}'),
+    ('\u{345}', '\u{345}'),
+    ('\u{363}', '\u{374}'),
+    ('\u{376}', '\u{377}'),
+    ('\u{37A}', '\u{37D}'),
+    ('\u{37F}', '\u{37F}'),
+    ('\u{386}', '\u{386}'),
+    ('\u{388}', '\u{38A}'),
+    ('\u{38C}', '\u{38C}'),
+    ('\u{38E}', '\u{3A1}'),
+    ('\u{3A3}', '\u{3F5}'),
+    ('\u{3F7}', '\u{481}'),
+    ('\u{48A}', '\u{52F}'),
+    ('\u{531}', '\u{556}'),
+    ('\u{559}', '\u{559}'),
+    ('\u{560}', '\u{588}'),
+    ('\u{5B0}', '\u{5BD}'),
+    ('\u{5BF}', '\u{5BF}'),
+    ('\u{5C1}', '\u{5C2}'),
+    ('\u{5C4}', '\u{5C5}'),
+    ('\u{5C7}', '\u{5C7}'),
+    ('\u{5D0}', '\u{5EA}'),
+    ('\u{5EF}', '\u{5F2}'),
+    ('\u{610}', '\u{61A}'),
+    ('\u{620}', '\u{657}'),
+    ('\u{659}', '\u{669}'),
+    ('\u{66E}', '\u{6D3}'),
+    ('\u{6D5}', '\u{6DC}'),
+    ('\u{6E1}', '\u{6E8}'),
+    ('\u{6ED}', '\u{6FC}'),
+    ('\u{6FF}', '\u{6FF}'),
+    ('\u{710}', '\u{73F}'),
+    ('\u{74D}', '\u{7B1}'),
+    ('\u{7C0}', '\u{7EA}'),
+    ('\u{7F4}', '\u{7F5}'),
+    ('\u{7FA}', '\u{7FA}'),
+    ('\u{800}', '\u{817}'),
+    ('\u{81A}', '\u{82C}'),
+    ('\u{840}', '\u{858}'),
+    ('\u{860}', '\u{86A}'),
+    ('\u{870}', '\u{887}'),
+    ('\u{889}', '\u{88F}'),
+    ('\u{897}', '\u{897}'),
+    ('\u{8A0}', '\u{8C9}'),
+    ('\u{8D4}', '\u{8DF}'),
+    ('\u{8E3}', '\u{8E9}'),
+    ('\u{8F0}', '\u{93B}'),
+    ('\u{93D}', '\u{94C}'),
+    ('\u{94E}', '\u{950}'),
+    ('\u{955}', '\u{963}'),
+    ('\u{966}', '\u{96F}'),
+    ('\u{971}', '\u{983}'),
+    ('\u{985}', '\u{98C}'),
+    ('\u{98F}', '\u{990}'),
+    ('\u{993}', '\u{9A8}'),
+    ('\u{9AA}', '\u{9B0}'),
+    ('\u{9B2}', '\u{9B2}'),
+    ('\u{9B6}', '\u{9B9}'),
+    ('\u{9BD}', '\u{9C4}'),
+    ('\u{9C7}', '\u{9C8}'),
+    ('\u{9CB}', '\u{9CC}'),
+    ('\u{9CE}', '\u{9CE}'),
+    ('\u{9D7}', '\u{9D7}'),
+    ('\u{9DC}', '\u{9DD}'),
+    ('\u{9DF}', '\u{9E3}'),
+    ('\u{9E6}', '\u{9F1}'),
+    ('\u{9F4}', '\u{9F9}'),
+    ('\u{9FC}', '\u{9FC}'),
+    ('\u{A01}', '\u{A03}'),
+    ('\u{A05}', '\u{A0A}'),
+    ('\u{A0F}', '\u{A10}'),
+    ('\u{A13}', '\u{A28}'),
+    ('\u{A2A}', '\u{A30}'),
+    ('\u{A32}', '\u{A33}'),
+    ('\u{A35}', '\u{A36}'),
+    ('\u{A38}', '\u{A39}'),
+    ('\u{A3E}', '\u{A42}'),
+    ('\u{A47}', '\u{A48}'),
+    ('\u{A4B}', '\u{A4C}'),
+    ('\u{A51}', '\u{A51}'),
+    ('\u{A59}', '\u{A5C}'),
+    ('\u{A5E}', '\u{A5E}'),
+    ('\u{A66}', '\u{A75}'),
+    ('\u{A81}', '\u{A83}'),
+    ('\u{A85}', '\u{A8D}'),
+    ('\u{A8F}', '\u{A91}'),
+    ('\u{A93}', '\u{AA8}'),
+    ('\u{AAA}', '\u{AB0}'),
+    ('\u{AB2}', '\u{AB3}'),
+    ('\u{AB5}', '\u{AB9}'),
+    ('\u{ABD}', '\u{AC5}'),
+    ('\u{AC7}', '\u{AC9}'),
+    ('\u{ACB}', '\u{ACC}'),
+    ('\u{AD0}', '\u{AD0}'),
+    ('\u{AE0}', '\u{AE3}'),
+    ('\u{AE6}', '\u{AEF}'),
+    ('\u{AF9}', '\u{AFC}'),
+    ('\u{B01}', '\u{B03}'),
+    ('\u{B05}', '\u{B0C}'),
+    ('\u{B0F}', '\u{B10}'),
+    ('\u{B13}', '\u{B28}'),
+    ('\u{B2A}', '\u{B30}'),
+    ('\u{B32}', '\u{B33}'),
+    ('\u{B35}', '\u{B39}'),
+    ('\u{B3D}', '\u{B44}'),
+    ('\u{B47}', '\u{B48}'),
+    ('\u{B4B}', '\u{B4C}'),
+    ('\u{B56}', '\u{B57}'),
+    ('\u{B5C}', '\u{B5D}'),
+    ('\u{B5F}', '\u{B63}'),
+    ('\u{B66}', '\u{B6F}'),
+    ('\u{B71}', '\u{B77}'),
+    ('\u{B82}', '\u{B83}'),
+    ('\u{B85}', '\u{B8A}'),
+    ('\u{B8E}', '\u{B90}'),
+    ('\u{B92}', '\u{B95}'),
+    ('\u{B99}', '\u{B9A}'),
+    ('\u{B9C}', '\u{B9C}'),
+    ('\u{B9E}', '\u{B9F}'),
+    ('\u{BA3}', '\u{BA4}'),
+    ('\u{BA8}', '\u{BAA}'),
+    ('\u{BAE}', '\u{BB9}'),
+    ('\u{BBE}', '\u{BC2}'),
+    ('\u{BC6}', '\u{BC8}'),
+    ('\u{BCA}', '\u{BCC}'),
+    ('\u{BD0}', '\u{BD0}'),
+    ('\u{BD7}', '\u{BD7}'),
+    ('\u{BE6}', '\u{BF2}'),
+    ('\u{C00}', '\u{C0C}'),
+    ('\u{C0E}', '\u{C10}'),
+    ('\u{C12}', '\u{C28}'),
+    ('\u{C2A}', '\u{C39}'),
+    ('\u{C3D}', '\u{C44}'),
+    ('\u{C46}', '\u{C48}'),
+    ('\u{C4A}', '\u{C4C}'),
+    ('\u{C55}', '\u{C56}'),
+    ('\u{C58}', '\u{C5A}'),
+    ('\u{C5C}', '\u{C5D}'),
+    ('\u{C60}', '\u{C63}'),
+    ('\u{C66}', '\u{C6F}'),
+    ('\u{C78}', '\u{C7E}'),
+    ('\u{C80}', '\u{C83}'),
+    ('\u{C85}', '\u{C8C}'),
+    ('\u{C8E}', '\u{C90}'),
+    ('\u{C92}', '\u{CA8}'),
+    ('\u{CAA}', '\u{CB3}'),
+    ('\u{CB5}', '\u{CB9}'),
+    ('\u{CBD}', '\u{CC4}'),
+    ('\u{CC6}', '\u{CC8}'),
+    ('\u{CCA}', '\u{CCC}'),
+    ('\u{CD5}', '\u{CD6}'),
+    ('\u{CDC}', '\u{CDE}'),
+    ('\u{CE0}', '\u{CE3}'),
+    ('\u{CE6}', '\u{CEF}'),
+    ('\u{CF1}', '\u{CF3}'),
+    ('\u{D00}', '\u{D0C}'),
+    ('\u{D0E}', '\u{D10}'),
+    ('\u{D12}', '\u{D3A}'),
+    ('\u{D3D}', '\u{D44}'),
+    ('\u{D46}', '\u{D48}'),
+    ('\u{D4A}', '\u{D4C}'),
+    ('\u{D4E}', '\u{D4E}'),
+    ('\u{D54}', '\u{D63}'),
+    ('\u{D66}', '\u{D78}'),
+    ('\u{D7A}', '\u{D7F}'),
+    ('\u{D81}', '\u{D83}'),
+    ('\u{D85}', '\u{D96}'),
+    ('\u{D9A}', '\u{DB1}'),
+    ('\u{DB3}', '\u{DBB}'),
+    ('\u{DBD}', '\u{DBD}'),
+    ('\u{DC0}', '\u{DC6}'),
+    ('\u{DCF}', '\u{DD4}'),
+    ('\u{DD6}', '\u{DD6}'),
+    ('\u{DD8}', '\u{DDF}'),
+    ('\u{DE6}', '\u{DEF}'),
+    ('\u{DF2}', '\u{DF3}'),
+    ('\u{E01}', '\u{E3A}'),
+    ('\u{E40}', '\u{E46}'),
+    ('\u{E4D}', '\u{E4D}'),
+    ('\u{E50}', '\u{E59}'),
+    ('\u{E81}', '\u{E82}'),
+    ('\u{E84}', '\u{E84}'),
+    ('\u{E86}', '\u{E8A}'),
+    ('\u{E8C}', '\u{EA3}'),
+    ('\u{EA5}', '\u{EA5}'),
+    ('\u{EA7}', '\u{EB9}'),
+    ('\u{EBB}', '\u{EBD}'),
+    ('\u{EC0}', '\u{EC4}'),
+    ('\u{EC6}', '\u{EC6}'),
+    ('\u{ECD}', '\u{ECD}'),
+    ('\u{ED0}', '\u{ED9}'),
+    ('\u{EDC}', '\u{EDF}'),
+    ('\u{F00}', '\u{F00}'),
+    ('\u{F20}', '\u{F33}'),
+    ('\u{F40}', '\u{F47}'),
+    ('\u{F49}', '\u{F6C}'),
+    ('\u{F71}', '\u{F83}'),
+    ('\u{F88}', '\u{F97}'),
+    ('\u{F99}', '\u{FBC}'),
+    ('\u{1000}', '\u{1036}'),
+    ('\u{1038}', '\u{1038}'),
+    ('\u{103B}', '\u{1049}'),
+    ('\u{1050}', '\u{109D}'),
+    ('\u{10A0}', '\u{10C5}'),
+    ('\u{10C7}', '\u{10C7}'),
+    ('\u{10CD}', '\u{10CD}'),
+    ('\u{10D0}', '\u{10FA}'),
+    ('\u{10FC}', '\u{1248}'),
+    ('\u{124A}', '\u{124D}'),
+    ('\u{1250}', '\u{1256}'),
+    ('\u{1258}', '\u{1258}'),
+    ('\u{125A}', '\u{125D}'),
+    ('\u{1260}', '\u{1288}'),
+    ('\u{128A}', '\u{128D}'),
+    ('\u{1290}', '\u{12B0}'),
+    ('\u{12B2}', '\u{12B5}'),
+    ('\u{12B8}', '\u{12BE}'),
+    ('\u{12C0}', '\u{12C0}'),
+    ('\u{12C2}', '\u{12C5}'),
+    ('\u{12C8}', '\u{12D6}'),
+    ('\u{12D8}', '\u{1310}'),
+    ('\u{1312}', '\u{1315}'),
+    ('\u{1318}', '\u{135A}'),
+    ('\u{1369}', '\u{137C}'),
+    ('\u{1380}', '\u{138F}'),
+    ('\u{13A0}', '\u{13F5}'),
+    ('\u{13F8}', '\u{13FD}'),
+    ('\u{1401}', '\u{166C}'),
+    ('\u{166F}', '\u{167F}'),
+    ('\u{1681}', '\u{169A}'),
+    ('\u{16A0}', '\u{16EA}'),
+    ('\u{16EE}', '\u{16F8}'),
+    ('\u{1700}', '\u{1713}'),
+    ('\u{171F}', '\u{1733}'),
+    ('\u{1740}', '\u{1753}'),
+    ('\u{1760}', '\u{176C}'),
+    ('\u{176E}', '\u{1770}'),
+    ('\u{1772}', '\u{1773}'),
+    ('\u{1780}', '\u{17B3}'),
+    ('\u{17B6}', '\u{17C8}'),
+    ('\u{17D7}', '\u{17D7}'),
+    ('\u{17DC}', '\u{17DC}'),
+    ('\u{17E0}', '\u{17E9}'),
+    ('\u{17F0}', '\u{17F9}'),
+    ('\u{1810}', '\u{1819}'),
+    ('\u{1820}', '\u{1878}'),
+    ('\u{1880}', '\u{18AA}'),
+    ('\u{18B0}', '\u{18F5}'),
+    ('\u{1900}', '\u{191E}'),
+    ('\u{1920}', '\u{192B}'),
+    ('\u{1930}', '\u{1938}'),
+    ('\u{1946}', '\u{196D}'),
+    ('\u{1970}', '\u{1974}'),
+    ('\u{1980}', '\u{19AB}'),
+    ('\u{19B0}', '\u{19C9}'),
+    ('\u{19D0}', '\u{19DA}'),
+    ('\u{1A00}', '\u{1A1B}'),
+    ('\u{1A20}', '\u{1A5E}'),
+    ('\u{1A61}', '\u{1A74}'),
+    ('\u{1A80}', '\u{1A89}'),
+    ('\u{1A90}', '\u{1A99}'),
+    ('\u{1AA7}', '\u{1AA7}'),
+    ('\u{1ABF}', '\u{1AC0}'),
+    ('\u{1ACC}', '\u{1ACE}'),
+    ('\u{1B00}', '\u{1B33}'),
+    ('\u{1B35}', '\u{1B43}'),
+    ('\u{1B45}', '\u{1B4C}'),
+    ('\u{1B50}', '\u{1B59}'),
+    ('\u{1B80}', '\u{1BA9}'),
+    ('\u{1BAC}', '\u{1BE5}'),
+    ('\u{1BE7}', '\u{1BF1}'),
+    ('\u{1C00}', '\u{1C36}'),
+    ('\u{1C40}', '\u{1C49}'),
+    ('\u{1C4D}', '\u{1C7D}'),
+    ('\u{1C80}', '\u{1C8A}'),
+    ('\u{1C90}', '\u{1CBA}'),
+    ('\u{1CBD}', '\u{1CBF}'),
+    ('\u{1CE9}', '\u{1CEC}'),
+    ('\u{1CEE}', '\u{1CF3}'),
+    ('\u{1CF5}', '\u{1CF6}'),
+    ('\u{1CFA}', '\u{1CFA}'),
+    ('\u{1D00}', '\u{1DBF}'),
+    ('\u{1DD3}', '\u{1DF4}'),
+    ('\u{1E00}', '\u{1F15}'),
+    ('\u{1F18}', '\u{1F1D}'),
+    ('\u{1F20}', '\u{1F45}'),
+    ('\u{1F48}', '\u{1F4D}'),
+    ('\u{1F50}', '\u{1F57}'),
+    ('\u{1F59}', '\u{1F59}'),
+    ('\u{1F5B}', '\u{1F5B}'),
+    ('\u{1F5D}', '\u{1F5D}'),
+    ('\u{1F5F}', '\u{1F7D}'),
+    ('\u{1F80}', '\u{1FB4}'),
+    ('\u{1FB6}', '\u{1FBC}'),
+    ('\u{1FBE}', '\u{1FBE}'),
+    ('\u{1FC2}', '\u{1FC4}'),
+    ('\u{1FC6}', '\u{1FCC}'),
+    ('\u{1FD0}', '\u{1FD3}'),
+    ('\u{1FD6}', '\u{1FDB}'),
+    ('\u{1FE0}', '\u{1FEC}'),
+    ('\u{1FF2}', '\u{1FF4}'),
+    ('\u{1FF6}', '\u{1FFC}'),
+    ('\u{2070}', '\u{2071}'),
+    ('\u{2074}', '\u{2079}'),
+    ('\u{207F}', '\u{2089}'),
+    ('\u{2090}', '\u{209C}'),
+    ('\u{2102}', '\u{2102}'),
+    ('\u{2107}', '\u{2107}'),
+    ('\u{210A}', '\u{2113}'),
+    ('\u{2115}', '\u{2115}'),
+    ('\u{2119}', '\u{211D}'),
+    ('\u{2124}', '\u{2124}'),
+    ('\u{2126}', '\u{2126}'),
+    ('\u{2128}', '\u{2128}'),
+    ('\u{212A}', '\u{212D}'),
+    ('\u{212F}', '\u{2139}'),
+    ('\u{213C}', '\u{213F}'),
+    ('\u{2145}', '\u{2149}'),
+    ('\u{214E}', '\u{214E}'),
+    ('\u{2150}', '\u{2189}'),
+    ('\u{2460}', '\u{249B}'),
+    ('\u{24B6}', '\u{24FF}'),
+    ('\u{2776}', '\u{2793}'),
+    ('\u{2C00}', '\u{2CE4}'),
+    ('\u{2CEB}', '\u{2CEE}'),
+    ('\u{2CF2}', '\u{2CF3}'),
+    ('\u{2CFD}', '\u{2CFD}'),
+    ('\u{2D00}', '\u{2D25}'),
+    ('\u{2D27}', '\u{2D27}'),
+    ('\u{2D2D}', '\u{2D2D}'),
+    ('\u{2D30}', '\u{2D67}'),
+    ('\u{2D6F}', '\u{2D6F}'),
+    ('\u{2D80}', '\u{2D96}'),
+    ('\u{2DA0}', '\u{2DA6}'),
+    ('\u{2DA8}', '\u{2DAE}'),
+    ('\u{2DB0}', '\u{2DB6}'),
+    ('\u{2DB8}', '\u{2DBE}'),
+    ('\u{2DC0}', '\u{2DC6}'),
+    ('\u{2DC8}', '\u{2DCE}'),
+    ('\u{2DD0}', '\u{2DD6}'),
+    ('\u{2DD8}', '\u{2DDE}'),
+    ('\u{2DE0}', '\u{2DFF}'),
+    ('\u{2E2F}', '\u{2E2F}'),
+    ('\u{3005}', '\u{3007}'),
+    ('\u{3021}', '\u{3029}'),
+    ('\u{3031}', '\u{3035}'),
+    ('\u{3038}', '\u{303C}'),
+    ('\u{3041}', '\u{3096}'),
+    ('\u{309D}', '\u{309F}'),
+    ('\u{30A1}', '\u{30FA}'),
+    ('\u{30FC}', '\u{30FF}'),
+    ('\u{3105}', '\u{312F}'),
+    ('\u{3131}', '\u{318E}'),
+    ('\u{3192}', '\u{3195}'),
+    ('\u{31A0}', '\u{31BF}'),
+    ('\u{31F0}', '\u{31FF}'),
+    ('\u{3220}', '\u{3229}'),
+    ('\u{3248}', '\u{324F}'),
+    ('\u{3251}', '\u{325F}'),
+    ('\u{3280}', '\u{3289}'),
+    ('\u{32B1}', '\u{32BF}'),
+    ('\u{3400}', '\u{4DBF}'),
+    ('\u{4E00}', '\u{A48C}'),
+    ('\u{A4D0}', '\u{A4FD}'),
+    ('\u{A500}', '\u{A60C}'),
+    ('\u{A610}', '\u{A62B}'),
+    ('\u{A640}', '\u{A66E}'),
+    ('\u{A674}', '\u{A67B}'),
+    ('\u{A67F}', '\u{A6EF}'),
+    ('\u{A717}', '\u{A71F}'),
+    ('\u{A722}', '\u{A788}'),
+    ('\u{A78B}', '\u{A7DC}'),
+    ('\u{A7F1}', '\u{A805}'),
+    ('\u{A807}', '\u{A827}'),
+    ('\u{A830}', '\u{A835}'),
+    ('\u{A840}', '\u{A873}'),
+    ('\u{A880}', '\u{A8C3}'),
+    ('\u{A8C5}', '\u{A8C5}'),
+    ('\u{A8D0}', '\u{A8D9}'),
+    ('\u{A8F2}', '\u{A8F7}'),
+    ('\u{A8FB}', '\u{A8FB}'),
+    ('\u{A8FD}', '\u{A92A}'),
+    ('\u{A930}', '\u{A952}'),
+    ('\u{A960}', '\u{A97C}'),
+    ('\u{A980}', '\u{A9B2}'),
+    ('\u{A9B4}', '\u{A9BF}'),
+    ('\u{A9CF}', '\u{A9D9}'),
+    ('\u{A9E0}', '\u{A9FE}'),
+    ('\u{AA00}', '\u{AA36}'),
+    ('\u{AA40}', '\u{AA4D}'),
+    ('\u{AA50}', '\u{AA59}'),
+    ('\u{AA60}', '\u{AA76}'),
+    ('\u{AA7A}', '\u{AABE}'),
+    ('\u{AAC0}', '\u{AAC0}'),
+    ('\u{AAC2}', '\u{AAC2}'),
+    ('\u{AADB}', '\u{AADD}'),
+    ('\u{AAE0}', '\u{AAEF}'),
+    ('\u{AAF2}', '\u{AAF5}'),
+    ('\u{AB01}', '\u{AB06}'),
+    ('\u{AB09}', '\u{AB0E}'),
+    ('\u{AB11}', '\u{AB16}'),
+    ('\u{AB20}', '\u{AB26}'),
+    ('\u{AB28}', '\u{AB2E}'),
+    ('\u{AB30}', '\u{AB5A}'),
+    ('\u{AB5C}', '\u{AB69}'),
+    ('\u{AB70}', '\u{ABEA}'),
+    ('\u{ABF0}', '\u{ABF9}'),
+    ('\u{AC00}', '\u{D7A3}'),
+    ('\u{D7B0}', '\u{D7C6}'),
+    ('\u{D7CB}', '\u{D7FB}'),
+    ('\u{F900}', '\u{FA6D}'),
+    ('\u{FA70}', '\u{FAD9}'),
+    ('\u{FB00}', '\u{FB06}'),
+    ('\u{FB13}', '\u{FB17}'),
+    ('\u{FB1D}', '\u{FB28}'),
+    ('\u{FB2A}', '\u{FB36}'),
+    ('\u{FB38}', '\u{FB3C}'),
+    ('\u{FB3E}', '\u{FB3E}'),
+    ('\u{FB40}', '\u{FB41}'),
+    ('\u{FB43}', '\u{FB44}'),
+    ('\u{FB46}', '\u{FBB1}'),
+    ('\u{FBD3}', '\u{FD3D}'),
+    ('\u{FD50}', '\u{FD8F}'),
+    ('\u{FD92}', '\u{FDC7}'),
+    ('\u{FDF0}', '\u{FDFB}'),
+    ('\u{FE70}', '\u{FE74}'),
+    ('\u{FE76}', '\u{FEFC}'),
+    ('\u{FF10}', '\u{FF19}'),
+    ('\u{FF21}', '\u{FF3A}'),
+    ('\u{FF41}', '\u{FF5A}'),
+    ('\u{FF66}', '\u{FFBE}'),
+    ('\u{FFC2}', '\u{FFC7}'),
+    ('\u{FFCA}', '\u{FFCF}'),
+    ('\u{FFD2}', '\u{FFD7}'),
+    ('\u{FFDA}', '\u{FFDC}'),
+    ('\u{10000}', '\u{1000B}'),
+    ('\u{1000D}', '\u{10026}'),
+    ('\u{10028}', '\u{1003A}'),
+    ('\u{1003C}', '\u{1003D}'),
+    ('\u{1003F}', '\u{1004D}'),
+    ('\u{10050}', '\u{1005D}'),
+    ('\u{10080}', '\u{100FA}'),
+    ('\u{10107}', '\u{10133}'),
+    ('\u{10140}', '\u{10178}'),
+    ('\u{1018A}', '\u{1018B}'),
+    ('\u{10280}', '\u{1029C}'),
+    ('\u{102A0}', '\u{102D0}'),
+    ('\u{102E1}', '\u{102FB}'),
+    ('\u{10300}', '\u{10323}'),
+    ('\u{1032D}', '\u{1034A}'),
+    ('\u{10350}', '\u{1037A}'),
+    ('\u{10380}', '\u{1039D}'),
+    ('\u{103A0}', '\u{103C3}'),
+    ('\u{103C8}', '\u{103CF}'),
+    ('\u{103D1}', '\u{103D5}'),
+    ('\u{10400}', '\u{1049D}'),
+    ('\u{104A0}', '\u{104A9}'),
+    ('\u{104B0}', '\u{104D3}'),
+    ('\u{104D8}', '\u{104FB}'),
+    ('\u{10500}', '\u{10527}'),
+    ('\u{10530}', '\u{10563}'),
+    ('\u{10570}', '\u{1057A}'),
+    ('\u{1057C}', '\u{1058A}'),
+    ('\u{1058C}', '\u{10592}'),
+    ('\u{10594}', '\u{10595}'),
+    ('\u{10597}', '\u{105A1}'),
+    ('\u{105A3}', '\u{105B1}'),
+    ('\u{105B3}', '\u{105B9}'),
+    ('\u{105BB}', '\u{105BC}'),
+    ('\u{105C0}', '\u{105F3}'),
+    ('\u{10600}', '\u{10736}'),
+    ('\u{10740}', '\u{10755}'),
+    ('\u{10760}', '\u{10767}'),
+    ('\u{10780}', '\u{10785}'),
+    ('\u{10787}', '\u{107B0}'),
+    ('\u{107B2}', '\u{107BA}'),
+    ('\u{10800}', '\u{10805}'),
+    ('\u{10808}', '\u{10808}'),
+    ('\u{1080A}', '\u{10835}'),
+    ('\u{10837}', '\u{10838}'),
+    ('\u{1083C}', '\u{1083C}'),
+    ('\u{1083F}', '\u{10855}'),
+    ('\u{10858}', '\u{10876}'),
+    ('\u{10879}', '\u{1089E}'),
+    ('\u{108A7}', '\u{108AF}'),
+    ('\u{108E0}', '\u{108F2}'),
+    ('\u{108F4}', '\u{108F5}'),
+    ('\u{108FB}', '\u{1091B}'),
+    ('\u{10920}', '\u{10939}'),
+    ('\u{10940}', '\u{10959}'),
+    ('\u{10980}', '\u{109B7}'),
+    ('\u{109BC}', '\u{109CF}'),
+    ('\u{109D2}', '\u{10A03}'),
+    ('\u{10A05}', '\u{10A06}'),
+    ('\u{10A0C}', '\u{10A13}'),
+    ('\u{10A15}', '\u{10A17}'),
+    ('\u{10A19}', '\u{10A35}'),
+    ('\u{10A40}', '\u{10A48}'),
+    ('\u{10A60}', '\u{10A7E}'),
+    ('\u{10A80}', '\u{10A9F}'),
+    ('\u{10AC0}', '\u{10AC7}'),
+    ('\u{10AC9}', '\u{10AE4}'),
+    ('\u{10AEB}', '\u{10AEF}'),
+    ('\u{10B00}', '\u{10B35}'),
+    ('\u{10B40}', '\u{10B55}'),
+    ('\u{10B58}', '\u{10B72}'),
+    ('\u{10B78}', '\u{10B91}'),
+    ('\u{10BA9}', '\u{10BAF}'),
+    ('\u{10C00}', '\u{10C48}'),
+    ('\u{10C80}', '\u{10CB2}'),
+    ('\u{10CC0}', '\u{10CF2}'),
+    ('\u{10CFA}', '\u{10D27}'),
+    ('\u{10D30}', '\u{10D39}'),
+    ('\u{10D40}', '\u{10D65}'),
+    ('\u{10D69}', '\u{10D69}'),
+    ('\u{10D6F}', '\u{10D85}'),
+    ('\u{10E60}', '\u{10E7E}'),
+    ('\u{10E80}', '\u{10EA9}'),
+    ('\u{10EAB}', '\u{10EAC}'),
+    ('\u{10EB0}', '\u{10EB1}'),
+    ('\u{10EC2}', '\u{10EC7}'),
+    ('\u{10EFA}', '\u{10EFC}'),
+    ('\u{10F00}', '\u{10F27}'),
+    ('\u{10F30}', '\u{10F45}'),
+    ('\u{10F51}', '\u{10F54}'),
+    ('\u{10F70}', '\u{10F81}'),
+    ('\u{10FB0}', '\u{10FCB}'),
+    ('\u{10FE0}', '\u{10FF6}'),
+    ('\u{11000}', '\u{11045}'),
+    ('\u{11052}', '\u{1106F}'),
+    ('\u{11071}', '\u{11075}'),
+    ('\u{11080}', '\u{110B8}'),
+    ('\u{110C2}', '\u{110C2}'),
+    ('\u{110D0}', '\u{110E8}'),
+    ('\u{110F0}', '\u{110F9}'),
+    ('\u{11100}', '\u{11132}'),
+    ('\u{11136}', '\u{1113F}'),
+    ('\u{11144}', '\u{11147}'),
+    ('\u{11150}', '\u{11172}'),
+    ('\u{11176}', '\u{11176}'),
+    ('\u{11180}', '\u{111BF}'),
+    ('\u{111C1}', '\u{111C4}'),
+    ('\u{111CE}', '\u{111DA}'),
+    ('\u{111DC}', '\u{111DC}'),
+    ('\u{111E1}', '\u{111F4}'),
+    ('\u{11200}', '\u{11211}'),
+    ('\u{11213}', '\u{11234}'),
+    ('\u{11237}', '\u{11237}'),
+    ('\u{1123E}', '\u{11241}'),
+    ('\u{11280}', '\u{11286}'),
+    ('\u{11288}', '\u{11288}'),
+    ('\u{1128A}', '\u{1128D}'),
+    ('\u{1128F}', '\u{1129D}'),
+    ('\u{1129F}', '\u{112A8}'),
+    ('\u{112B0}', '\u{112E8}'),
+    ('\u{112F0}', '\u{112F9}'),
+    ('\u{11300}', '\u{11303}'),
+    ('\u{11305}', '\u{1130C}'),
+    ('\u{1130F}', '\u{11310}'),
+    ('\u{11313}', '\u{11328}'),
+    ('\u{1132A}', '\u{11330}'),
+    ('\u{11332}', '\u{11333}'),
+    ('\u{11335}', '\u{11339}'),
+    ('\u{1133D}', '\u{11344}'),
+    ('\u{11347}', '\u{11348}'),
+    ('\u{1134B}', '\u{1134C}'),
+    ('\u{11350}', '\u{11350}'),
+    ('\u{11357}', '\u{11357}'),
+    ('\u{1135D}', '\u{11363}'),
+    ('\u{11380}', '\u{11389}'),
+    ('\u{1138B}', '\u{1138B}'),
+    ('\u{1138E}', '\u{1138E}'),
+    ('\u{11390}', '\u{113B5}'),
+    ('\u{113B7}', '\u{113C0}'),
+    ('\u{113C2}', '\u{113C2}'),
+    ('\u{113C5}', '\u{113C5}'),
+    ('\u{113C7}', '\u{113CA}'),
+    ('\u{113CC}', '\u{113CD}'),
+    ('\u{113D1}', '\u{113D1}'),
+    ('\u{113D3}', '\u{113D3}'),
+    ('\u{11400}', '\u{11441}'),
+    ('\u{11443}', '\u{11445}'),
+    ('\u{11447}', '\u{1144A}'),
+    ('\u{11450}', '\u{11459}'),
+    ('\u{1145F}', '\u{11461}'),
+    ('\u{11480}', '\u{114C1}'),
+    ('\u{114C4}', '\u{114C5}'),
+    ('\u{114C7}', '\u{114C7}'),
+    ('\u{114D0}', '\u{114D9}'),
+    ('\u{11580}', '\u{115B5}'),
+    ('\u{115B8}', '\u{115BE}'),
+    ('\u{115D8}', '\u{115DD}'),
+    ('\u{11600}', '\u{1163E}'),
+    ('\u{11640}', '\u{11640}'),
+    ('\u{11644}', '\u{11644}'),
+    ('\u{11650}', '\u{11659}'),
+    ('\u{11680}', '\u{116B5}'),
+    ('\u{116B8}', '\u{116B8}'),
+    ('\u{116C0}', '\u{116C9}'),
+    ('\u{116D0}', '\u{116E3}'),
+    ('\u{11700}', '\u{1171A}'),
+    ('\u{1171D}', '\u{1172A}'),
+    ('\u{11730}', '\u{1173B}'),
+    ('\u{11740}', '\u{11746}'),
+    ('\u{11800}', '\u{11838}'),
+    ('\u{118A0}', '\u{118F2}'),
+    ('\u{118FF}', '\u{11906}'),
+    ('\u{11909}', '\u{11909}'),
+    ('\u{1190C}', '\u{11913}'),
+    ('\u{11915}', '\u{11916}'),
+    ('\u{11918}', '\u{11935}'),
+    ('\u{11937}', '\u{11938}'),
+    ('\u{1193B}', '\u{1193C}'),
+    ('\u{1193F}', '\u{11942}'),
+    ('\u{11950}', '\u{11959}'),
+    ('\u{119A0}', '\u{119A7}'),
+    ('\u{119AA}', '\u{119D7}'),
+    ('\u{119DA}', '\u{119DF}'),
+    ('\u{119E1}', '\u{119E1}'),
+    ('\u{119E3}', '\u{119E4}'),
+    ('\u{11A00}', '\u{11A32}'),
+    ('\u{11A35}', '\u{11A3E}'),
+    ('\u{11A50}', '\u{11A97}'),
+    ('\u{11A9D}', '\u{11A9D}'),
+    ('\u{11AB0}', '\u{11AF8}'),
+    ('\u{11B60}', '\u{11B67}'),
+    ('\u{11BC0}', '\u{11BE0}'),
+    ('\u{11BF0}', '\u{11BF9}'),
+    ('\u{11C00}', '\u{11C08}'),
+    ('\u{11C0A}', '\u{11C36}'),
+    ('\u{11C38}', '\u{11C3E}'),
+    ('\u{11C40}', '\u{11C40}'),
+    ('\u{11C50}', '\u{11C6C}'),
+    ('\u{11C72}', '\u{11C8F}'),
+    ('\u{11C92}', '\u{11CA7}'),
+    ('\u{11CA9}', '\u{11CB6}'),
+    ('\u{11D00}', '\u{11D06}'),
+    ('\u{11D08}', '\u{11D09}'),
+    ('\u{11D0B}', '\u{11D36}'),
+    ('\u{11D3A}', '\u{11D3A}'),
+    ('\u{11D3C}', '\u{11D3D}'),
+    ('\u{11D3F}', '\u{11D41}'),
+    ('\u{11D43}', '\u{11D43}'),
+    ('\u{11D46}', '\u{11D47}'),
+    ('\u{11D50}', '\u{11D59}'),
+    ('\u{11D60}', '\u{11D65}'),
+    ('\u{11D67}', '\u{11D68}'),
+    ('\u{11D6A}', '\u{11D8E}'),
+    ('\u{11D90}', '\u{11D91}'),
+    ('\u{11D93}', '\u{11D96}'),
+    ('\u{11D98}', '\u{11D98}'),
+    ('\u{11DA0}', '\u{11DA9}'),
+    ('\u{11DB0}', '\u{11DDB}'),
+    ('\u{11DE0}', '\u{11DE9}'),
+    ('\u{11EE0}', '\u{11EF6}'),
+    ('\u{11F00}', '\u{11F10}'),
+    ('\u{11F12}', '\u{11F3A}'),
+    ('\u{11F3E}', '\u{11F40}'),
+    ('\u{11F50}', '\u{11F59}'),
+    ('\u{11FB0}', '\u{11FB0}'),
+    ('\u{11FC0}', '\u{11FD4}'),
+    ('\u{12000}', '\u{12399}'),
+    ('\u{12400}', '\u{1246E}'),
+    ('\u{12480}', '\u{12543}'),
+    ('\u{12F90}', '\u{12FF0}'),
+    ('\u{13000}', '\u{1342F}'),
+    ('\u{13441}', '\u{13446}'),
+    ('\u{13460}', '\u{143FA}'),
+    ('\u{14400}', '\u{14646}'),
+    ('\u{16100}', '\u{1612E}'),
+    ('\u{16130}', '\u{16139}'),
+    ('\u{16800}', '\u{16A38}'),
+    ('\u{16A40}', '\u{16A5E}'),
+    ('\u{16A60}', '\u{16A69}'),
+    ('\u{16A70}', '\u{16ABE}'),
+    ('\u{16AC0}', '\u{16AC9}'),
+    ('\u{16AD0}', '\u{16AED}'),
+    ('\u{16B00}', '\u{16B2F}'),
+    ('\u{16B40}', '\u{16B43}'),
+    ('\u{16B50}', '\u{16B59}'),
+    ('\u{16B5B}', '\u{16B61}'),
+    ('\u{16B63}', '\u{16B77}'),
+    ('\u{16B7D}', '\u{16B8F}'),
+    ('\u{16D40}', '\u{16D6C}'),
+    ('\u{16D70}', '\u{16D79}'),
+    ('\u{16E40}', '\u{16E96}'),
+    ('\u{16EA0}', '\u{16EB8}'),
+    ('\u{16EBB}', '\u{16ED3}'),
+    ('\u{16F00}', '\u{16F4A}'),
+    ('\u{16F4F}', '\u{16F87}'),
+    ('\u{16F8F}', '\u{16F9F}'),
+    ('\u{16FE0}', '\u{16FE1}'),
+    ('\u{16FE3}', '\u{16FE3}'),
+    ('\u{16FF0}', '\u{16FF6}'),
+    ('\u{17000}', '\u{18CD5}'),
+    ('\u{18CFF}', '\u{18D1E}'),
+    ('\u{18D80}', '\u{18DF2}'),
+    ('\u{1AFF0}', '\u{1AFF3}'),
+    ('\u{1AFF5}', '\u{1AFFB}'),
+    ('\u{1AFFD}', '\u{1AFFE}'),
+    ('\u{1B000}', '\u{1B122}'),
+    ('\u{1B132}', '\u{1B132}'),
+    ('\u{1B150}', '\u{1B152}'),
+    ('\u{1B155}', '\u{1B155}'),
+    ('\u{1B164}', '\u{1B167}'),
+    ('\u{1B170}', '\u{1B2FB}'),
+    ('\u{1BC00}', '\u{1BC6A}'),
+    ('\u{1BC70}', '\u{1BC7C}'),
+    ('\u{1BC80}', '\u{1BC88}'),
+    ('\u{1BC90}', '\u{1BC99}'),
+    ('\u{1BC9E}', '\u{1BC9E}'),
+    ('\u{1CCF0}', '\u{1CCF9}'),
+    ('\u{1D2C0}', '\u{1D2D3}'),
+    ('\u{1D2E0}', '\u{1D2F3}'),
+    ('\u{1D360}', '\u{1D378}'),
+    ('\u{1D400}', '\u{1D454}'),
+    ('\u{1D456}', '\u{1D49C}'),
+    ('\u{1D49E}', '\u{1D49F}'),
+    ('\u{1D4A2}', '\u{1D4A2}'),
+    ('\u{1D4A5}', '\u{1D4A6}'),
+    ('\u{1D4A9}', '\u{1D4AC}'),
+    ('\u{1D4AE}', '\u{1D4B9}'),
+    ('\u{1D4BB}', '\u{1D4BB}'),
+    ('\u{1D4BD}', '\u{1D4C3}'),
+    ('\u{1D4C5}', '\u{1D505}'),
+    ('\u{1D507}', '\u{1D50A}'),
+    ('\u{1D50D}', '\u{1D514}'),
+    ('\u{1D516}', '\u{1D51C}'),
+    ('\u{1D51E}', '\u{1D539}'),
+    ('\u{1D53B}', '\u{1D53E}'),
+    ('\u{1D540}', '\u{1D544}'),
+    ('\u{1D546}', '\u{1D546}'),
+    ('\u{1D54A}', '\u{1D550}'),
+    ('\u{1D552}', '\u{1D6A5}'),
+    ('\u{1D6A8}', '\u{1D6C0}'),
+    ('\u{1D6C2}', '\u{1D6DA}'),
+    ('\u{1D6DC}', '\u{1D6FA}'),
+    ('\u{1D6FC}', '\u{1D714}'),
+    ('\u{1D716}', '\u{1D734}'),
+    ('\u{1D736}', '\u{1D74E}'),
+    ('\u{1D750}', '\u{1D76E}'),
+    ('\u{1D770}', '\u{1D788}'),
+    ('\u{1D78A}', '\u{1D7A8}'),
+    ('\u{1D7AA}', '\u{1D7C2}'),
+    ('\u{1D7C4}', '\u{1D7CB}'),
+    ('\u{1D7CE}', '\u{1D7FF}'),
+    ('\u{1DF00}', '\u{1DF1E}'),
+    ('\u{1DF25}', '\u{1DF2A}'),
+    ('\u{1E000}', '\u{1E006}'),
+    ('\u{1E008}', '\u{1E018}'),
+    ('\u{1E01B}', '\u{1E021}'),
+    ('\u{1E023}', '\u{1E024}'),
+    ('\u{1E026}', '\u{1E02A}'),
+    ('\u{1E030}', '\u{1E06D}'),
+    ('\u{1E08F}', '\u{1E08F}'),
+    ('\u{1E100}', '\u{1E12C}'),
+    ('\u{1E137}', '\u{1E13D}'),
+    ('\u{1E140}', '\u{1E149}'),
+    ('\u{1E14E}', '\u{1E14E}'),
+    ('\u{1E290}', '\u{1E2AD}'),
+    ('\u{1E2C0}', '\u{1E2EB}'),
+    ('\u{1E2F0}', '\u{1E2F9}'),
+    ('\u{1E4D0}', '\u{1E4EB}'),
+    ('\u{1E4F0}', '\u{1E4F9}'),
+    ('\u{1E5D0}', '\u{1E5ED}'),
+    ('\u{1E5F0}', '\u{1E5FA}'),
+    ('\u{1E6C0}', '\u{1E6DE}'),
+    ('\u{1E6E0}', '\u{1E6F5}'),
+    ('\u{1E6FE}', '\u{1E6FF}'),
+    ('\u{1E7E0}', '\u{1E7E6}'),
+    ('\u{1E7E8}', '\u{1E7EB}'),
+    ('\u{1E7ED}', '\u{1E7EE}'),
+    ('\u{1E7F0}', '\u{1E7FE}'),
+    ('\u{1E800}', '\u{1E8C4}'),
+    ('\u{1E8C7}', '\u{1E8CF}'),
+    ('\u{1E900}', '\u{1E943}'),
+    ('\u{1E947}', '\u{1E947}'),
+    ('\u{1E94B}', '\u{1E94B}'),
+    ('\u{1E950}', '\u{1E959}'),
+    ('\u{1EC71}', '\u{1ECAB}'),
+    ('\u{1ECAD}', '\u{1ECAF}'),
+    ('\u{1ECB1}', '\u{1ECB4}'),
+    ('\u{1ED01}', '\u{1ED2D}'),
+    ('\u{1ED2F}', '\u{1ED3D}'),
+    ('\u{1EE00}', '\u{1EE03}'),
+    ('\u{1EE05}', '\u{1EE1F}'),
+    ('\u{1EE21}', '\u{1EE22}'),
+    ('\u{1EE24}', '\u{1EE24}'),
+    ('\u{1EE27}', '\u{1EE27}'),
+    ('\u{1EE29}', '\u{1EE32}'),
+    ('\u{1EE34}', '\u{1EE37}'),
+    ('\u{1EE39}', '\u{1EE39}'),
+    ('\u{1EE3B}', '\u{1EE3B}'),
+    ('\u{1EE42}', '\u{1EE42}'),
+    ('\u{1EE47}', '\u{1EE47}'),
+    ('\u{1EE49}', '\u{1EE49}'),
+    ('\u{1EE4B}', '\u{1EE4B}'),
+    ('\u{1EE4D}', '\u{1EE4F}'),
+    ('\u{1EE51}', '\u{1EE52}'),
+    ('\u{1EE54}', '\u{1EE54}'),
+    ('\u{1EE57}', '\u{1EE57}'),
+    ('\u{1EE59}', '\u{1EE59}'),
+    ('\u{1EE5B}', '\u{1EE5B}'),
+    ('\u{1EE5D}', '\u{1EE5D}'),
+    ('\u{1EE5F}', '\u{1EE5F}'),
+    ('\u{1EE61}', '\u{1EE62}'),
+    ('\u{1EE64}', '\u{1EE64}'),
+    ('\u{1EE67}', '\u{1EE6A}'),
+    ('\u{1EE6C}', '\u{1EE72}'),
+    ('\u{1EE74}', '\u{1EE77}'),
+    ('\u{1EE79}', '\u{1EE7C}'),
+    ('\u{1EE7E}', '\u{1EE7E}'),
+    ('\u{1EE80}', '\u{1EE89}'),
+    ('\u{1EE8B}', '\u{1EE9B}'),
+    ('\u{1EEA1}', '\u{1EEA3}'),
+    ('\u{1EEA5}', '\u{1EEA9}'),
+    ('\u{1EEAB}', '\u{1EEBB}'),
+    ('\u{1F100}', '\u{1F10C}'),
+    ('\u{1F130}', '\u{1F149}'),
+    ('\u{1F150}', '\u{1F169}'),
+    ('\u{1F170}', '\u{1F189}'),
+    ('\u{1FBF0}', '\u{1FBF9}'),
+    ('\u{20000}', '\u{2A6DF}'),
+    ('\u{2A700}', '\u{2B81D}'),
+    ('\u{2B820}', '\u{2CEAD}'),
+    ('\u{2CEB0}', '\u{2EBE0}'),
+    ('\u{2EBF0}', '\u{2EE5D}'),
+    ('\u{2F800}', '\u{2FA1D}'),
+    ('\u{30000}', '\u{3134A}'),
+    ('\u{31350}', '\u{33479}'),
+];
+
+// Unicode 17.0.0 simple case folding orbits, from the single-char lowercase
+// and uppercase mappings of `char::to_lowercase` and `char::to_uppercase`.
+// Each char with case variants is paired with the other chars in its orbit,
+// sorted by char.
+const CASE_ORBITS: &[(char, &[char])] = &[
+    ('\u{41}', &['\u{61}']),
+    ('\u{42}', &['\u{62}']),
+    ('\u{43}', &['\u{63}']),
+    ('\u{44}', &['\u{64}']),
+    ('\u{45}', &['\u{65}']),
+    ('\u{46}', &['\u{66}']),
+    ('\u{47}', &['\u{67}']),
+    ('\u{48}', &['\u{68}']),
+    ('\u{49}', &['\u{69}', '\u{131}']),
+    ('\u{4A}', &['\u{6A}']),
+    ('\u{4B}', &['\u{6B}', '\u{212A}']),
+    ('\u{4C}', &['\u{6C}']),
+    ('\u{4D}', &['\u{6D}']),
+    ('\u{4E}', &['\u{6E}']),
+    ('\u{4F}', &['\u{6F}']),
+    ('\u{50}', &['\u{70}']),
+    ('\u{51}', &['\u{71}']),
+    ('\u{52}', &['\u{72}']),
+    ('\u{53}', &['\u{73}', '\u{17F}']),
+    ('\u{54}', &['\u{74}']),
+    ('\u{55}', &['\u{75}']),
+    ('\u{56}', &['\u{76}']),
+    ('\u{57}', &['\u{77}']),
+    ('\u{58}', &['\u{78}']),
+    ('\u{59}', &['\u{79}']),
+    ('\u{5A}', &['\u{7A}']),
+    ('\u{61}', &['\u{41}']),
+    ('\u{62}', &['\u{42}']),
+    ('\u{63}', &['\u{43}']),
+    ('\u{64}', &['\u{44}']),
+    ('\u{65}', &['\u{45}']),
+    ('\u{66}', &['\u{46}']),
+    ('\u{67}', &['\u{47}']),
+    ('\u{68}', &['\u{48}']),
+    ('\u{69}', &['\u{49}', '\u{131}']),
+    ('\u{6A}', &['\u{4A}']),
+    ('\u{6B}', &['\u{4B}', '\u{212A}']),
+    ('\u{6C}', &['\u{4C}']),
+    ('\u{6D}', &['\u{4D}']),
+    ('\u{6E}', &['\u{4E}']),
+    ('\u{6F}', &['\u{4F}']),
+    ('\u{70}', &['\u{50}']),
+    ('\u{71}', &['\u{51}']),
+    ('\u{72}', &['\u{52}']),
+    ('\u{73}', &['\u{53}', '\u{17F}']),
+    ('\u{74}', &['\u{54}']),
+    ('\u{75}', &['\u{55}']),
+    ('\u{76}', &['\u{56}']),
+    ('\u{77}', &['\u{57}']),
+    ('\u{78}', &['\u{58}']),
+    ('\u{79}', &['\u{59}']),
+    ('\u{7A}', &['\u{5A}']),
+    ('\u{B5}', &['\u{39C}', '\u{3BC}']),
+    ('\u{C0}', &['\u{E0}']),
+    ('\u{C1}', &['\u{E1}']),
+    ('\u{C2}', &['\u{E2}']),
+    ('\u{C3}', &['\u{E3}']),
+    ('\u{C4}', &['\u{E4}']),
+    ('\u{C5}', &['\u{E5}', '\u{212B}']),
+    ('\u{C6}', &['\u{E6}']),
+    ('\u{C7}', &['\u{E7}']),
+    ('\u{C8}', &['\u{E8}']),
+    ('\u{C9}', &['\u{E9}']),
+    ('\u{CA}', &['\u{EA}']),
+    ('\u{CB}', &['\u{EB}']),
+    ('\u{CC}', &['\u{EC}']),
+    ('\u{CD}', &['\u{ED}']),
+    ('\u{CE}', &['\u{EE}']),
+    ('\u{CF}', &['\u{EF}']),
+    ('\u{D0}', &['\u{F0}']),
+    ('\u{D1}', &['\u{F1}']),
+    ('\u{D2}', &['\u{F2}']),
+    ('\u{D3}', &['\u{F3}']),
+    ('\u{D4}', &['\u{F4}']),
+    ('\u{D5}', &['\u{F5}']),
+    ('\u{D6}', &['\u{F6}']),
+    ('\u{D8}', &['\u{F8}']),
+    ('\u{D9}', &['\u{F9}']),
+    ('\u{DA}', &['\u{FA}']),
+    ('\u{DB}', &['\u{FB}']),
+    ('\u{DC}', &['\u{FC}']),
+    ('\u{DD}', &['\u{FD}']),
+    ('\u{DE}', &['\u{FE}']),
+    ('\u{DF}', &['\u{1E9E}']),
+    ('\u{E0}', &['\u{C0}']),
+    ('\u{E1}', &['\u{C1}']),
+    ('\u{E2}', &['\u{C2}']),
+    ('\u{E3}', &['\u{C3}']),
+    ('\u{E4}', &['\u{C4}']),
+    ('\u{E5}', &['\u{C5}', '\u{212B}']),
+    ('\u{E6}', &['\u{C6}']),
+    ('\u{E7}', &['\u{C7}']),
+    ('\u{E8}', &['\u{C8}']),
+    ('\u{E9}', &['\u{C9}']),
+    ('\u{EA}', &['\u{CA}']),
+    ('\u{EB}', &['\u{CB}']),
+    ('\u{EC}', &['\u{CC}']),
+    ('\u{ED}', &['\u{CD}']),
+    ('\u{EE}', &['\u{CE}']),
+    ('\u{EF}', &['\u{CF}']),
+    ('\u{F0}', &['\u{D0}']),
+    ('\u{F1}', &['\u{D1}']),
+    ('\u{F2}', &['\u{D2}']),
+    ('\u{F3}', &['\u{D3}']),
+    ('\u{F4}', &['\u{D4}']),
+    ('\u{F5}', &['\u{D5}']),
+    ('\u{F6}', &['\u{D6}']),
+    ('\u{F8}', &['\u{D8}']),
+    ('\u{F9}', &['\u{D9}']),
+    ('\u{FA}', &['\u{DA}']),
+    ('\u{FB}', &['\u{DB}']),
+    ('\u{FC}', &['\u{DC}']),
+    ('\u{FD}', &['\u{DD}']),
+    ('\u{FE}', &['\u{DE}']),
+    ('\u{FF}', &['\u{178}']),
+    ('\u{100}', &['\u{101}']),
+    ('\u{101}', &['\u{100}']),
+    ('\u{102}', &['\u{103}']),
+    ('\u{103}', &['\u{102}']),
+    ('\u{104}', &['\u{105}']),
+    ('\u{105}', &['\u{104}']),
+    ('\u{106}', &['\u{107}']),
+    ('\u{107}', &['\u{106}']),
+    ('\u{108}', &['\u{109}']),
+    ('\u{109}', &['\u{108}']),
+    ('\u{10A}', &['\u{10B}']),
+    ('\u{10B}', &['\u{10A}']),
+    ('\u{10C}', &['\u{10D}']),
+    ('\u{10D}', &['\u{10C}']),
+    ('\u{10E}', &['\u{10F}']),
+    ('\u{10F}', &['\u{10E}']),
+    ('\u{110}', &['\u{111}']),
+    ('\u{111}', &['\u{110}']),
+    ('\u{112}', &['\u{113}']),
+    ('\u{113}', &['\u{112}']),
+    ('\u{114}', &['\u{115}']),
+    ('\u{115}', &['\u{114}']),
+    ('\u{116}', &['\u{117}']),
+    ('\u{117}', &['\u{116}']),
+    ('\u{118}', &['\u{119}']),
+    ('\u{119}', &['\u{118}']),
+    ('\u{11A}', &['\u{11B}']),
+    ('\u{11B}', &['\u{11A}']),
+    ('\u{11C}', &['\u{11D}']),
+    ('\u{11D}', &['\u{11C}']),
+    ('\u{11E}', &['\u{11F}']),
+    ('\u{11F}', &['\u{11E}']),
+    ('\u{120}', &['\u{121}']),
+    ('\u{121}', &['\u{120}']),
+    ('\u{122}', &['\u{123}']),
+    ('\u{123}', &['\u{122}']),
+    ('\u{124}', &['\u{125}']),
+    ('\u{125}', &['\u{124}']),
+    ('\u{126}', &['\u{127}']),
+    ('\u{127}', &['\u{126}']),
+    ('\u{128}', &['\u{129}']),
+    ('\u{129}', &['\u{128}']),
+    ('\u{12A}', &['\u{12B}']),
+    ('\u{12B}', &['\u{12A}']),
+    ('\u{12C}', &['\u{12D}']),
+    ('\u{12D}', &['\u{12C}']),
+    ('\u{12E}', &['\u{12F}']),
+    ('\u{12F}', &['\u{12E}']),
+    ('\u{131}', &['\u{49}', '\u{69}']),
+    ('\u{132}', &['\u{133}']),
+    ('\u{133}', &['\u{132}']),
+    ('\u{134}', &['\u{135}']),
+    ('\u{135}', &['\u{134}']),
+    ('\u{136}', &['\u{137}']),
+    ('\u{137}', &['\u{136}']),
+    ('\u{139}', &['\u{13A}']),
+    ('\u{13A}', &['\u{139}']),
+    ('\u{13B}', &['\u{13C}']),
+    ('\u{13C}', &['\u{13B}']),
+    ('\u{13D}', &['\u{13E}']),
+    ('\u{13E}', &['\u{13D}']),
+    ('\u{13F}', &['\u{140}']),
+    ('\u{140}', &['\u{13F}']),
+    ('\u{141}', &['\u{142}']),
+    ('\u{142}', &['\u{141}']),
+    ('\u{143}', &['\u{144}']),
+    ('\u{144}', &['\u{143}']),
+    ('\u{145}', &['\u{146}']),
+    ('\u{146}', &['\u{145}']),
+    ('\u{147}', &['\u{148}']),
+    ('\u{148}', &['\u{147}']),
+    ('\u{14A}', &['\u{14B}']),
+    ('\u{14B}', &['\u{14A}']),
+    ('\u{14C}', &['\u{14D}']),
+    ('\u{14D}', &['\u{14C}']),
+    ('\u{14E}', &['\u{14F}']),
+    ('\u{14F}', &['\u{14E}']),
+    ('\u{150}', &['\u{151}']),
+    ('\u{151}', &['\u{150}']),
+    ('\u{152}', &['\u{153}']),
+    ('\u{153}', &['\u{152}']),
+    ('\u{154}', &['\u{155}']),
+    ('\u{155}', &['\u{154}']),
+    ('\u{156}', &['\u{157}']),
+    ('\u{157}', &['\u{156}']),
+    ('\u{158}', &['\u{159}']),
+    ('\u{159}', &['\u{158}']),
+    ('\u{15A}', &['\u{15B}']),
+    ('\u{15B}', &['\u{15A}']),
+    ('\u{15C}', &['\u{15D}']),
+    ('\u{15D}', &['\u{15C}']),
+    ('\u{15E}', &['\u{15F}']),
+    ('\u{15F}', &['\u{15E}']),
+    ('\u{160}', &['\u{161}']),
+    ('\u{161}', &['\u{160}']),
+    ('\u{162}', &['\u{163}']),
+    ('\u{163}', &['\u{162}']),
+    ('\u{164}', &['\u{165}']),
+    ('\u{165}', &['\u{164}']),
+    ('\u{166}', &['\u{167}']),
+    ('\u{167}', &['\u{166}']),
+    ('\u{168}', &['\u{169}']),
+    ('\u{169}', &['\u{168}']),
+    ('\u{16A}', &['\u{16B}']),
+    ('\u{16B}', &['\u{16A}']),
+    ('\u{16C}', &['\u{16D}']),
+    ('\u{16D}', &['\u{16C}']),
+    ('\u{16E}', &['\u{16F}']),
+    ('\u{16F}', &['\u{16E}']),
+    ('\u{170}', &['\u{171}']),
+    ('\u{171}', &['\u{170}']),
+    ('\u{172}', &['\u{173}']),
+    ('\u{173}', &['\u{172}']),
+    ('\u{174}', &['\u{175}']),
+    ('\u{175}', &['\u{174}']),
+    ('\u{176}', &['\u{177}']),
+    ('\u{177}', &['\u{176}']),
+    ('\u{178}', &['\u{FF}']),
+    ('\u{179}', &['\u{17A}']),
+    ('\u{17A}', &['\u{179}']),
+    ('\u{17B}', &['\u{17C}']),
+    ('\u{17C}', &['\u{17B}']),
+    ('\u{17D}', &['\u{17E}']),
+    ('\u{17E}', &['\u{17D}']),
+    ('\u{17F}', &['\u{53}', '\u{73}']),
+    ('\u{180}', &['\u{243}']),
+    ('\u{181}', &['\u{253}']),
+    ('\u{182}', &['\u{183}']),
+    ('\u{183}', &['\u{182}']),
+    ('\u{184}', &['\u{185}']),
+    ('\u{185}', &['\u{184}']),
+    ('\u{186}', &['\u{254}']),
+    ('\u{187}', &['\u{188}']),
+    ('\u{188}', &['\u{187}']),
+    ('\u{189}', &['\u{256}']),
+    ('\u{18A}', &['\u{257}']),
+    ('\u{18B}', &['\u{18C}']),
+    ('\u{18C}', &['\u{18B}']),
+    ('\u{18E}', &['\u{1DD}']),
+    ('\u{18F}', &['\u{259}']),
+    ('\u{190}', &['\u{25B}']),
+    ('\u{191}', &['\u{192}']),
+    ('\u{192}', &['\u{191}']),
+    ('\u{193}', &['\u{260}']),
+    ('\u{194}', &['\u{263}']),
+    ('\u{195}', &['\u{1F6}']),
+    ('\u{196}', &['\u{269}']),
+    ('\u{197}', &['\u{268}']),
+    ('\u{198}', &['\u{199}']),
+    ('\u{199}', &['\u{198}']),
+    ('\u{19A}', &['\u{23D}']),
+    ('\u{19B}', &['\u{A7DC}']),
+    ('\u{19C}', &['\u{26F}']),
+    ('\u{19D}', &['\u{272}']),
+    ('\u{19E}', &['\u{220}']),
+    ('\u{19F}', &['\u{275}']),
+    ('\u{1A0}', &['\u{1A1}']),
+    ('\u{1A1}', &['\u{1A0}']),
+    ('\u{1A2}', &['\u{1A3}']),
+    ('\u{1A3}', &['\u{1A2}']),
+    ('\u{1A4}', &['\u{1A5}']),
+    ('\u{1A5}', &['\u{1A4}']),
+    ('\u{1A6}', &['\u{280}']),
+    ('\u{1A7}', &['\u{1A8}']),
+    ('\u{1A8}', &['\u{1A7}']),
+    ('\u{1A9}', &['\u{283}']),
+    ('\u{1AC}', &['\u{1AD}']),
+    ('\u{1AD}', &['\u{1AC}']),
+    ('\u{1AE}', &['\u{288}']),
+    ('\u{1AF}', &['\u{1B0}']),
+    ('\u{1B0}', &['\u{1AF}']),
+    ('\u{1B1}', &['\u{28A}']),
+    ('\u{1B2}', &['\u{28B}']),
+    ('\u{1B3}', &['\u{1B4}']),
+    ('\u{1B4}', &['\u{1B3}']),
+    ('\u{1B5}', &['\u{1B6}']),
+    ('\u{1B6}', &['\u{1B5}']),
+    ('\u{1B7}', &['\u{292}']),
+    ('\u{1B8}', &['\u{1B9}']),
+    ('\u{1B9}', &['\u{1B8}']),
+    ('\u{1BC}', &['\u{1BD}']),
+    ('\u{1BD}', &['\u{1BC}']),
+    ('\u{1BF}', &['\u{1F7}']),
+    ('\u{1C4}', &['\u{1C5}', '\u{1C6}']),
+    ('\u{1C5}', &['\u{1C4}', '\u{1C6}']),
+    ('\u{1C6}', &['\u{1C4}', '\u{1C5}']),
+    ('\u{1C7}', &['\u{1C8}', '\u{1C9}']),
+    ('\u{1C8}', &['\u{1C7}', '\u{1C9}']),
+    ('\u{1C9}', &['\u{1C7}', '\u{1C8}']),
+    ('\u{1CA}', &['\u{1CB}', '\u{1CC}']),
+    ('\u{1CB}', &['\u{1CA}', '\u{1CC}']),
+    ('\u{1CC}', &['\u{1CA}', '\u{1CB}']),
+    ('\u{1CD}', &['\u{1CE}']),
+    ('\u{1CE}', &['\u{1CD}']),
+    ('\u{1CF}', &['\u{1D0}']),
+    ('\u{1D0}', &['\u{1CF}']),
+    ('\u{1D1}', &['\u{1D2}']),
+    ('\u{1D2}', &['\u{1D1}']),
+    ('\u{1D3}', &['\u{1D4}']),
+    ('\u{1D4}', &['\u{1D3}']),
+    ('\u{1D5}', &['\u{1D6}']),
+    ('\u{1D6}', &['\u{1D5}']),
+    ('\u{1D7}', &['\u{1D8}']),
+    ('\u{1D8}', &['\u{1D7}']),
+    ('\u{1D9}', &['\u{1DA}']),
+    ('\u{1DA}', &['\u{1D9}']),
+    ('\u{1DB}', &['\u{1DC}']),
+    ('\u{1DC}', &['\u{1DB}']),
+    ('\u{1DD}', &['\u{18E}']),
+    ('\u{1DE}', &['\u{1DF}']),
+    ('\u{1DF}', &['\u{1DE}']),
+    ('\u{1E0}', &['\u{1E1}']),
+    ('\u{1E1}', &['\u{1E0}']),
+    ('\u{1E2}', &['\u{1E3}']),
+    ('\u{1E3}', &['\u{1E2}']),
+    ('\u{1E4}', &['\u{1E5}']),
+    ('\u{1E5}', &['\u{1E4}']),
+    ('\u{1E6}', &['\u{1E7}']),
+    ('\u{1E7}', &['\u{1E6}']),
+    ('\u{1E8}', &['\u{1E9}']),
+    ('\u{1E9}', &['\u{1E8}']),
+    ('\u{1EA}', &['\u{1EB}']),
+    ('\u{1EB}', &['\u{1EA}']),
+    ('\u{1EC}', &['\u{1ED}']),
+    ('\u{1ED}', &['\u{1EC}']),
+    ('\u{1EE}', &['\u{1EF}']),
+    ('\u{1EF}', &['\u{1EE}']),
+    ('\u{1F1}', &['\u{1F2}', '\u{1F3}']),
+    ('\u{1F2}', &['\u{1F1}', '\u{1F3}']),
+    ('\u{1F3}', &['\u{1F1}', '\u{1F2}']),
+    ('\u{1F4}', &['\u{1F5}']),
+    ('\u{1F5}', &['\u{1F4}']),
+    ('\u{1F6}', &['\u{195}']),
+    ('\u{1F7}', &['\u{1BF}']),
+    ('\u{1F8}', &['\u{1F9}']),
+    ('\u{1F9}', &['\u{1F8}']),
+    ('\u{1FA}', &['\u{1FB}']),
+    ('\u{1FB}', &['\u{1FA}']),
+    ('\u{1FC}', &['\u{1FD}']),
+    ('\u{1FD}', &['\u{1FC}']),
+    ('\u{1FE}', &['\u{1FF}']),
+    ('\u{1FF}', &['\u{1FE}']),
+    ('\u{200}', &['\u{201}']),
+    ('\u{201}', &['\u{200}']),
+    ('\u{202}', &['\u{203}']),
+    ('\u{203}', &['\u{202}']),
+    ('\u{204}', &['\u{205}']),
+    ('\u{205}', &['\u{204}']),
+    ('\u{206}', &['\u{207}']),
+    ('\u{207}', &['\u{206}']),
+    ('\u{208}', &['\u{209}']),
+    ('\u{209}', &['\u{208}']),
+    ('\u{20A}', &['\u{20B}']),
+    ('\u{20B}', &['\u{20A}']),
+    ('\u{20C}', &['\u{20D}']),
+    ('\u{20D}', &['\u{20C}']),
+    ('\u{20E}', &['\u{20F}']),
+    ('\u{20F}', &['\u{20E}']),
+    ('\u{210}', &['\u{211}']),
+    ('\u{211}', &['\u{210}']),
+    ('\u{212}', &['\u{213}']),
+    ('\u{213}', &['\u{212}']),
+    ('\u{214}', &['\u{215}']),
+    ('\u{215}', &['\u{214}']),
+    ('\u{216}', &['\u{217}']),
+    ('\u{217}', &['\u{216}']),
+    ('\u{218}', &['\u{219}']),
+    ('\u{219}', &['\u{218}']),
+    ('\u{21A}', &['\u{21B}']),
+    ('\u{21B}', &['\u{21A}']),
+    ('\u{21C}', &['\u{21D}']),
+    ('\u{21D}', &['\u{21C}']),
+    ('\u{21E}', &['\u{21F}']),
+    ('\u{21F}', &['\u{21E}']),
+    ('\u{220}', &['\u{19E}']),
+    ('\u{222}', &['\u{223}']),
+    ('\u{223}', &['\u{222}']),
+    ('\u{224}', &['\u{225}']),
+    ('\u{225}', &['\u{224}']),
+    ('\u{226}', &['\u{227}']),
+    ('\u{227}', &['\u{226}']),
+    ('\u{228}', &['\u{229}']),
+    ('\u{229}', &['\u{228}']),
+    ('\u{22A}', &['\u{22B}']),
+    ('\u{22B}', &['\u{22A}']),
+    ('\u{22C}', &['\u{22D}']),
+    ('\u{22D}', &['\u{22C}']),
+    ('\u{22E}', &['\u{22F}']),
+    ('\u{22F}', &['\u{22E}']),
+    ('\u{230}', &['\u{231}']),
+    ('\u{231}', &['\u{230}']),
+    ('\u{232}', &['\u{233}']),
+    ('\u{233}', &['\u{232}']),
+    ('\u{23A}', &['\u{2C65}']),
+    ('\u{23B}', &['\u{23C}']),
+    ('\u{23C}', &['\u{23B}']),
+    ('\u{23D}', &['\u{19A}']),
+    ('\u{23E}', &['\u{2C66}']),
+    ('\u{23F}', &['\u{2C7E}']),
+    ('\u{240}', &['\u{2C7F}']),
+    ('\u{241}', &['\u{242}']),
+    ('\u{242}', &['\u{241}']),
+    ('\u{243}', &['\u{180}']),
+    ('\u{244}', &['\u{289}']),
+    ('\u{245}', &['\u{28C}']),
+    ('\u{246}', &['\u{247}']),
+    ('\u{247}', &['\u{246}']),
+    ('\u{248}', &['\u{249}']),
+    ('\u{249}', &['\u{248}']),
+    ('\u{24A}', &['\u{24B}']),
+    ('\u{24B}', &['\u{24A}']),
+    ('\u{24C}', &['\u{24D}']),
+    ('\u{24D}', &['\u{24C}']),
+    ('\u{24E}', &['\u{24F}']),
+    ('\u{24F}', &['\u{24E}']),
+    ('\u{250}', &['\u{2C6F}']),
+    ('\u{251}', &['\u{2C6D}']),
+    ('\u{252}', &['\u{2C70}']),
+    ('\u{253}', &['\u{181}']),
+    ('\u{254}', &['\u{186}']),
+    ('\u{256}', &['\u{189}']),
+    ('\u{257}', &['\u{18A}']),
+    ('\u{259}', &['\u{18F}']),
+    ('\u{25B}', &['\u{190}']),
+    ('\u{25C}', &['\u{A7AB}']),
+    ('\u{260}', &['\u{193}']),
+    ('\u{261}', &['\u{A7AC}']),
+    ('\u{263}', &['\u{194}']),
+    ('\u{264}', &['\u{A7CB}']),
+    ('\u{265}', &['\u{A78D}']),
+    ('\u{266}', &['\u{A7AA}']),
+    ('\u{268}', &['\u{197}']),
+    ('\u{269}', &['\u{196}']),
+    ('\u{26A}', &['\u{A7AE}']),
+    ('\u{26B}', &['\u{2C62}']),
+    ('\u{26C}', &['\u{A7AD}']),
+    ('\u{26F}', &['\u{19C}']),
+    ('\u{271}', &['\u{2C6E}']),
+    ('\u{272}', &['\u{19D}']),
+    ('\u{275}', &['\u{19F}']),
+    ('\u{27D}', &['\u{2C64}']),
+    ('\u{280}', &['\u{1A6}']),
+    ('\u{282}', &['\u{A7C5}']),
+    ('\u{283}', &['\u{1A9}']),
+    ('\u{287}', &['\u{A7B1}']),
+    ('\u{288}', &['\u{1AE}']),
+    ('\u{289}', &['\u{244}']),
+    ('\u{28A}', &['\u{1B1}']),
+    ('\u{28B}', &['\u{1B2}']),
+    ('\u{28C}', &['\u{245}']),
+    ('\u{292}', &['\u{1B7}']),
+    ('\u{29D}', &['\u{A7B2}']),
+    ('\u{29E}', &['\u{A7B0}']),
+    ('\u{345}', &['\u{399}', '\u{3B9}', '\u{1FBE}']),
+    ('\u{370}', &['\u{371}']),
+    ('\u{371}', &['\u{370}']),
+    ('\u{372}', &['\u{373}']),
+    ('\u{373}', &['\u{372}']),
+    ('\u{376}', &['\u{377}']),
+    ('\u{377}', &['\u{376}']),
+    ('\u{37B}', &['\u{3FD}']),
+    ('\u{37C}', &['\u{3FE}']),
+    ('\u{37D}', &['\u{3FF}']),
+    ('\u{37F}', &['\u{3F3}']),
+    ('\u{386}', &['\u{3AC}']),
+    ('\u{388}', &['\u{3AD}']),
+    ('\u{389}', &['\u{3AE}']),
+    ('\u{38A}', &['\u{3AF}']),
+    ('\u{38C}', &['\u{3CC}']),
+    ('\u{38E}', &['\u{3CD}']),
+    ('\u{38F}', &['\u{3CE}']),
+    ('\u{391}', &['\u{3B1}']),
+    ('\u{392}', &['\u{3B2}', '\u{3D0}']),
+    ('\u{393}', &['\u{3B3}']),
+    ('\u{394}', &['\u{3B4}']),
+    ('\u{395}', &['\u{3B5}', '\u{3F5}']),
+    ('\u{396}', &['\u{3B6}']),
+    ('\u{397}', &['\u{3B7}']),
+    ('\u{398}', &['\u{3B8}', '\u{3D1}', '\u{3F4}']),
+    ('\u{399}', &['\u{345}', '\u{3B9}', '\u{1FBE}']),
+    ('\u{39A}', &['\u{3BA}', '\u{3F0}']),
+    ('\u{39B}', &['\u{3BB}']),
+    ('\u{39C}', &['\u{B5}', '\u{3BC}']),
+    ('\u{39D}', &['\u{3BD}']),
+    ('\u{39E}', &['\u{3BE}']),
+    ('\u{39F}', &['\u{3BF}']),
+    ('\u{3A0}', &['\u{3C0}', '\u{3D6}']),
+    ('\u{3A1}', &['\u{3C1}', '\u{3F1}']),
+    ('\u{3A3}', &['\u{3C2}', '\u{3C3}']),
+    ('\u{3A4}', &['\u{3C4}']),
+    ('\u{3A5}', &['\u{3C5}']),
+    ('\u{3A6}', &['\u{3C6}', '\u{3D5}']),
+    ('\u{3A7}', &['\u{3C7}']),
+    ('\u{3A8}', &['\u{3C8}']),
+    ('\u{3A9}', &['\u{3C9}', '\u{2126}']),
+    ('\u{3AA}', &['\u{3CA}']),
+    ('\u{3AB}', &['\u{3CB}']),
+    ('\u{3AC}', &['\u{386}']),
+    ('\u{3AD}', &['\u{388}']),
+    ('\u{3AE}', &['\u{389}']),
+    ('\u{3AF}', &['\u{38A}']),
+    ('\u{3B1}', &['\u{391}']),
+    ('\u{3B2}', &['\u{392}', '\u{3D0}']),
+    ('\u{3B3}', &['\u{393}']),
+    ('\u{3B4}', &['\u{394}']),
+    ('\u{3B5}', &['\u{395}', '\u{3F5}']),
+    ('\u{3B6}', &['\u{396}']),
+    ('\u{3B7}', &['\u{397}']),
+    ('\u{3B8}', &['\u{398}', '\u{3D1}', '\u{3F4}']),
+    ('\u{3B9}', &['\u{345}', '\u{399}', '\u{1FBE}']),
+    ('\u{3BA}', &['\u{39A}', '\u{3F0}']),
+    ('\u{3BB}', &['\u{39B}']),
+    ('\u{3BC}', &['\u{B5}', '\u{39C}']),
+    ('\u{3BD}', &['\u{39D}']),
+    ('\u{3BE}', &['\u{39E}']),
+    ('\u{3BF}', &['\u{39F}']),
+    ('\u{3C0}', &['\u{3A0}', '\u{3D6}']),
+    ('\u{3C1}', &['\u{3A1}', '\u{3F1}']),
+    ('\u{3C2}', &['\u{3A3}', '\u{3C3}']),
+    ('\u{3C3}', &['\u{3A3}', '\u{3C2}']),
+    ('\u{3C4}', &['\u{3A4}']),
+    ('\u{3C5}', &['\u{3A5}']),
+    ('\u{3C6}', &['\u{3A6}', '\u{3D5}']),
+    ('\u{3C7}', &['\u{3A7}']),
+    ('\u{3C8}', &['\u{3A8}']),
+    ('\u{3C9}', &['\u{3A9}', '\u{2126}']),
+    ('\u{3CA}', &['\u{3AA}']),
+    ('\u{3CB}', &['\u{3AB}']),
+    ('\u{3CC}', &['\u{38C}']),
+    ('\u{3CD}', &['\u{38E}']),
+    ('\u{3CE}', &['\u{38F}']),
+    ('\u{3CF}', &['\u{3D7}']),
+    ('\u{3D0}', &['\u{392}', '\u{3B2}']),
+    ('\u{3D1}', &['\u{398}', '\u{3B8}', '\u{3F4}']),
+    ('\u{3D5}', &['\u{3A6}', '\u{3C6}']),
+    ('\u{3D6}', &['\u{3A0}', '\u{3C0}']),
+    ('\u{3D7}', &['\u{3CF}']),
+    ('\u{3D8}', &['\u{3D9}']),
+    ('\u{3D9}', &['\u{3D8}']),
+    ('\u{3DA}', &['\u{3DB}']),
+    ('\u{3DB}', &['\u{3DA}']),
+    ('\u{3DC}', &['\u{3DD}']),
+    ('\u{3DD}', &['\u{3DC}']),
+    ('\u{3DE}', &['\u{3DF}']),
+    ('\u{3DF}', &['\u{3DE}']),
+    ('\u{3E0}', &['\u{3E1}']),
+    ('\u{3E1}', &['\u{3E0}']),
+    ('\u{3E2}', &['\u{3E3}']),
+    ('\u{3E3}', &['\u{3E2}']),
+    ('\u{3E4}', &['\u{3E5}']),
+    ('\u{3E5}', &['\u{3E4}']),
+    ('\u{3E6}', &['\u{3E7}']),
+    ('\u{3E7}', &['\u{3E6}']),
+    ('\u{3E8}', &['\u{3E9}']),
+    ('\u{3E9}', &['\u{3E8}']),
+    ('\u{3EA}', &['\u{3EB}']),
+    ('\u{3EB}', &['\u{3EA}']),
+    ('\u{3EC}', &['\u{3ED}']),
+    ('\u{3ED}', &['\u{3EC}']),
+    ('\u{3EE}', &['\u{3EF}']),
+    ('\u{3EF}', &['\u{3EE}']),
+    ('\u{3F0}', &['\u{39A}', '\u{3BA}']),
+    ('\u{3F1}', &['\u{3A1}', '\u{3C1}']),
+    ('\u{3F2}', &['\u{3F9}']),
+    ('\u{3F3}', &['\u{37F}']),
+    ('\u{3F4}', &['\u{398}', '\u{3B8}', '\u{3D1}']),
+    ('\u{3F5}', &['\u{395}', '\u{3B5}']),
+    ('\u{3F7}', &['\u{3F8}']),
+    ('\u{3F8}', &['\u{3F7}']),
+    ('\u{3F9}', &['\u{3F2}']),
+    ('\u{3FA}', &['\u{3FB}']),
+    ('\u{3FB}', &['\u{3FA}']),
+    ('\u{3FD}', &['\u{37B}']),
+    ('\u{3FE}', &['\u{37C}']),
+    ('\u{3FF}', &['\u{37D}']),
+    ('\u{400}', &['\u{450}']),
+    ('\u{401}', &['\u{451}']),
+    ('\u{402}', &['\u{452}']),
+    ('\u{403}', &['\u{453}']),
+    ('\u{404}', &['\u{454}']),
+    ('\u{405}', &['\u{455}']),
+    ('\u{406}', &['\u{456}']),
+    ('\u{407}', &['\u{457}']),
+    ('\u{408}', &['\u{458}']),
+    ('\u{409}', &['\u{459}']),
+    ('\u{40A}', &['\u{45A}']),
+    ('\u{40B}', &['\u{45B}']),
+    ('\u{40C}', &['\u{45C}']),
+    ('\u{40D}', &['\u{45D}']),
+    ('\u{40E}', &['\u{45E}']),
+    ('\u{40F}', &['\u{45F}']),
+    ('\u{410}', &['\u{430}']),
+    ('\u{411}', &['\u{431}']),
+    ('\u{412}', &['\u{432}', '\u{1C80}']),
+    ('\u{413}', &['\u{433}']),
+    ('\u{414}', &['\u{434}', '\u{1C81}']),
+    ('\u{415}', &['\u{435}']),
+    ('\u{416}', &['\u{436}']),
+    ('\u{417}', &['\u{437}']),
+    ('\u{418}', &['\u{438}']),
+    ('\u{419}', &['\u{439}']),
+    ('\u{41A}', &['\u{43A}']),
+    ('\u{41B}', &['\u{43B}']),
+    ('\u{41C}', &['\u{43C}']),
+    ('\u{41D}', &['\u{43D}']),
+    ('\u{41E}', &['\u{43E}', '\u{1C82}']),
+    ('\u{41F}', &['\u{43F}']),
+    ('\u{420}', &['\u{440}']),
+    ('\u{421}', &['\u{441}', '\u{1C83}']),
+    ('\u{422}', &['\u{442}', '\u{1C84}', '\u{1C85}']),
+    ('\u{423}', &['\u{443}']),
+    ('\u{424}', &['\u{444}']),
+    ('\u{425}', &['\u{445}']),
+    ('\u{426}', &['\u{446}']),
+    ('\u{427}', &['\u{447}']),
+    ('\u{428}', &['\u{448}']),
+    ('\u{429}', &['\u{449}']),
+    ('\u{42A}', &['\u{44A}', '\u{1C86}']),
+    ('\u{42B}', &['\u{44B}']),
+    ('\u{42C}', &['\u{44C}']),
+    ('\u{42D}', &['\u{44D}']),
+    ('\u{42E}', &['\u{44E}']),
+    ('\u{42F}', &['\u{44F}']),
+    ('\u{430}', &['\u{410}']),
+    ('\u{431}', &['\u{411}']),
+    ('\u{432}', &['\u{412}', '\u{1C80}']),
+    ('\u{433}', &['\u{413}']),
+    ('\u{434}', &['\u{414}', '\u{1C81}']),
+    ('\u{435}', &['\u{415}']),
+    ('\u{436}', &['\u{416}']),
+    ('\u{437}', &['\u{417}']),
+    ('\u{438}', &['\u{418}']),
+    ('\u{439}', &['\u{419}']),
+    ('\u{43A}', &['\u{41A}']),
+    ('\u{43B}', &['\u{41B}']),
+    ('\u{43C}', &['\u{41C}']),
+    ('\u{43D}', &['\u{41D}']),
+    ('\u{43E}', &['\u{41E}', '\u{1C82}']),
+    ('\u{43F}', &['\u{41F}']),
+    ('\u{440}', &['\u{420}']),
+    ('\u{441}', &['\u{421}', '\u{1C83}']),
+    ('\u{442}', &['\u{422}', '\u{1C84}', '\u{1C85}']),
+    ('\u{443}', &['\u{423}']),
+    ('\u{444}', &['\u{424}']),
+    ('\u{445}', &['\u{425}']),
+    ('\u{446}', &['\u{426}']),
+    ('\u{447}', &['\u{427}']),
+    ('\u{448}', &['\u{428}']),
+    ('\u{449}', &['\u{429}']),
+    ('\u{44A}', &['\u{42A}', '\u{1C86}']),
+    ('\u{44B}', &['\u{42B}']),
+    ('\u{44C}', &['\u{42C}']),
+    ('\u{44D}', &['\u{42D}']),
+    ('\u{44E}', &['\u{42E}']),
+    ('\u{44F}', &['\u{42F}']),
+    ('\u{450}', &['\u{400}']),
+    ('\u{451}', &['\u{401}']),
+    ('\u{452}', &['\u{402}']),
+    ('\u{453}', &['\u{403}']),
+    ('\u{454}', &['\u{404}']),
+    ('\u{455}', &['\u{405}']),
+    ('\u{456}', &['\u{406}']),
+    ('\u{457}', &['\u{407}']),
+    ('\u{458}', &['\u{408}']),
+    ('\u{459}', &['\u{409}']),
+    ('\u{45A}', &['\u{40A}']),
+    ('\u{45B}', &['\u{40B}']),
+    ('\u{45C}', &['\u{40C}']),
+    ('\u{45D}', &['\u{40D}']),
+    ('\u{45E}', &['\u{40E}']),
+    ('\u{45F}', &['\u{40F}']),
+    ('\u{460}', &['\u{461}']),
+    ('\u{461}', &['\u{460}']),
+    ('\u{462}', &['\u{463}', '\u{1C87}']),
+    ('\u{463}', &['\u{462}', '\u{1C87}']),
+    ('\u{464}', &['\u{465}']),
+    ('\u{465}', &['\u{464}']),
+    ('\u{466}', &['\u{467}']),
+    ('\u{467}', &['\u{466}']),
+    ('\u{468}', &['\u{469}']),
+    ('\u{469}', &['\u{468}']),
+    ('\u{46A}', &['\u{46B}']),
+    ('\u{46B}', &['\u{46A}']),
+    ('\u{46C}', &['\u{46D}']),
+    ('\u{46D}', &['\u{46C}']),
+    ('\u{46E}', &['\u{46F}']),
+    ('\u{46F}', &['\u{46E}']),
+    ('\u{470}', &['\u{471}']),
+    ('\u{471}', &['\u{470}']),
+    ('\u{472}', &['\u{473}']),
+    ('\u{473}', &['\u{472}']),
+    ('\u{474}', &['\u{475}']),
+    ('\u{475}', &['\u{474}']),
+    ('\u{476}', &['\u{477}']),
+    ('\u{477}', &['\u{476}']),
+    ('\u{478}', &['\u{479}']),
+    ('\u{479}', &['\u{478}']),
+    ('\u{47A}', &['\u{47B}']),
+    ('\u{47B}', &['\u{47A}']),
+    ('\u{47C}', &['\u{47D}']),
+    ('\u{47D}', &['\u{47C}']),
+    ('\u{47E}', &['\u{47F}']),
+    ('\u{47F}', &['\u{47E}']),
+    ('\u{480}', &['\u{481}']),
+    ('\u{481}', &['\u{480}']),
+    ('\u{48A}', &['\u{48B}']),
+    ('\u{48B}', &['\u{48A}']),
+    ('\u{48C}', &['\u{48D}']),
+    ('\u{48D}', &['\u{48C}']),
+    ('\u{48E}', &['\u{48F}']),
+    ('\u{48F}', &['\u{48E}']),
+    ('\u{490}', &['\u{491}']),
+    ('\u{491}', &['\u{490}']),
+    ('\u{492}', &['\u{493}']),
+    ('\u{493}', &['\u{492}']),
+    ('\u{494}', &['\u{495}']),
+    ('\u{495}', &['\u{494}']),
+    ('\u{496}', &['\u{497}']),
+    ('\u{497}', &['\u{496}']),
+    ('\u{498}', &['\u{499}']),
+    ('\u{499}', &['\u{498}']),
+    ('\u{49A}', &['\u{49B}']),
+    ('\u{49B}', &['\u{49A}']),
+    ('\u{49C}', &['\u{49D}']),
+    ('\u{49D}', &['\u{49C}']),
+    ('\u{49E}', &['\u{49F}']),
+    ('\u{49F}', &['\u{49E}']),
+    ('\u{4A0}', &['\u{4A1}']),
+    ('\u{4A1}', &['\u{4A0}']),
+    ('\u{4A2}', &['\u{4A3}']),
+    ('\u{4A3}', &['\u{4A2}']),
+    ('\u{4A4}', &['\u{4A5}']),
+    ('\u{4A5}', &['\u{4A4}']),
+    ('\u{4A6}', &['\u{4A7}']),
+    ('\u{4A7}', &['\u{4A6}']),
+    ('\u{4A8}', &['\u{4A9}']),
+    ('\u{4A9}', &['\u{4A8}']),
+    ('\u{4AA}', &['\u{4AB}']),
+    ('\u{4AB}', &['\u{4AA}']),
+    ('\u{4AC}', &['\u{4AD}']),
+    ('\u{4AD}', &['\u{4AC}']),
+    ('\u{4AE}', &['\u{4AF}']),
+    ('\u{4AF}', &['\u{4AE}']),
+    ('\u{4B0}', &['\u{4B1}']),
+    ('\u{4B1}', &['\u{4B0}']),
+    ('\u{4B2}', &['\u{4B3}']),
+    ('\u{4B3}', &['\u{4B2}']),
+    ('\u{4B4}', &['\u{4B5}']),
+    ('\u{4B5}', &['\u{4B4}']),
+    ('\u{4B6}', &['\u{4B7}']),
+    ('\u{4B7}', &['\u{4B6}']),
+    ('\u{4B8}', &['\u{4B9}']),
+    ('\u{4B9}', &['\u{4B8}']),
+    ('\u{4BA}', &['\u{4BB}']),
+    ('\u{4BB}', &['\u{4BA}']),
+    ('\u{4BC}', &['\u{4BD}']),
+    ('\u{4BD}', &['\u{4BC}']),
+    ('\u{4BE}', &['\u{4BF}']),
+    ('\u{4BF}', &['\u{4BE}']),
+    ('\u{4C0}', &['\u{4CF}']),
+    ('\u{4C1}', &['\u{4C2}']),
+    ('\u{4C2}', &['\u{4C1}']),
+    ('\u{4C3}', &['\u{4C4}']),
+    ('\u{4C4}', &['\u{4C3}']),
+    ('\u{4C5}', &['\u{4C6}']),
+    ('\u{4C6}', &['\u{4C5}']),
+    ('\u{4C7}', &['\u{4C8}']),
+    ('\u{4C8}', &['\u{4C7}']),
+    ('\u{4C9}', &['\u{4CA}']),
+    ('\u{4CA}', &['\u{4C9}']),
+    ('\u{4CB}', &['\u{4CC}']),
+    ('\u{4CC}', &['\u{4CB}']),
+    ('\u{4CD}', &['\u{4CE}']),
+    ('\u{4CE}', &['\u{4CD}']),
+    ('\u{4CF}', &['\u{4C0}']),
+    ('\u{4D0}', &['\u{4D1}']),
+    ('\u{4D1}', &['\u{4D0}']),
+    ('\u{4D2}', &['\u{4D3}']),
+    ('\u{4D3}', &['\u{4D2}']),
+    ('\u{4D4}', &['\u{4D5}']),
+    ('\u{4D5}', &['\u{4D4}']),
+    ('\u{4D6}', &['\u{4D7}']),
+    ('\u{4D7}', &['\u{4D6}']),
+    ('\u{4D8}', &['\u{4D9}']),
+    ('\u{4D9}', &['\u{4D8}']),
+    ('\u{4DA}', &['\u{4DB}']),
+    ('\u{4DB}', &['\u{4DA}']),
+    ('\u{4DC}', &['\u{4DD}']),
+    ('\u{4DD}', &['\u{4DC}']),
+    ('\u{4DE}', &['\u{4DF}']),
+    ('\u{4DF}', &['\u{4DE}']),
+    ('\u{4E0}', &['\u{4E1}']),
+    ('\u{4E1}', &['\u{4E0}']),
+    ('\u{4E2}', &['\u{4E3}']),
+    ('\u{4E3}', &['\u{4E2}']),
+    ('\u{4E4}', &['\u{4E5}']),
+    ('\u{4E5}', &['\u{4E4}']),
+    ('\u{4E6}', &['\u{4E7}']),
+    ('\u{4E7}', &['\u{4E6}']),
+    ('\u{4E8}', &['\u{4E9}']),
+    ('\u{4E9}', &['\u{4E8}']),
+    ('\u{4EA}', &['\u{4EB}']),
+    ('\u{4EB}', &['\u{4EA}']),
+    ('\u{4EC}', &['\u{4ED}']),
+    ('\u{4ED}', &['\u{4EC}']),
+    ('\u{4EE}', &['\u{4EF}']),
+    ('\u{4EF}', &['\u{4EE}']),
+    ('\u{4F0}', &['\u{4F1}']),
+    ('\u{4F1}', &['\u{4F0}']),
+    ('\u{4F2}', &['\u{4F3}']),
+    ('\u{4F3}', &['\u{4F2}']),
+    ('\u{4F4}', &['\u{4F5}']),
+    ('\u{4F5}', &['\u{4F4}']),
+    ('\u{4F6}', &['\u{4F7}']),
+    ('\u{4F7}', &['\u{4F6}']),
+    ('\u{4F8}', &['\u{4F9}']),
+    ('\u{4F9}', &['\u{4F8}']),
+    ('\u{4FA}', &['\u{4FB}']),
+    ('\u{4FB}', &['\u{4FA}']),
+    ('\u{4FC}', &['\u{4FD}']),
+    ('\u{4FD}', &['\u{4FC}']),
+    ('\u{4FE}', &['\u{4FF}']),
+    ('\u{4FF}', &['\u{4FE}']),
+    ('\u{500}', &['\u{501}']),
+    ('\u{501}', &['\u{500}']),
+    ('\u{502}', &['\u{503}']),
+    ('\u{503}', &['\u{502}']),
+    ('\u{504}', &['\u{505}']),
+    ('\u{505}', &['\u{504}']),
+    ('\u{506}', &['\u{507}']),
+    ('\u{507}', &['\u{506}']),
+    ('\u{508}', &['\u{509}']),
+    ('\u{509}', &['\u{508}']),
+    ('\u{50A}', &['\u{50B}']),
+    ('\u{50B}', &['\u{50A}']),
+    ('\u{50C}', &['\u{50D}']),
+    ('\u{50D}', &['\u{50C}']),
+    ('\u{50E}', &['\u{50F}']),
+    ('\u{50F}', &['\u{50E}']),
+    ('\u{510}', &['\u{511}']),
+    ('\u{511}', &['\u{510}']),
+    ('\u{512}', &['\u{513}']),
+    ('\u{513}', &['\u{512}']),
+    ('\u{514}', &['\u{515}']),
+    ('\u{515}', &['\u{514}']),
+    ('\u{516}', &['\u{517}']),
+    ('\u{517}', &['\u{516}']),
+    ('\u{518}', &['\u{519}']),
+    ('\u{519}', &['\u{518}']),
+    ('\u{51A}', &['\u{51B}']),
+    ('\u{51B}', &['\u{51A}']),
+    ('\u{51C}', &['\u{51D}']),
+    ('\u{51D}', &['\u{51C}']),
+    ('\u{51E}', &['\u{51F}']),
+    ('\u{51F}', &['\u{51E}']),
+    ('\u{520}', &['\u{521}']),
+    ('\u{521}', &['\u{520}']),
+    ('\u{522}', &['\u{523}']),
+    ('\u{523}', &['\u{522}']),
+    ('\u{524}', &['\u{525}']),
+    ('\u{525}', &['\u{524}']),
+    ('\u{526}', &['\u{527}']),
+    ('\u{527}', &['\u{526}']),
+    ('\u{528}', &['\u{529}']),
+    ('\u{529}', &['\u{528}']),
+    ('\u{52A}', &['\u{52B}']),
+    ('\u{52B}', &['\u{52A}']),
+    ('\u{52C}', &['\u{52D}']),
+    ('\u{52D}', &['\u{52C}']),
+    ('\u{52E}', &['\u{52F}']),
+    ('\u{52F}', &['\u{52E}']),
+    ('\u{531}', &['\u{561}']),
+    ('\u{532}', &['\u{562}']),
+    ('\u{533}', &['\u{563}']),
+    ('\u{534}', &['\u{564}']),
+    ('\u{535}', &['\u{565}']),
+    ('\u{536}', &['\u{566}']),
+    ('\u{537}', &['\u{567}']),
+    ('\u{538}', &['\u{568}']),
+    ('\u{539}', &['\u{569}']),
+    ('\u{53A}', &['\u{56A}']),
+    ('\u{53B}', &['\u{56B}']),
+    ('\u{53C}', &['\u{56C}']),
+    ('\u{53D}', &['\u{56D}']),
+    ('\u{53E}', &['\u{56E}']),
+    ('\u{53F}', &['\u{56F}']),
+    ('\u{540}', &['\u{570}']),
+    ('\u{541}', &['\u{571}']),
+    ('\u{542}', &['\u{572}']),
+    ('\u{543}', &['\u{573}']),
+    ('\u{544}', &['\u{574}']),
+    ('\u{545}', &['\u{575}']),
+    ('\u{546}', &['\u{576}']),
+    ('\u{547}', &['\u{577}']),
+    ('\u{548}', &['\u{578}']),
+    ('\u{549}', &['\u{579}']),
+    ('\u{54A}', &['\u{57A}']),
+    ('\u{54B}', &['\u{57B}']),
+    ('\u{54C}', &['\u{57C}']),
+    ('\u{54D}', &['\u{57D}']),
+    ('\u{54E}', &['\u{57E}']),
+    ('\u{54F}', &['\u{57F}']),
+    ('\u{550}', &['\u{580}']),
+    ('\u{551}', &['\u{581}']),
+    ('\u{552}', &['\u{582}']),
+    ('\u{553}', &['\u{583}']),
+    ('\u{554}', &['\u{584}']),
+    ('\u{555}', &['\u{585}']),
+    ('\u{556}', &['\u{586}']),
+    ('\u{561}', &['\u{531}']),
+    ('\u{562}', &['\u{532}']),
+    ('\u{563}', &['\u{533}']),
+    ('\u{564}', &['\u{534}']),
+    ('\u{565}', &['\u{535}']),
+    ('\u{566}', &['\u{536}']),
+    ('\u{567}', &['\u{537}']),
+    ('\u{568}', &['\u{538}']),
+    ('\u{569}', &['\u{539}']),
+    ('\u{56A}', &['\u{53A}']),
+    ('\u{56B}', &['\u{53B}']),
+    ('\u{56C}', &['\u{53C}']),
+    ('\u{56D}', &['\u{53D}']),
+    ('\u{56E}', &['\u{53E}']),
+    ('\u{56F}', &['\u{53F}']),
+    ('\u{570}', &['\u{540}']),
+    ('\u{571}', &['\u{541}']),
+    ('\u{572}', &['\u{542}']),
+    ('\u{573}', &['\u{543}']),
+    ('\u{574}', &['\u{544}']),
+    ('\u{575}', &['\u{545}']),
+    ('\u{576}', &['\u{546}']),
+    ('\u{577}', &['\u{547}']),
+    ('\u{578}', &['\u{548}']),
+    ('\u{579}', &['\u{549}']),
+    ('\u{57A}', &['\u{54A}']),
+    ('\u{57B}', &['\u{54B}']),
+    ('\u{57C}', &['\u{54C}']),
+    ('\u{57D}', &['\u{54D}']),
+    ('\u{57E}', &['\u{54E}']),
+    ('\u{57F}', &['\u{54F}']),
+    ('\u{580}', &['\u{550}']),
+    ('\u{581}', &['\u{551}']),
+    ('\u{582}', &['\u{552}']),
+    ('\u{583}', &['\u{553}']),
+    ('\u{584}', &['\u{554}']),
+    ('\u{585}', &['\u{555}']),
+    ('\u{586}', &['\u{556}']),
+    ('\u{10A0}', &['\u{2D00}']),
+    ('\u{10A1}', &['\u{2D01}']),
+    ('\u{10A2}', &['\u{2D02}']),
+    ('\u{10A3}', &['\u{2D03}']),
+    ('\u{10A4}', &['\u{2D04}']),
+    ('\u{10A5}', &['\u{2D05}']),
+    ('\u{10A6}', &['\u{2D06}']),
+    ('\u{10A7}', &['\u{2D07}']),
+    ('\u{10A8}', &['\u{2D08}']),
+    ('\u{10A9}', &['\u{2D09}']),
+    ('\u{10AA}', &['\u{2D0A}']),
+    ('\u{10AB}', &['\u{2D0B}']),
+    ('\u{10AC}', &['\u{2D0C}']),
+    ('\u{10AD}', &['\u{2D0D}']),
+    ('\u{10AE}', &['\u{2D0E}']),
+    ('\u{10AF}', &['\u{2D0F}']),
+    ('\u{10B0}', &['\u{2D10}']),
+    ('\u{10B1}', &['\u{2D11}']),
+    ('\u{10B2}', &['\u{2D12}']),
+    ('\u{10B3}', &['\u{2D13}']),
+    ('\u{10B4}', &['\u{2D14}']),
+    ('\u{10B5}', &['\u{2D15}']),
+    ('\u{10B6}', &['\u{2D16}']),
+    ('\u{10B7}', &['\u{2D17}']),
+    ('\u{10B8}', &['\u{2D18}']),
+    ('\u{10B9}', &['\u{2D19}']),
+    ('\u{10BA}', &['\u{2D1A}']),
+    ('\u{10BB}', &['\u{2D1B}']),
+    ('\u{10BC}', &['\u{2D1C}']),
+    ('\u{10BD}', &['\u{2D1D}']),
+    ('\u{10BE}', &['\u{2D1E}']),
+    ('\u{10BF}', &['\u{2D1F}']),
+    ('\u{10C0}', &['\u{2D20}']),
+    ('\u{10C1}', &['\u{2D21}']),
+    ('\u{10C2}', &['\u{2D22}']),
+    ('\u{10C3}', &['\u{2D23}']),
+    ('\u{10C4}', &['\u{2D24}']),
+    ('\u{10C5}', &['\u{2D25}']),
+    ('\u{10C7}', &['\u{2D27}']),
+    ('\u{10CD}', &['\u{2D2D}']),
+    ('\u{10D0}', &['\u{1C90}']),
+    ('\u{10D1}', &['\u{1C91}']),
+    ('\u{10D2}', &['\u{1C92}']),
+    ('\u{10D3}', &['\u{1C93}']),
+    ('\u{10D4}', &['\u{1C94}']),
+    ('\u{10D5}', &['\u{1C95}']),
+    ('\u{10D6}', &['\u{1C96}']),
+    ('\u{10D7}', &['\u{1C97}']),
+    ('\u{10D8}', &['\u{1C98}']),
+    ('\u{10D9}', &['\u{1C99}']),
+    ('\u{10DA}', &['\u{1C9A}']),
+    ('\u{10DB}', &['\u{1C9B}']),
+    ('\u{10DC}', &['\u{1C9C}']),
+    ('\u{10DD}', &['\u{1C9D}']),
+    ('\u{10DE}', &['\u{1C9E}']),
+    ('\u{10DF}', &['\u{1C9F}']),
+    ('\u{10E0}', &['\u{1CA0}']),
+    ('\u{10E1}', &['\u{1CA1}']),
+    ('\u{10E2}', &['\u{1CA2}']),
+    ('\u{10E3}', &['\u{1CA3}']),
+    ('\u{10E4}', &['\u{1CA4}']),
+    ('\u{10E5}', &['\u{1CA5}']),
+    ('\u{10E6}', &['\u{1CA6}']),
+    ('\u{10E7}', &['\u{1CA7}']),
+    ('\u{10E8}', &['\u{1CA8}']),
+    ('\u{10E9}', &['\u{1CA9}']),
+    ('\u{10EA}', &['\u{1CAA}']),
+    ('\u{10EB}', &['\u{1CAB}']),
+    ('\u{10EC}', &['\u{1CAC}']),
+    ('\u{10ED}', &['\u{1CAD}']),
+    ('\u{10EE}', &['\u{1CAE}']),
+    ('\u{10EF}', &['\u{1CAF}']),
+    ('\u{10F0}', &['\u{1CB0}']),
+    ('\u{10F1}', &['\u{1CB1}']),
+    ('\u{10F2}', &['\u{1CB2}']),
+    ('\u{10F3}', &['\u{1CB3}']),
+    ('\u{10F4}', &['\u{1CB4}']),
+    ('\u{10F5}', &['\u{1CB5}']),
+    ('\u{10F6}', &['\u{1CB6}']),
+    ('\u{10F7}', &['\u{1CB7}']),
+    ('\u{10F8}', &['\u{1CB8}']),
+    ('\u{10F9}', &['\u{1CB9}']),
+    ('\u{10FA}', &['\u{1CBA}']),
+    ('\u{10FD}', &['\u{1CBD}']),
+    ('\u{10FE}', &['\u{1CBE}']),
+    ('\u{10FF}', &['\u{1CBF}']),
+    ('\u{13A0}', &['\u{AB70}']),
+    ('\u{13A1}', &['\u{AB71}']),
+    ('\u{13A2}', &['\u{AB72}']),
+    ('\u{13A3}', &['\u{AB73}']),
+    ('\u{13A4}', &['\u{AB74}']),
+    ('\u{13A5}', &['\u{AB75}']),
+    ('\u{13A6}', &['\u{AB76}']),
+    ('\u{13A7}', &['\u{AB77}']),
+    ('\u{13A8}', &['\u{AB78}']),
+    ('\u{13A9}', &['\u{AB79}']),
+    ('\u{13AA}', &['\u{AB7A}']),
+    ('\u{13AB}', &['\u{AB7B}']),
+    ('\u{13AC}', &['\u{AB7C}']),
+    ('\u{13AD}', &['\u{AB7D}']),
+    ('\u{13AE}', &['\u{AB7E}']),
+    ('\u{13AF}', &['\u{AB7F}']),
+    ('\u{13B0}', &['\u{AB80}']),
+    ('\u{13B1}', &['\u{AB81}']),
+    ('\u{13B2}', &['\u{AB82}']),
+    ('\u{13B3}', &['\u{AB83}']),
+    ('\u{13B4}', &['\u{AB84}']),
+    ('\u{13B5}', &['\u{AB85}']),
+    ('\u{13B6}', &['\u{AB86}']),
+    ('\u{13B7}', &['\u{AB87}']),
+    ('\u{13B8}', &['\u{AB88}']),
+    ('\u{13B9}', &['\u{AB89}']),
+    ('\u{13BA}', &['\u{AB8A}']),
+    ('\u{13BB}', &['\u{AB8B}']),
+    ('\u{13BC}', &['\u{AB8C}']),
+    ('\u{13BD}', &['\u{AB8D}']),
+    ('\u{13BE}', &['\u{AB8E}']),
+    ('\u{13BF}', &['\u{AB8F}']),
+    ('\u{13C0}', &['\u{AB90}']),
+    ('\u{13C1}', &['\u{AB91}']),
+    ('\u{13C2}', &['\u{AB92}']),
+    ('\u{13C3}', &['\u{AB93}']),
+    ('\u{13C4}', &['\u{AB94}']),
+    ('\u{13C5}', &['\u{AB95}']),
+    ('\u{13C6}', &['\u{AB96}']),
+    ('\u{13C7}', &['\u{AB97}']),
+    ('\u{13C8}', &['\u{AB98}']),
+    ('\u{13C9}', &['\u{AB99}']),
+    ('\u{13CA}', &['\u{AB9A}']),
+    ('\u{13CB}', &['\u{AB9B}']),
+    ('\u{13CC}', &['\u{AB9C}']),
+    ('\u{13CD}', &['\u{AB9D}']),
+    ('\u{13CE}', &['\u{AB9E}']),
+    ('\u{13CF}', &['\u{AB9F}']),
+    ('\u{13D0}', &['\u{ABA0}']),
+    ('\u{13D1}', &['\u{ABA1}']),
+    ('\u{13D2}', &['\u{ABA2}']),
+    ('\u{13D3}', &['\u{ABA3}']),
+    ('\u{13D4}', &['\u{ABA4}']),
+    ('\u{13D5}', &['\u{ABA5}']),
+    ('\u{13D6}', &['\u{ABA6}']),
+    ('\u{13D7}', &['\u{ABA7}']),
+    ('\u{13D8}', &['\u{ABA8}']),
+    ('\u{13D9}', &['\u{ABA9}']),
+    ('\u{13DA}', &['\u{ABAA}']),
+    ('\u{13DB}', &['\u{ABAB}']),
+    ('\u{13DC}', &['\u{ABAC}']),
+    ('\u{13DD}', &['\u{ABAD}']),
+    ('\u{13DE}', &['\u{ABAE}']),
+    ('\u{13DF}', &['\u{ABAF}']),
+    ('\u{13E0}', &['\u{ABB0}']),
+    ('\u{13E1}', &['\u{ABB1}']),
+    ('\u{13E2}', &['\u{ABB2}']),
+    ('\u{13E3}', &['\u{ABB3}']),
+    ('\u{13E4}', &['\u{ABB4}']),
+    ('\u{13E5}', &['\u{ABB5}']),
+    ('\u{13E6}', &['\u{ABB6}']),
+    ('\u{13E7}', &['\u{ABB7}']),
+    ('\u{13E8}', &['\u{ABB8}']),
+    ('\u{13E9}', &['\u{ABB9}']),
+    ('\u{13EA}', &['\u{ABBA}']),
+    ('\u{13EB}', &['\u{ABBB}']),
+    ('\u{13EC}', &['\u{ABBC}']),
+    ('\u{13ED}', &['\u{ABBD}']),
+    ('\u{13EE}', &['\u{ABBE}']),
+    ('\u{13EF}', &['\u{ABBF}']),
+    ('\u{13F0}', &['\u{13F8}']),
+    ('\u{13F1}', &['\u{13F9}']),
+    ('\u{13F2}', &['\u{13FA}']),
+    ('\u{13F3}', &['\u{13FB}']),
+    ('\u{13F4}', &['\u{13FC}']),
+    ('\u{13F5}', &['\u{13FD}']),
+    ('\u{13F8}', &['\u{13F0}']),
+    ('\u{13F9}', &['\u{13F1}']),
+    ('\u{13FA}', &['\u{13F2}']),
+    ('\u{13FB}', &['\u{13F3}']),
+    ('\u{13FC}', &['\u{13F4}']),
+    ('\u{13FD}', &['\u{13F5}']),
+    ('\u{1C80}', &['\u{412}', '\u{432}']),
+    ('\u{1C81}', &['\u{414}', '\u{434}']),
+    ('\u{1C82}', &['\u{41E}', '\u{43E}']),
+    ('\u{1C83}', &['\u{421}', '\u{441}']),
+    ('\u{1C84}', &['\u{422}', '\u{442}', '\u{1C85}']),
+    ('\u{1C85}', &['\u{422}', '\u{442}', '\u{1C84}']),
+    ('\u{1C86}', &['\u{42A}', '\u{44A}']),
+    ('\u{1C87}', &['\u{462}', '\u{463}']),
+    ('\u{1C88}', &['\u{A64A}', '\u{A64B}']),
+    ('\u{1C89}', &['\u{1C8A}']),
+    ('\u{1C8A}', &['\u{1C89}']),
+    ('\u{1C90}', &['\u{10D0}']),
+    ('\u{1C91}', &['\u{10D1}']),
+    ('\u{1C92}', &['\u{10D2}']),
+    ('\u{1C93}', &['\u{10D3}']),
+    ('\u{1C94}', &['\u{10D4}']),
+    ('\u{1C95}', &['\u{10D5}']),
+    ('\u{1C96}', &['\u{10D6}']),
+    ('\u{1C97}', &['\u{10D7}']),
+    ('\u{1C98}', &['\u{10D8}']),
+    ('\u{1C99}', &['\u{10D9}']),
+    ('\u{1C9A}', &['\u{10DA}']),
+    ('\u{1C9B}', &['\u{10DB}']),
+    ('\u{1C9C}', &['\u{10DC}']),
+    ('\u{1C9D}', &['\u{10DD}']),
+    ('\u{1C9E}', &['\u{10DE}']),
+    ('\u{1C9F}', &['\u{10DF}']),
+    ('\u{1CA0}', &['\u{10E0}']),
+    ('\u{1CA1}', &['\u{10E1}']),
+    ('\u{1CA2}', &['\u{10E2}']),
+    ('\u{1CA3}', &['\u{10E3}']),
+    ('\u{1CA4}', &['\u{10E4}']),
+    ('\u{1CA5}', &['\u{10E5}']),
+    ('\u{1CA6}', &['\u{10E6}']),
+    ('\u{1CA7}', &['\u{10E7}']),
+    ('\u{1CA8}', &['\u{10E8}']),
+    ('\u{1CA9}', &['\u{10E9}']),
+    ('\u{1CAA}', &['\u{10EA}']),
+    ('\u{1CAB}', &['\u{10EB}']),
+    ('\u{1CAC}', &['\u{10EC}']),
+    ('\u{1CAD}', &['\u{10ED}']),
+    ('\u{1CAE}', &['\u{10EE}']),
+    ('\u{1CAF}', &['\u{10EF}']),
+    ('\u{1CB0}', &['\u{10F0}']),
+    ('\u{1CB1}', &['\u{10F1}']),
+    ('\u{1CB2}', &['\u{10F2}']),
+    ('\u{1CB3}', &['\u{10F3}']),
+    ('\u{1CB4}', &['\u{10F4}']),
+    ('\u{1CB5}', &['\u{10F5}']),
+    ('\u{1CB6}', &['\u{10F6}']),
+    ('\u{1CB7}', &['\u{10F7}']),
+    ('\u{1CB8}', &['\u{10F8}']),
+    ('\u{1CB9}', &['\u{10F9}']),
+    ('\u{1CBA}', &['\u{10FA}']),
+    ('\u{1CBD}', &['\u{10FD}']),
+    ('\u{1CBE}', &['\u{10FE}']),
+    ('\u{1CBF}', &['\u{10FF}']),
+    ('\u{1D79}', &['\u{A77D}']),
+    ('\u{1D7D}', &['\u{2C63}']),
+    ('\u{1D8E}', &['\u{A7C6}']),
+    ('\u{1E00}', &['\u{1E01}']),
+    ('\u{1E01}', &['\u{1E00}']),
+    ('\u{1E02}', &['\u{1E03}']),
+    ('\u{1E03}', &['\u{1E02}']),
+    ('\u{1E04}', &['\u{1E05}']),
+    ('\u{1E05}', &['\u{1E04}']),
+    ('\u{1E06}', &['\u{1E07}']),
+    ('\u{1E07}', &['\u{1E06}']),
+    ('\u{1E08}', &['\u{1E09}']),
+    ('\u{1E09}', &['\u{1E08}']),
+    ('\u{1E0A}', &['\u{1E0B}']),
+    ('\u{1E0B}', &['\u{1E0A}']),
+    ('\u{1E0C}', &['\u{1E0D}']),
+    ('\u{1E0D}', &['\u{1E0C}']),
+    ('\u{1E0E}', &['\u{1E0F}']),
+    ('\u{1E0F}', &['\u{1E0E}']),
+    ('\u{1E10}', &['\u{1E11}']),
+    ('\u{1E11}', &['\u{1E10}']),
+    ('\u{1E12}', &['\u{1E13}']),
+    ('\u{1E13}', &['\u{1E12}']),
+    ('\u{1E14}', &['\u{1E15}']),
+    ('\u{1E15}', &['\u{1E14}']),
+    ('\u{1E16}', &['\u{1E17}']),
+    ('\u{1E17}', &['\u{1E16}']),
+    ('\u{1E18}', &['\u{1E19}']),
+    ('\u{1E19}', &['\u{1E18}']),
+    ('\u{1E1A}', &['\u{1E1B}']),
+    ('\u{1E1B}', &['\u{1E1A}']),
+    ('\u{1E1C}', &['\u{1E1D}']),
+    ('\u{1E1D}', &['\u{1E1C}']),
+    ('\u{1E1E}', &['\u{1E1F}']),
+    ('\u{1E1F}', &['\u{1E1E}']),
+    ('\u{1E20}', &['\u{1E21}']),
+    ('\u{1E21}', &['\u{1E20}']),
+    ('\u{1E22}', &['\u{1E23}']),
+    ('\u{1E23}', &['\u{1E22}']),
+    ('\u{1E24}', &['\u{1E25}']),
+    ('\u{1E25}', &['\u{1E24}']),
+    ('\u{1E26}', &['\u{1E27}']),
+    ('\u{1E27}', &['\u{1E26}']),
+    ('\u{1E28}', &['\u{1E29}']),
+    ('\u{1E29}', &['\u{1E28}']),
+    ('\u{1E2A}', &['\u{1E2B}']),
+    ('\u{1E2B}', &['\u{1E2A}']),
+    ('\u{1E2C}', &['\u{1E2D}']),
+    ('\u{1E2D}', &['\u{1E2C}']),
+    ('\u{1E2E}', &['\u{1E2F}']),
+    ('\u{1E2F}', &['\u{1E2E}']),
+    ('\u{1E30}', &['\u{1E31}']),
+    ('\u{1E31}', &['\u{1E30}']),
+    ('\u{1E32}', &['\u{1E33}']),
+    ('\u{1E33}', &['\u{1E32}']),
+    ('\u{1E34}', &['\u{1E35}']),
+    ('\u{1E35}', &['\u{1E34}']),
+    ('\u{1E36}', &['\u{1E37}']),
+    ('\u{1E37}', &['\u{1E36}']),
+    ('\u{1E38}', &['\u{1E39}']),
+    ('\u{1E39}', &['\u{1E38}']),
+    ('\u{1E3A}', &['\u{1E3B}']),
+    ('\u{1E3B}', &['\u{1E3A}']),
+    ('\u{1E3C}', &['\u{1E3D}']),
+    ('\u{1E3D}', &['\u{1E3C}']),
+    ('\u{1E3E}', &['\u{1E3F}']),
+    ('\u{1E3F}', &['\u{1E3E}']),
+    ('\u{1E40}', &['\u{1E41}']),
+    ('\u{1E41}', &['\u{1E40}']),
+    ('\u{1E42}', &['\u{1E43}']),
+    ('\u{1E43}', &['\u{1E42}']),
+    ('\u{1E44}', &['\u{1E45}']),
+    ('\u{1E45}', &['\u{1E44}']),
+    ('\u{1E46}', &['\u{1E47}']),
+    ('\u{1E47}', &['\u{1E46}']),
+    ('\u{1E48}', &['\u{1E49}']),
+    ('\u{1E49}', &['\u{1E48}']),
+    ('\u{1E4A}', &['\u{1E4B}']),
+    ('\u{1E4B}', &['\u{1E4A}']),
+    ('\u{1E4C}', &['\u{1E4D}']),
+    ('\u{1E4D}', &['\u{1E4C}']),
+    ('\u{1E4E}', &['\u{1E4F}']),
+    ('\u{1E4F}', &['\u{1E4E}']),
+    ('\u{1E50}', &['\u{1E51}']),
+    ('\u{1E51}', &['\u{1E50}']),
+    ('\u{1E52}', &['\u{1E53}']),
+    ('\u{1E53}', &['\u{1E52}']),
+    ('\u{1E54}', &['\u{1E55}']),
+    ('\u{1E55}', &['\u{1E54}']),
+    ('\u{1E56}', &['\u{1E57}']),
+    ('\u{1E57}', &['\u{1E56}']),
+    ('\u{1E58}', &['\u{1E59}']),
+    ('\u{1E59}', &['\u{1E58}']),
+    ('\u{1E5A}', &['\u{1E5B}']),
+    ('\u{1E5B}', &['\u{1E5A}']),
+    ('\u{1E5C}', &['\u{1E5D}']),
+    ('\u{1E5D}', &['\u{1E5C}']),
+    ('\u{1E5E}', &['\u{1E5F}']),
+    ('\u{1E5F}', &['\u{1E5E}']),
+    ('\u{1E60}', &['\u{1E61}', '\u{1E9B}']),
+    ('\u{1E61}', &['\u{1E60}', '\u{1E9B}']),
+    ('\u{1E62}', &['\u{1E63}']),
+    ('\u{1E63}', &['\u{1E62}']),
+    ('\u{1E64}', &['\u{1E65}']),
+    ('\u{1E65}', &['\u{1E64}']),
+    ('\u{1E66}', &['\u{1E67}']),
+    ('\u{1E67}', &['\u{1E66}']),
+    ('\u{1E68}', &['\u{1E69}']),
+    ('\u{1E69}', &['\u{1E68}']),
+    ('\u{1E6A}', &['\u{1E6B}']),
+    ('\u{1E6B}', &['\u{1E6A}']),
+    ('\u{1E6C}', &['\u{1E6D}']),
+    ('\u{1E6D}', &['\u{1E6C}']),
+    ('\u{1E6E}', &['\u{1E6F}']),
+    ('\u{1E6F}', &['\u{1E6E}']),
+    ('\u{1E70}', &['\u{1E71}']),
+    ('\u{1E71}', &['\u{1E70}']),
+    ('\u{1E72}', &['\u{1E73}']),
+    ('\u{1E73}', &['\u{1E72}']),
+    ('\u{1E74}', &['\u{1E75}']),
+    ('\u{1E75}', &['\u{1E74}']),
+    ('\u{1E76}', &['\u{1E77}']),
+    ('\u{1E77}', &['\u{1E76}']),
+    ('\u{1E78}', &['\u{1E79}']),
+    ('\u{1E79}', &['\u{1E78}']),
+    ('\u{1E7A}', &['\u{1E7B}']),
+    ('\u{1E7B}', &['\u{1E7A}']),
+    ('\u{1E7C}', &['\u{1E7D}']),
+    ('\u{1E7D}', &['\u{1E7C}']),
+    ('\u{1E7E}', &['\u{1E7F}']),
+    ('\u{1E7F}', &['\u{1E7E}']),
+    ('\u{1E80}', &['\u{1E81}']),
+    ('\u{1E81}', &['\u{1E80}']),
+    ('\u{1E82}', &['\u{1E83}']),
+    ('\u{1E83}', &['\u{1E82}']),
+    ('\u{1E84}', &['\u{1E85}']),
+    ('\u{1E85}', &['\u{1E84}']),
+    ('\u{1E86}', &['\u{1E87}']),
+    ('\u{1E87}', &['\u{1E86}']),
+    ('\u{1E88}', &['\u{1E89}']),
+    ('\u{1E89}', &['\u{1E88}']),
+    ('\u{1E8A}', &['\u{1E8B}']),
+    ('\u{1E8B}', &['\u{1E8A}']),
+    ('\u{1E8C}', &['\u{1E8D}']),
+    ('\u{1E8D}', &['\u{1E8C}']),
+    ('\u{1E8E}', &['\u{1E8F}']),
+    ('\u{1E8F}', &['\u{1E8E}']),
+    ('\u{1E90}', &['\u{1E91}']),
+    ('\u{1E91}', &['\u{1E90}']),
+    ('\u{1E92}', &['\u{1E93}']),
+    ('\u{1E93}', &['\u{1E92}']),
+    ('\u{1E94}', &['\u{1E95}']),
+    ('\u{1E95}', &['\u{1E94}']),
+    ('\u{1E9B}', &['\u{1E60}', '\u{1E61}']),
+    ('\u{1E9E}', &['\u{DF}']),
+    ('\u{1EA0}', &['\u{1EA1}']),
+    ('\u{1EA1}', &['\u{1EA0}']),
+    ('\u{1EA2}', &['\u{1EA3}']),
+    ('\u{1EA3}', &['\u{1EA2}']),
+    ('\u{1EA4}', &['\u{1EA5}']),
+    ('\u{1EA5}', &['\u{1EA4}']),
+    ('\u{1EA6}', &['\u{1EA7}']),
+    ('\u{1EA7}', &['\u{1EA6}']),
+    ('\u{1EA8}', &['\u{1EA9}']),
+    ('\u{1EA9}', &['\u{1EA8}']),
+    ('\u{1EAA}', &['\u{1EAB}']),
+    ('\u{1EAB}', &['\u{1EAA}']),
+    ('\u{1EAC}', &['\u{1EAD}']),
+    ('\u{1EAD}', &['\u{1EAC}']),
+    ('\u{1EAE}', &['\u{1EAF}']),
+    ('\u{1EAF}', &['\u{1EAE}']),
+    ('\u{1EB0}', &['\u{1EB1}']),
+    ('\u{1EB1}', &['\u{1EB0}']),
+    ('\u{1EB2}', &['\u{1EB3}']),
+    ('\u{1EB3}', &['\u{1EB2}']),
+    ('\u{1EB4}', &['\u{1EB5}']),
+    ('\u{1EB5}', &['\u{1EB4}']),
+    ('\u{1EB6}', &['\u{1EB7}']),
+    ('\u{1EB7}', &['\u{1EB6}']),
+    ('\u{1EB8}', &['\u{1EB9}']),
+    ('\u{1EB9}', &['\u{1EB8}']),
+    ('\u{1EBA}', &['\u{1EBB}']),
+    ('\u{1EBB}', &['\u{1EBA}']),
+    ('\u{1EBC}', &['\u{1EBD}']),
+    ('\u{1EBD}', &['\u{1EBC}']),
+    ('\u{1EBE}', &['\u{1EBF}']),
+    ('\u{1EBF}', &['\u{1EBE}']),
+    ('\u{1EC0}', &['\u{1EC1}']),
+    ('\u{1EC1}', &['\u{1EC0}']),
+    ('\u{1EC2}', &['\u{1EC3}']),
+    ('\u{1EC3}', &['\u{1EC2}']),
+    ('\u{1EC4}', &['\u{1EC5}']),
+    ('\u{1EC5}', &['\u{1EC4}']),
+    ('\u{1EC6}', &['\u{1EC7}']),
+    ('\u{1EC7}', &['\u{1EC6}']),
+    ('\u{1EC8}', &['\u{1EC9}']),
+    ('\u{1EC9}', &['\u{1EC8}']),
+    ('\u{1ECA}', &['\u{1ECB}']),
+    ('\u{1ECB}', &['\u{1ECA}']),
+    ('\u{1ECC}', &['\u{1ECD}']),
+    ('\u{1ECD}', &['\u{1ECC}']),
+    ('\u{1ECE}', &['\u{1ECF}']),
+    ('\u{1ECF}', &['\u{1ECE}']),
+    ('\u{1ED0}', &['\u{1ED1}']),
+    ('\u{1ED1}', &['\u{1ED0}']),
+    ('\u{1ED2}', &['\u{1ED3}']),
+    ('\u{1ED3}', &['\u{1ED2}']),
+    ('\u{1ED4}', &['\u{1ED5}']),
+    ('\u{1ED5}', &['\u{1ED4}']),
+    ('\u{1ED6}', &['\u{1ED7}']),
+    ('\u{1ED7}', &['\u{1ED6}']),
+    ('\u{1ED8}', &['\u{1ED9}']),
+    ('\u{1ED9}', &['\u{1ED8}']),
+    ('\u{1EDA}', &['\u{1EDB}']),
+    ('\u{1EDB}', &['\u{1EDA}']),
+    ('\u{1EDC}', &['\u{1EDD}']),
+    ('\u{1EDD}', &['\u{1EDC}']),
+    ('\u{1EDE}', &['\u{1EDF}']),
+    ('\u{1EDF}', &['\u{1EDE}']),
+    ('\u{1EE0}', &['\u{1EE1}']),
+    ('\u{1EE1}', &['\u{1EE0}']),
+    ('\u{1EE2}', &['\u{1EE3}']),
+    ('\u{1EE3}', &['\u{1EE2}']),
+    ('\u{1EE4}', &['\u{1EE5}']),
+    ('\u{1EE5}', &['\u{1EE4}']),
+    ('\u{1EE6}', &['\u{1EE7}']),
+    ('\u{1EE7}', &['\u{1EE6}']),
+    ('\u{1EE8}', &['\u{1EE9}']),
+    ('\u{1EE9}', &['\u{1EE8}']),
+    ('\u{1EEA}', &['\u{1EEB}']),
+    ('\u{1EEB}', &['\u{1EEA}']),
+    ('\u{1EEC}', &['\u{1EED}']),
+    ('\u{1EED}', &['\u{1EEC}']),
+    ('\u{1EEE}', &['\u{1EEF}']),
+    ('\u{1EEF}', &['\u{1EEE}']),
+    ('\u{1EF0}', &['\u{1EF1}']),
+    ('\u{1EF1}', &['\u{1EF0}']),
+    ('\u{1EF2}', &['\u{1EF3}']),
+    ('\u{1EF3}', &['\u{1EF2}']),
+    ('\u{1EF4}', &['\u{1EF5}']),
+    ('\u{1EF5}', &['\u{1EF4}']),
+    ('\u{1EF6}', &['\u{1EF7}']),
+    ('\u{1EF7}', &['\u{1EF6}']),
+    ('\u{1EF8}', &['\u{1EF9}']),
+    ('\u{1EF9}', &['\u{1EF8}']),
+    ('\u{1EFA}', &['\u{1EFB}']),
+    ('\u{1EFB}', &['\u{1EFA}']),
+    ('\u{1EFC}', &['\u{1EFD}']),
+    ('\u{1EFD}', &['\u{1EFC}']),
+    ('\u{1EFE}', &['\u{1EFF}']),
+    ('\u{1EFF}', &['\u{1EFE}']),
+    ('\u{1F00}', &['\u{1F08}']),
+    ('\u{1F01}', &['\u{1F09}']),
+    ('\u{1F02}', &['\u{1F0A}']),
+    ('\u{1F03}', &['\u{1F0B}']),
+    ('\u{1F04}', &['\u{1F0C}']),
+    ('\u{1F05}', &['\u{1F0D}']),
+    ('\u{1F06}', &['\u{1F0E}']),
+    ('\u{1F07}', &['\u{1F0F}']),
+    ('\u{1F08}', &['\u{1F00}']),
+    ('\u{1F09}', &['\u{1F01}']),
+    ('\u{1F0A}', &['\u{1F02}']),
+    ('\u{1F0B}', &['\u{1F03}']),
+    ('\u{1F0C}', &['\u{1F04}']),
+    ('\u{1F0D}', &['\u{1F05}']),
+    ('\u{1F0E}', &['\u{1F06}']),
+    ('\u{1F0F}', &['\u{1F07}']),
+    ('\u{1F10}', &['\u{1F18}']),
+    ('\u{1F11}', &['\u{1F19}']),
+    ('\u{1F12}', &['\u{1F1A}']),
+    ('\u{1F13}', &['\u{1F1B}']),
+    ('\u{1F14}', &['\u{1F1C}']),
+    ('\u{1F15}', &['\u{1F1D}']),
+    ('\u{1F18}', &['\u{1F10}']),
+    ('\u{1F19}', &['\u{1F11}']),
+    ('\u{1F1A}', &['\u{1F12}']),
+    ('\u{1F1B}', &['\u{1F13}']),
+    ('\u{1F1C}', &['\u{1F14}']),
+    ('\u{1F1D}', &['\u{1F15}']),
+    ('\u{1F20}', &['\u{1F28}']),
+    ('\u{1F21}', &['\u{1F29}']),
+    ('\u{1F22}', &['\u{1F2A}']),
+    ('\u{1F23}', &['\u{1F2B}']),
+    ('\u{1F24}', &['\u{1F2C}']),
+    ('\u{1F25}', &['\u{1F2D}']),
+    ('\u{1F26}', &['\u{1F2E}']),
+    ('\u{1F27}', &['\u{1F2F}']),
+    ('\u{1F28}', &['\u{1F20}']),
+    ('\u{1F29}', &['\u{1F21}']),
+    ('\u{1F2A}', &['\u{1F22}']),
+    ('\u{1F2B}', &['\u{1F23}']),
+    ('\u{1F2C}', &['\u{1F24}']),
+    ('\u{1F2D}', &['\u{1F25}']),
+    ('\u{1F2E}', &['\u{1F26}']),
+    ('\u{1F2F}', &['\u{1F27}']),
+    ('\u{1F30}', &['\u{1F38}']),
+    ('\u{1F31}', &['\u{1F39}']),
+    ('\u{1F32}', &['\u{1F3A}']),
+    ('\u{1F33}', &['\u{1F3B}']),
+    ('\u{1F34}', &['\u{1F3C}']),
+    ('\u{1F35}', &['\u{1F3D}']),
+    ('\u{1F36}', &['\u{1F3E}']),
+    ('\u{1F37}', &['\u{1F3F}']),
+    ('\u{1F38}', &['\u{1F30}']),
+    ('\u{1F39}', &['\u{1F31}']),
+    ('\u{1F3A}', &['\u{1F32}']),
+    ('\u{1F3B}', &['\u{1F33}']),
+    ('\u{1F3C}', &['\u{1F34}']),
+    ('\u{1F3D}', &['\u{1F35}']),
+    ('\u{1F3E}', &['\u{1F36}']),
+    ('\u{1F3F}', &['\u{1F37}']),
+    ('\u{1F40}', &['\u{1F48}']),
+    ('\u{1F41}', &['\u{1F49}']),
+    ('\u{1F42}', &['\u{1F4A}']),
+    ('\u{1F43}', &['\u{1F4B}']),
+    ('\u{1F44}', &['\u{1F4C}']),
+    ('\u{1F45}', &['\u{1F4D}']),
+    ('\u{1F48}', &['\u{1F40}']),
+    ('\u{1F49}', &['\u{1F41}']),
+    ('\u{1F4A}', &['\u{1F42}']),
+    ('\u{1F4B}', &['\u{1F43}']),
+    ('\u{1F4C}', &['\u{1F44}']),
+    ('\u{1F4D}', &['\u{1F45}']),
+    ('\u{1F51}', &['\u{1F59}']),
+    ('\u{1F53}', &['\u{1F5B}']),
+    ('\u{1F55}', &['\u{1F5D}']),
+    ('\u{1F57}', &['\u{1F5F}']),
+    ('\u{1F59}', &['\u{1F51}']),
+    ('\u{1F5B}', &['\u{1F53}']),
+    ('\u{1F5D}', &['\u{1F55}']),
+    ('\u{1F5F}', &['\u{1F57}']),
+    ('\u{1F60}', &['\u{1F68}']),
+    ('\u{1F61}', &['\u{1F69}']),
+    ('\u{1F62}', &['\u{1F6A}']),
+    ('\u{1F63}', &['\u{1F6B}']),
+    ('\u{1F64}', &['\u{1F6C}']),
+    ('\u{1F65}', &['\u{1F6D}']),
+    ('\u{1F66}', &['\u{1F6E}']),
+    ('\u{1F67}', &['\u{1F6F}']),
+    ('\u{1F68}', &['\u{1F60}']),
+    ('\u{1F69}', &['\u{1F61}']),
+    ('\u{1F6A}', &['\u{1F62}']),
+    ('\u{1F6B}', &['\u{1F63}']),
+    ('\u{1F6C}', &['\u{1F64}']),
+    ('\u{1F6D}', &['\u{1F65}']),
+    ('\u{1F6E}', &['\u{1F66}']),
+    ('\u{1F6F}', &['\u{1F67}']),
+    ('\u{1F70}', &['\u{1FBA}']),
+    ('\u{1F71}', &['\u{1FBB}']),
+    ('\u{1F72}', &['\u{1FC8}']),
+    ('\u{1F73}', &['\u{1FC9}']),
+    ('\u{1F74}', &['\u{1FCA}']),
+    ('\u{1F75}', &['\u{1FCB}']),
+    ('\u{1F76}', &['\u{1FDA}']),
+    ('\u{1F77}', &['\u{1FDB}']),
+    ('\u{1F78}', &['\u{1FF8}']),
+    ('\u{1F79}', &['\u{1FF9}']),
+    ('\u{1F7A}', &['\u{1FEA}']),
+    ('\u{1F7B}', &['\u{1FEB}']),
+    ('\u{1F7C}', &['\u{1FFA}']),
+    ('\u{1F7D}', &['\u{1FFB}']),
+    ('\u{1F80}', &['\u{1F88}']),
+    ('\u{1F81}', &['\u{1F89}']),
+    ('\u{1F82}', &['\u{1F8A}']),
+    ('\u{1F83}', &['\u{1F8B}']),
+    ('\u{1F84}', &['\u{1F8C}']),
+    ('\u{1F85}', &['\u{1F8D}']),
+    ('\u{1F86}', &['\u{1F8E}']),
+    ('\u{1F87}', &['\u{1F8F}']),
+    ('\u{1F88}', &['\u{1F80}']),
+    ('\u{1F89}', &['\u{1F81}']),
+    ('\u{1F8A}', &['\u{1F82}']),
+    ('\u{1F8B}', &['\u{1F83}']),
+    ('\u{1F8C}', &['\u{1F84}']),
+    ('\u{1F8D}', &['\u{1F85}']),
+    ('\u{1F8E}', &['\u{1F86}']),
+    ('\u{1F8F}', &['\u{1F87}']),
+    ('\u{1F90}', &['\u{1F98}']),
+    ('\u{1F91}', &['\u{1F99}']),
+    ('\u{1F92}', &['\u{1F9A}']),
+    ('\u{1F93}', &['\u{1F9B}']),
+    ('\u{1F94}', &['\u{1F9C}']),
+    ('\u{1F95}', &['\u{1F9D}']),
+    ('\u{1F96}', &['\u{1F9E}']),
+    ('\u{1F97}', &['\u{1F9F}']),
+    ('\u{1F98}', &['\u{1F90}']),
+    ('\u{1F99}', &['\u{1F91}']),
+    ('\u{1F9A}', &['\u{1F92}']),
+    ('\u{1F9B}', &['\u{1F93}']),
+    ('\u{1F9C}', &['\u{1F94}']),
+    ('\u{1F9D}', &['\u{1F95}']),
+    ('\u{1F9E}', &['\u{1F96}']),
+    ('\u{1F9F}', &['\u{1F97}']),
+    ('\u{1FA0}', &['\u{1FA8}']),
+    ('\u{1FA1}', &['\u{1FA9}']),
+    ('\u{1FA2}', &['\u{1FAA}']),
+    ('\u{1FA3}', &['\u{1FAB}']),
+    ('\u{1FA4}', &['\u{1FAC}']),
+    ('\u{1FA5}', &['\u{1FAD}']),
+    ('\u{1FA6}', &['\u{1FAE}']),
+    ('\u{1FA7}', &['\u{1FAF}']),
+    ('\u{1FA8}', &['\u{1FA0}']),
+    ('\u{1FA9}', &['\u{1FA1}']),
+    ('\u{1FAA}', &['\u{1FA2}']),
+    ('\u{1FAB}', &['\u{1FA3}']),
+    ('\u{1FAC}', &['\u{1FA4}']),
+    ('\u{1FAD}', &['\u{1FA5}']),
+    ('\u{1FAE}', &['\u{1FA6}']),
+    ('\u{1FAF}', &['\u{1FA7}']),
+    ('\u{1FB0}', &['\u{1FB8}']),
+    ('\u{1FB1}', &['\u{1FB9}']),
+    ('\u{1FB3}', &['\u{1FBC}']),
+    ('\u{1FB8}', &['\u{1FB0}']),
+    ('\u{1FB9}', &['\u{1FB1}']),
+    ('\u{1FBA}', &['\u{1F70}']),
+    ('\u{1FBB}', &['\u{1F71}']),
+    ('\u{1FBC}', &['\u{1FB3}']),
+    ('\u{1FBE}', &['\u{345}', '\u{399}', '\u{3B9}']),
+    ('\u{1FC3}', &['\u{1FCC}']),
+    ('\u{1FC8}', &['\u{1F72}']),
+    ('\u{1FC9}', &['\u{1F73}']),
+    ('\u{1FCA}', &['\u{1F74}']),
+    ('\u{1FCB}', &['\u{1F75}']),
+    ('\u{1FCC}', &['\u{1FC3}']),
+    ('\u{1FD0}', &['\u{1FD8}']),
+    ('\u{1FD1}', &['\u{1FD9}']),
+    ('\u{1FD8}', &['\u{1FD0}']),
+    ('\u{1FD9}', &['\u{1FD1}']),
+    ('\u{1FDA}', &['\u{1F76}']),
+    ('\u{1FDB}', &['\u{1F77}']),
+    ('\u{1FE0}', &['\u{1FE8}']),
+    ('\u{1FE1}', &['\u{1FE9}']),
+    ('\u{1FE5}', &['\u{1FEC}']),
+    ('\u{1FE8}', &['\u{1FE0}']),
+    ('\u{1FE9}', &['\u{1FE1}']),
+    ('\u{1FEA}', &['\u{1F7A}']),
+    ('\u{1FEB}', &['\u{1F7B}']),
+    ('\u{1FEC}', &['\u{1FE5}']),
+    ('\u{1FF3}', &['\u{1FFC}']),
+    ('\u{1FF8}', &['\u{1F78}']),
+    ('\u{1FF9}', &['\u{1F79}']),
+    ('\u{1FFA}', &['\u{1F7C}']),
+    ('\u{1FFB}', &['\u{1F7D}']),
+    ('\u{1FFC}', &['\u{1FF3}']),
+    ('\u{2126}', &['\u{3A9}', '\u{3C9}']),
+    ('\u{212A}', &['\u{4B}', '\u{6B}']),
+    ('\u{212B}', &['\u{C5}', '\u{E5}']),
+    ('\u{2132}', &['\u{214E}']),
+    ('\u{214E}', &['\u{2132}']),
+    ('\u{2160}', &['\u{2170}']),
+    ('\u{2161}', &['\u{2171}']),
+    ('\u{2162}', &['\u{2172}']),
+    ('\u{2163}', &['\u{2173}']),
+    ('\u{2164}', &['\u{2174}']),
+    ('\u{2165}', &['\u{2175}']),
+    ('\u{2166}', &['\u{2176}']),
+    ('\u{2167}', &['\u{2177}']),
+    ('\u{2168}', &['\u{2178}']),
+    ('\u{2169}', &['\u{2179}']),
+    ('\u{216A}', &['\u{217A}']),
+    ('\u{216B}', &['\u{217B}']),
+    ('\u{216C}', &['\u{217C}']),
+    ('\u{216D}', &['\u{217D}']),
+    ('\u{216E}', &['\u{217E}']),
+    ('\u{216F}', &['\u{217F}']),
+    ('\u{2170}', &['\u{2160}']),
+    ('\u{2171}', &['\u{2161}']),
+    ('\u{2172}', &['\u{2162}']),
+    ('\u{2173}', &['\u{2163}']),
+    ('\u{2174}', &['\u{2164}']),
+    ('\u{2175}', &['\u{2165}']),
+    ('\u{2176}', &['\u{2166}']),
+    ('\u{2177}', &['\u{2167}']),
+    ('\u{2178}', &['\u{2168}']),
+    ('\u{2179}', &['\u{2169}']),
+    ('\u{217A}', &['\u{216A}']),
+    ('\u{217B}', &['\u{216B}']),
+    ('\u{217C}', &['\u{216C}']),
+    ('\u{217D}', &['\u{216D}']),
+    ('\u{217E}', &['\u{216E}']),
+    ('\u{217F}', &['\u{216F}']),
+    ('\u{2183}', &['\u{2184}']),
+    ('\u{2184}', &['\u{2183}']),
+    ('\u{24B6}', &['\u{24D0}']),
+    ('\u{24B7}', &['\u{24D1}']),
+    ('\u{24B8}', &['\u{24D2}']),
+    ('\u{24B9}', &['\u{24D3}']),
+    ('\u{24BA}', &['\u{24D4}']),
+    ('\u{24BB}', &['\u{24D5}']),
+    ('\u{24BC}', &['\u{24D6}']),
+    ('\u{24BD}', &['\u{24D7}']),
+    ('\u{24BE}', &['\u{24D8}']),
+    ('\u{24BF}', &['\u{24D9}']),
+    ('\u{24C0}', &['\u{24DA}']),
+    ('\u{24C1}', &['\u{24DB}']),
+    ('\u{24C2}', &['\u{24DC}']),
+    ('\u{24C3}', &['\u{24DD}']),
+    ('\u{24C4}', &['\u{24DE}']),
+    ('\u{24C5}', &['\u{24DF}']),
+    ('\u{24C6}', &['\u{24E0}']),
+    ('\u{24C7}', &['\u{24E1}']),
+    ('\u{24C8}', &['\u{24E2}']),
+    ('\u{24C9}', &['\u{24E3}']),
+    ('\u{24CA}', &['\u{24E4}']),
+    ('\u{24CB}', &['\u{24E5}']),
+    ('\u{24CC}', &['\u{24E6}']),
+    ('\u{24CD}', &['\u{24E7}']),
+    ('\u{24CE}', &['\u{24E8}']),
+    ('\u{24CF}', &['\u{24E9}']),
+    ('\u{24D0}', &['\u{24B6}']),
+    ('\u{24D1}', &['\u{24B7}']),
+    ('\u{24D2}', &['\u{24B8}']),
+    ('\u{24D3}', &['\u{24B9}']),
+    ('\u{24D4}', &['\u{24BA}']),
+    ('\u{24D5}', &['\u{24BB}']),
+    ('\u{24D6}', &['\u{24BC}']),
+    ('\u{24D7}', &['\u{24BD}']),
+    ('\u{24D8}', &['\u{24BE}']),
+    ('\u{24D9}', &['\u{24BF}']),
+    ('\u{24DA}', &['\u{24C0}']),
+    ('\u{24DB}', &['\u{24C1}']),
+    ('\u{24DC}', &['\u{24C2}']),
+    ('\u{24DD}', &['\u{24C3}']),
+    ('\u{24DE}', &['\u{24C4}']),
+    ('\u{24DF}', &['\u{24C5}']),
+    ('\u{24E0}', &['\u{24C6}']),
+    ('\u{24E1}', &['\u{24C7}']),
+    ('\u{24E2}', &['\u{24C8}']),
+    ('\u{24E3}', &['\u{24C9}']),
+    ('\u{24E4}', &['\u{24CA}']),
+    ('\u{24E5}', &['\u{24CB}']),
+    ('\u{24E6}', &['\u{24CC}']),
+    ('\u{24E7}', &['\u{24CD}']),
+    ('\u{24E8}', &['\u{24CE}']),
+    ('\u{24E9}', &['\u{24CF}']),
+    ('\u{2C00}', &['\u{2C30}']),
+    ('\u{2C01}', &['\u{2C31}']),
+    ('\u{2C02}', &['\u{2C32}']),
+    ('\u{2C03}', &['\u{2C33}']),
+    ('\u{2C04}', &['\u{2C34}']),
+    ('\u{2C05}', &['\u{2C35}']),
+    ('\u{2C06}', &['\u{2C36}']),
+    ('\u{2C07}', &['\u{2C37}']),
+    ('\u{2C08}', &['\u{2C38}']),
+    ('\u{2C09}', &['\u{2C39}']),
+    ('\u{2C0A}', &['\u{2C3A}']),
+    ('\u{2C0B}', &['\u{2C3B}']),
+    ('\u{2C0C}', &['\u{2C3C}']),
+    ('\u{2C0D}', &['\u{2C3D}']),
+    ('\u{2C0E}', &['\u{2C3E}']),
+    ('\u{2C0F}', &['\u{2C3F}']),
+    ('\u{2C10}', &['\u{2C40}']),
+    ('\u{2C11}', &['\u{2C41}']),
+    ('\u{2C12}', &['\u{2C42}']),
+    ('\u{2C13}', &['\u{2C43}']),
+    ('\u{2C14}', &['\u{2C44}']),
+    ('\u{2C15}', &['\u{2C45}']),
+    ('\u{2C16}', &['\u{2C46}']),
+    ('\u{2C17}', &['\u{2C47}']),
+    ('\u{2C18}', &['\u{2C48}']),
+    ('\u{2C19}', &['\u{2C49}']),
+    ('\u{2C1A}', &['\u{2C4A}']),
+    ('\u{2C1B}', &['\u{2C4B}']),
+    ('\u{2C1C}', &['\u{2C4C}']),
+    ('\u{2C1D}', &['\u{2C4D}']),
+    ('\u{2C1E}', &['\u{2C4E}']),
+    ('\u{2C1F}', &['\u{2C4F}']),
+    ('\u{2C20}', &['\u{2C50}']),
+    ('\u{2C21}', &['\u{2C51}']),
+    ('\u{2C22}', &['\u{2C52}']),
+    ('\u{2C23}', &['\u{2C53}']),
+    ('\u{2C24}', &['\u{2C54}']),
+    ('\u{2C25}', &['\u{2C55}']),
+    ('\u{2C26}', &['\u{2C56}']),
+    ('\u{2C27}', &['\u{2C57}']),
+    ('\u{2C28}', &['\u{2C58}']),
+    ('\u{2C29}', &['\u{2C59}']),
+    ('\u{2C2A}', &['\u{2C5A}']),
+    ('\u{2C2B}', &['\u{2C5B}']),
+    ('\u{2C2C}', &['\u{2C5C}']),
+    ('\u{2C2D}', &['\u{2C5D}']),
+    ('\u{2C2E}', &['\u{2C5E}']),
+    ('\u{2C2F}', &['\u{2C5F}']),
+    ('\u{2C30}', &['\u{2C00}']),
+    ('\u{2C31}', &['\u{2C01}']),
+    ('\u{2C32}', &['\u{2C02}']),
+    ('\u{2C33}', &['\u{2C03}']),
+    ('\u{2C34}', &['\u{2C04}']),
+    ('\u{2C35}', &['\u{2C05}']),
+    ('\u{2C36}', &['\u{2C06}']),
+    ('\u{2C37}', &['\u{2C07}']),
+    ('\u{2C38}', &['\u{2C08}']),
+    ('\u{2C39}', &['\u{2C09}']),
+    ('\u{2C3A}', &['\u{2C0A}']),
+    ('\u{2C3B}', &['\u{2C0B}']),
+    ('\u{2C3C}', &['\u{2C0C}']),
+    ('\u{2C3D}', &['\u{2C0D}']),
+    ('\u{2C3E}', &['\u{2C0E}']),
+    ('\u{2C3F}', &['\u{2C0F}']),
+    ('\u{2C40}', &['\u{2C10}']),
+    ('\u{2C41}', &['\u{2C11}']),
+    ('\u{2C42}', &['\u{2C12}']),
+    ('\u{2C43}', &['\u{2C13}']),
+    ('\u{2C44}', &['\u{2C14}']),
+    ('\u{2C45}', &['\u{2C15}']),
+    ('\u{2C46}', &['\u{2C16}']),
+    ('\u{2C47}', &['\u{2C17}']),
+    ('\u{2C48}', &['\u{2C18}']),
+    ('\u{2C49}', &['\u{2C19}']),
+    ('\u{2C4A}', &['\u{2C1A}']),
+    ('\u{2C4B}', &['\u{2C1B}']),
+    ('\u{2C4C}', &['\u{2C1C}']),
+    ('\u{2C4D}', &['\u{2C1D}']),
+    ('\u{2C4E}', &['\u{2C1E}']),
+    ('\u{2C4F}', &['\u{2C1F}']),
+    ('\u{2C50}', &['\u{2C20}']),
+    ('\u{2C51}', &['\u{2C21}']),
+    ('\u{2C52}', &['\u{2C22}']),
+    ('\u{2C53}', &['\u{2C23}']),
+    ('\u{2C54}', &['\u{2C24}']),
+    ('\u{2C55}', &['\u{2C25}']),
+    ('\u{2C56}', &['\u{2C26}']),
+    ('\u{2C57}', &['\u{2C27}']),
+    ('\u{2C58}', &['\u{2C28}']),
+    ('\u{2C59}', &['\u{2C29}']),
+    ('\u{2C5A}', &['\u{2C2A}']),
+    ('\u{2C5B}', &['\u{2C2B}']),
+    ('\u{2C5C}', &['\u{2C2C}']),
+    ('\u{2C5D}', &['\u{2C2D}']),
+    ('\u{2C5E}', &['\u{2C2E}']),
+    ('\u{2C5F}', &['\u{2C2F}']),
+    ('\u{2C60}', &['\u{2C61}']),
+    ('\u{2C61}', &['\u{2C60}']),
+    ('\u{2C62}', &['\u{26B}']),
+    ('\u{2C63}', &['\u{1D7D}']),
+    ('\u{2C64}', &['\u{27D}']),
+    ('\u{2C65}', &['\u{23A}']),
+    ('\u{2C66}', &['\u{23E}']),
+    ('\u{2C67}', &['\u{2C68}']),
+    ('\u{2C68}', &['\u{2C67}']),
+    ('\u{2C69}', &['\u{2C6A}']),
+    ('\u{2C6A}', &['\u{2C69}']),
+    ('\u{2C6B}', &['\u{2C6C}']),
+    ('\u{2C6C}', &['\u{2C6B}']),
+    ('\u{2C6D}', &['\u{251}']),
+    ('\u{2C6E}', &['\u{271}']),
+    ('\u{2C6F}', &['\u{250}']),
+    ('\u{2C70}', &['\u{252}']),
+    ('\u{2C72}', &['\u{2C73}']),
+    ('\u{2C73}', &['\u{2C72}']),
+    ('\u{2C75}', &['\u{2C76}']),
+    ('\u{2C76}', &['\u{2C75}']),
+    ('\u{2C7E}', &['\u{23F}']),
+    ('\u{2C7F}', &['\u{240}']),
+    ('\u{2C80}', &['\u{2C81}']),
+    ('\u{2C81}', &['\u{2C80}']),
+    ('\u{2C82}', &['\u{2C83}']),
+    ('\u{2C83}', &['\u{2C82}']),
+    ('\u{2C84}', &['\u{2C85}']),
+    ('\u{2C85}', &['\u{2C84}']),
+    ('\u{2C86}', &['\u{2C87}']),
+    ('\u{2C87}', &['\u{2C86}']),
+    ('\u{2C88}', &['\u{2C89}']),
+    ('\u{2C89}', &['\u{2C88}']),
+    ('\u{2C8A}', &['\u{2C8B}']),
+    ('\u{2C8B}', &['\u{2C8A}']),
+    ('\u{2C8C}', &['\u{2C8D}']),
+    ('\u{2C8D}', &['\u{2C8C}']),
+    ('\u{2C8E}', &['\u{2C8F}']),
+    ('\u{2C8F}', &['\u{2C8E}']),
+    ('\u{2C90}', &['\u{2C91}']),
+    ('\u{2C91}', &['\u{2C90}']),
+    ('\u{2C92}', &['\u{2C93}']),
+    ('\u{2C93}', &['\u{2C92}']),
+    ('\u{2C94}', &['\u{2C95}']),
+    ('\u{2C95}', &['\u{2C94}']),
+    ('\u{2C96}', &['\u{2C97}']),
+    ('\u{2C97}', &['\u{2C96}']),
+    ('\u{2C98}', &['\u{2C99}']),
+    ('\u{2C99}', &['\u{2C98}']),
+    ('\u{2C9A}', &['\u{2C9B}']),
+    ('\u{2C9B}', &['\u{2C9A}']),
+    ('\u{2C9C}', &['\u{2C9D}']),
+    ('\u{2C9D}', &['\u{2C9C}']),
+    ('\u{2C9E}', &['\u{2C9F}']),
+    ('\u{2C9F}', &['\u{2C9E}']),
+    ('\u{2CA0}', &['\u{2CA1}']),
+    ('\u{2CA1}', &['\u{2CA0}']),
+    ('\u{2CA2}', &['\u{2CA3}']),
+    ('\u{2CA3}', &['\u{2CA2}']),
+    ('\u{2CA4}', &['\u{2CA5}']),
+    ('\u{2CA5}', &['\u{2CA4}']),
+    ('\u{2CA6}', &['\u{2CA7}']),
+    ('\u{2CA7}', &['\u{2CA6}']),
+    ('\u{2CA8}', &['\u{2CA9}']),
+    ('\u{2CA9}', &['\u{2CA8}']),
+    ('\u{2CAA}', &['\u{2CAB}']),
+    ('\u{2CAB}', &['\u{2CAA}']),
+    ('\u{2CAC}', &['\u{2CAD}']),
+    ('\u{2CAD}', &['\u{2CAC}']),
+    ('\u{2CAE}', &['\u{2CAF}']),
+    ('\u{2CAF}', &['\u{2CAE}']),
+    ('\u{2CB0}', &['\u{2CB1}']),
+    ('\u{2CB1}', &['\u{2CB0}']),
+    ('\u{2CB2}', &['\u{2CB3}']),
+    ('\u{2CB3}', &['\u{2CB2}']),
+    ('\u{2CB4}', &['\u{2CB5}']),
+    ('\u{2CB5}', &['\u{2CB4}']),
+    ('\u{2CB6}', &['\u{2CB7}']),
+    ('\u{2CB7}', &['\u{2CB6}']),
+    ('\u{2CB8}', &['\u{2CB9}']),
+    ('\u{2CB9}', &['\u{2CB8}']),
+    ('\u{2CBA}', &['\u{2CBB}']),
+    ('\u{2CBB}', &['\u{2CBA}']),
+    ('\u{2CBC}', &['\u{2CBD}']),
+    ('\u{2CBD}', &['\u{2CBC}']),
+    ('\u{2CBE}', &['\u{2CBF}']),
+    ('\u{2CBF}', &['\u{2CBE}']),
+    ('\u{2CC0}', &['\u{2CC1}']),
+    ('\u{2CC1}', &['\u{2CC0}']),
+    ('\u{2CC2}', &['\u{2CC3}']),
+    ('\u{2CC3}', &['\u{2CC2}']),
+    ('\u{2CC4}', &['\u{2CC5}']),
+    ('\u{2CC5}', &['\u{2CC4}']),
+    ('\u{2CC6}', &['\u{2CC7}']),
+    ('\u{2CC7}', &['\u{2CC6}']),
+    ('\u{2CC8}', &['\u{2CC9}']),
+    ('\u{2CC9}', &['\u{2CC8}']),
+    ('\u{2CCA}', &['\u{2CCB}']),
+    ('\u{2CCB}', &['\u{2CCA}']),
+    ('\u{2CCC}', &['\u{2CCD}']),
+    ('\u{2CCD}', &['\u{2CCC}']),
+    ('\u{2CCE}', &['\u{2CCF}']),
+    ('\u{2CCF}', &['\u{2CCE}']),
+    ('\u{2CD0}', &['\u{2CD1}']),
+    ('\u{2CD1}', &['\u{2CD0}']),
+    ('\u{2CD2}', &['\u{2CD3}']),
+    ('\u{2CD3}', &['\u{2CD2}']),
+    ('\u{2CD4}', &['\u{2CD5}']),
+    ('\u{2CD5}', &['\u{2CD4}']),
+    ('\u{2CD6}', &['\u{2CD7}']),
+    ('\u{2CD7}', &['\u{2CD6}']),
+    ('\u{2CD8}', &['\u{2CD9}']),
+    ('\u{2CD9}', &['\u{2CD8}']),
+    ('\u{2CDA}', &['\u{2CDB}']),
+    ('\u{2CDB}', &['\u{2CDA}']),
+    ('\u{2CDC}', &['\u{2CDD}']),
+    ('\u{2CDD}', &['\u{2CDC}']),
+    ('\u{2CDE}', &['\u{2CDF}']),
+    ('\u{2CDF}', &['\u{2CDE}']),
+    ('\u{2CE0}', &['\u{2CE1}']),
+    ('\u{2CE1}', &['\u{2CE0}']),
+    ('\u{2CE2}', &['\u{2CE3}']),
+    ('\u{2CE3}', &['\u{2CE2}']),
+    ('\u{2CEB}', &['\u{2CEC}']),
+    ('\u{2CEC}', &['\u{2CEB}']),
+    ('\u{2CED}', &['\u{2CEE}']),
+    ('\u{2CEE}', &['\u{2CED}']),
+    ('\u{2CF2}', &['\u{2CF3}']),
+    ('\u{2CF3}', &['\u{2CF2}']),
+    ('\u{2D00}', &['\u{10A0}']),
+    ('\u{2D01}', &['\u{10A1}']),
+    ('\u{2D02}', &['\u{10A2}']),
+    ('\u{2D03}', &['\u{10A3}']),
+    ('\u{2D04}', &['\u{10A4}']),
+    ('\u{2D05}', &['\u{10A5}']),
+    ('\u{2D06}', &['\u{10A6}']),
+    ('\u{2D07}', &['\u{10A7}']),
+    ('\u{2D08}', &['\u{10A8}']),
+    ('\u{2D09}', &['\u{10A9}']),
+    ('\u{2D0A}', &['\u{10AA}']),
+    ('\u{2D0B}', &['\u{10AB}']),
+    ('\u{2D0C}', &['\u{10AC}']),
+    ('\u{2D0D}', &['\u{10AD}']),
+    ('\u{2D0E}', &['\u{10AE}']),
+    ('\u{2D0F}', &['\u{10AF}']),
+    ('\u{2D10}', &['\u{10B0}']),
+    ('\u{2D11}', &['\u{10B1}']),
+    ('\u{2D12}', &['\u{10B2}']),
+    ('\u{2D13}', &['\u{10B3}']),
+    ('\u{2D14}', &['\u{10B4}']),
+    ('\u{2D15}', &['\u{10B5}']),
+    ('\u{2D16}', &['\u{10B6}']),
+    ('\u{2D17}', &['\u{10B7}']),
+    ('\u{2D18}', &['\u{10B8}']),
+    ('\u{2D19}', &['\u{10B9}']),
+    ('\u{2D1A}', &['\u{10BA}']),
+    ('\u{2D1B}', &['\u{10BB}']),
+    ('\u{2D1C}', &['\u{10BC}']),
+    ('\u{2D1D}', &['\u{10BD}']),
+    ('\u{2D1E}', &['\u{10BE}']),
+    ('\u{2D1F}', &['\u{10BF}']),
+    ('\u{2D20}', &['\u{10C0}']),
+    ('\u{2D21}', &['\u{10C1}']),
+    ('\u{2D22}', &['\u{10C2}']),
+    ('\u{2D23}', &['\u{10C3}']),
+    ('\u{2D24}', &['\u{10C4}']),
+    ('\u{2D25}', &['\u{10C5}']),
+    ('\u{2D27}', &['\u{10C7}']),
+    ('\u{2D2D}', &['\u{10CD}']),
+    ('\u{A640}', &['\u{A641}']),
+    ('\u{A641}', &['\u{A640}']),
+    ('\u{A642}', &['\u{A643}']),
+    ('\u{A643}', &['\u{A642}']),
+    ('\u{A644}', &['\u{A645}']),
+    ('\u{A645}', &['\u{A644}']),
+    ('\u{A646}', &['\u{A647}']),
+    ('\u{A647}', &['\u{A646}']),
+    ('\u{A648}', &['\u{A649}']),
+    ('\u{A649}', &['\u{A648}']),
+    ('\u{A64A}', &['\u{1C88}', '\u{A64B}']),
+    ('\u{A64B}', &['\u{1C88}', '\u{A64A}']),
+    ('\u{A64C}', &['\u{A64D}']),
+    ('\u{A64D}', &['\u{A64C}']),
+    ('\u{A64E}', &['\u{A64F}']),
+    ('\u{A64F}', &['\u{A64E}']),
+    ('\u{A650}', &['\u{A651}']),
+    ('\u{A651}', &['\u{A650}']),
+    ('\u{A652}', &['\u{A653}']),
+    ('\u{A653}', &['\u{A652}']),
+    ('\u{A654}', &['\u{A655}']),
+    ('\u{A655}', &['\u{A654}']),
+    ('\u{A656}', &['\u{A657}']),
+    ('\u{A657}', &['\u{A656}']),
+    ('\u{A658}', &['\u{A659}']),
+    ('\u{A659}', &['\u{A658}']),
+    ('\u{A65A}', &['\u{A65B}']),
+    ('\u{A65B}', &['\u{A65A}']),
+    ('\u{A65C}', &['\u{A65D}']),
+    ('\u{A65D}', &['\u{A65C}']),
+    ('\u{A65E}', &['\u{A65F}']),
+    ('\u{A65F}', &['\u{A65E}']),
+    ('\u{A660}', &['\u{A661}']),
+    ('\u{A661}', &['\u{A660}']),
+    ('\u{A662}', &['\u{A663}']),
+    ('\u{A663}', &['\u{A662}']),
+    ('\u{A664}', &['\u{A665}']),
+    ('\u{A665}', &['\u{A664}']),
+    ('\u{A666}', &['\u{A667}']),
+    ('\u{A667}', &['\u{A666}']),
+    ('\u{A668}', &['\u{A669}']),
+    ('\u{A669}', &['\u{A668}']),
+    ('\u{A66A}', &['\u{A66B}']),
+    ('\u{A66B}', &['\u{A66A}']),
+    ('\u{A66C}', &['\u{A66D}']),
+    ('\u{A66D}', &['\u{A66C}']),
+    ('\u{A680}', &['\u{A681}']),
+    ('\u{A681}', &['\u{A680}']),
+    ('\u{A682}', &['\u{A683}']),
+    ('\u{A683}', &['\u{A682}']),
+    ('\u{A684}', &['\u{A685}']),
+    ('\u{A685}', &['\u{A684}']),
+    ('\u{A686}', &['\u{A687}']),
+    ('\u{A687}', &['\u{A686}']),
+    ('\u{A688}', &['\u{A689}']),
+    ('\u{A689}', &['\u{A688}']),
+    ('\u{A68A}', &['\u{A68B}']),
+    ('\u{A68B}', &['\u{A68A}']),
+    ('\u{A68C}', &['\u{A68D}']),
+    ('\u{A68D}', &['\u{A68C}']),
+    ('\u{A68E}', &['\u{A68F}']),
+    ('\u{A68F}', &['\u{A68E}']),
+    ('\u{A690}', &['\u{A691}']),
+    ('\u{A691}', &['\u{A690}']),
+    ('\u{A692}', &['\u{A693}']),
+    ('\u{A693}', &['\u{A692}']),
+    ('\u{A694}', &['\u{A695}']),
+    ('\u{A695}', &['\u{A694}']),
+    ('\u{A696}', &['\u{A697}']),
+    ('\u{A697}', &['\u{A696}']),
+    ('\u{A698}', &['\u{A699}']),
+    ('\u{A699}', &['\u{A698}']),
+    ('\u{A69A}', &['\u{A69B}']),
+    ('\u{A69B}', &['\u{A69A}']),
+    ('\u{A722}', &['\u{A723}']),
+    ('\u{A723}', &['\u{A722}']),
+    ('\u{A724}', &['\u{A725}']),
+    ('\u{A725}', &['\u{A724}']),
+    ('\u{A726}', &['\u{A727}']),
+    ('\u{A727}', &['\u{A726}']),
+    ('\u{A728}', &['\u{A729}']),
+    ('\u{A729}', &['\u{A728}']),
+    ('\u{A72A}', &['\u{A72B}']),
+    ('\u{A72B}', &['\u{A72A}']),
+    ('\u{A72C}', &['\u{A72D}']),
+    ('\u{A72D}', &['\u{A72C}']),
+    ('\u{A72E}', &['\u{A72F}']),
+    ('\u{A72F}', &['\u{A72E}']),
+    ('\u{A732}', &['\u{A733}']),
+    ('\u{A733}', &['\u{A732}']),
+    ('\u{A734}', &['\u{A735}']),
+    ('\u{A735}', &['\u{A734}']),
+    ('\u{A736}', &['\u{A737}']),
+    ('\u{A737}', &['\u{A736}']),
+    ('\u{A738}', &['\u{A739}']),
+    ('\u{A739}', &['\u{A738}']),
+    ('\u{A73A}', &['\u{A73B}']),
+    ('\u{A73B}', &['\u{A73A}']),
+    ('\u{A73C}', &['\u{A73D}']),
+    ('\u{A73D}', &['\u{A73C}']),
+    ('\u{A73E}', &['\u{A73F}']),
+    ('\u{A73F}', &['\u{A73E}']),
+    ('\u{A740}', &['\u{A741}']),
+    ('\u{A741}', &['\u{A740}']),
+    ('\u{A742}', &['\u{A743}']),
+    ('\u{A743}', &['\u{A742}']),
+    ('\u{A744}', &['\u{A745}']),
+    ('\u{A745}', &['\u{A744}']),
+    ('\u{A746}', &['\u{A747}']),
+    ('\u{A747}', &['\u{A746}']),
+    ('\u{A748}', &['\u{A749}']),
+    ('\u{A749}', &['\u{A748}']),
+    ('\u{A74A}', &['\u{A74B}']),
+    ('\u{A74B}', &['\u{A74A}']),
+    ('\u{A74C}', &['\u{A74D}']),
+    ('\u{A74D}', &['\u{A74C}']),
+    ('\u{A74E}', &['\u{A74F}']),
+    ('\u{A74F}', &['\u{A74E}']),
+    ('\u{A750}', &['\u{A751}']),
+    ('\u{A751}', &['\u{A750}']),
+    ('\u{A752}', &['\u{A753}']),
+    ('\u{A753}', &['\u{A752}']),
+    ('\u{A754}', &['\u{A755}']),
+    ('\u{A755}', &['\u{A754}']),
+    ('\u{A756}', &['\u{A757}']),
+    ('\u{A757}', &['\u{A756}']),
+    ('\u{A758}', &['\u{A759}']),
+    ('\u{A759}', &['\u{A758}']),
+    ('\u{A75A}', &['\u{A75B}']),
+    ('\u{A75B}', &['\u{A75A}']),
+    ('\u{A75C}', &['\u{A75D}']),
+    ('\u{A75D}', &['\u{A75C}']),
+    ('\u{A75E}', &['\u{A75F}']),
+    ('\u{A75F}', &['\u{A75E}']),
+    ('\u{A760}', &['\u{A761}']),
+    ('\u{A761}', &['\u{A760}']),
+    ('\u{A762}', &['\u{A763}']),
+    ('\u{A763}', &['\u{A762}']),
+    ('\u{A764}', &['\u{A765}']),
+    ('\u{A765}', &['\u{A764}']),
+    ('\u{A766}', &['\u{A767}']),
+    ('\u{A767}', &['\u{A766}']),
+    ('\u{A768}', &['\u{A769}']),
+    ('\u{A769}', &['\u{A768}']),
+    ('\u{A76A}', &['\u{A76B}']),
+    ('\u{A76B}', &['\u{A76A}']),
+    ('\u{A76C}', &['\u{A76D}']),
+    ('\u{A76D}', &['\u{A76C}']),
+    ('\u{A76E}', &['\u{A76F}']),
+    ('\u{A76F}', &['\u{A76E}']),
+    ('\u{A779}', &['\u{A77A}']),
+    ('\u{A77A}', &['\u{A779}']),
+    ('\u{A77B}', &['\u{A77C}']),
+    ('\u{A77C}', &['\u{A77B}']),
+    ('\u{A77D}', &['\u{1D79}']),
+    ('\u{A77E}', &['\u{A77F}']),
+    ('\u{A77F}', &['\u{A77E}']),
+    ('\u{A780}', &['\u{A781}']),
+    ('\u{A781}', &['\u{A780}']),
+    ('\u{A782}', &['\u{A783}']),
+    ('\u{A783}', &['\u{A782}']),
+    ('\u{A784}', &['\u{A785}']),
+    ('\u{A785}', &['\u{A784}']),
+    ('\u{A786}', &['\u{A787}']),
+    ('\u{A787}', &['\u{A786}']),
+    ('\u{A78B}', &['\u{A78C}']),
+    ('\u{A78C}', &['\u{A78B}']),
+    ('\u{A78D}', &['\u{265}']),
+    ('\u{A790}', &['\u{A791}']),
+    ('\u{A791}', &['\u{A790}']),
+    ('\u{A792}', &['\u{A793}']),
+    ('\u{A793}', &['\u{A792}']),
+    ('\u{A794}', &['\u{A7C4}']),
+    ('\u{A796}', &['\u{A797}']),
+    ('\u{A797}', &['\u{A796}']),
+    ('\u{A798}', &['\u{A799}']),
+    ('\u{A799}', &['\u{A798}']),
+    ('\u{A79A}', &['\u{A79B}']),
+    ('\u{A79B}', &['\u{A79A}']),
+    ('\u{A79C}', &['\u{A79D}']),
+    ('\u{A79D}', &['\u{A79C}']),
+    ('\u{A79E}', &['\u{A79F}']),
+    ('\u{A79F}', &['\u{A79E}']),
+    ('\u{A7A0}', &['\u{A7A1}']),
+    ('\u{A7A1}', &['\u{A7A0}']),
+    ('\u{A7A2}', &['\u{A7A3}']),
+    ('\u{A7A3}', &['\u{A7A2}']),
+    ('\u{A7A4}', &['\u{A7A5}']),
+    ('\u{A7A5}', &['\u{A7A4}']),
+    ('\u{A7A6}', &['\u{A7A7}']),
+    ('\u{A7A7}', &['\u{A7A6}']),
+    ('\u{A7A8}', &['\u{A7A9}']),
+    ('\u{A7A9}', &['\u{A7A8}']),
+    ('\u{A7AA}', &['\u{266}']),
+    ('\u{A7AB}', &['\u{25C}']),
+    ('\u{A7AC}', &['\u{261}']),
+    ('\u{A7AD}', &['\u{26C}']),
+    ('\u{A7AE}', &['\u{26A}']),
+    ('\u{A7B0}', &['\u{29E}']),
+    ('\u{A7B1}', &['\u{287}']),
+    ('\u{A7B2}', &['\u{29D}']),
+    ('\u{A7B3}', &['\u{AB53}']),
+    ('\u{A7B4}', &['\u{A7B5}']),
+    ('\u{A7B5}', &['\u{A7B4}']),
+    ('\u{A7B6}', &['\u{A7B7}']),
+    ('\u{A7B7}', &['\u{A7B6}']),
+    ('\u{A7B8}', &['\u{A7B9}']),
+    ('\u{A7B9}', &['\u{A7B8}']),
+    ('\u{A7BA}', &['\u{A7BB}']),
+    ('\u{A7BB}', &['\u{A7BA}']),
+    ('\u{A7BC}', &['\u{A7BD}']),
+    ('\u{A7BD}', &['\u{A7BC}']),
+    ('\u{A7BE}', &['\u{A7BF}']),
+    ('\u{A7BF}', &['\u{A7BE}']),
+    ('\u{A7C0}', &['\u{A7C1}']),
+    ('\u{A7C1}', &['\u{A7C0}']),
+    ('\u{A7C2}', &['\u{A7C3}']),
+    ('\u{A7C3}', &['\u{A7C2}']),
+    ('\u{A7C4}', &['\u{A794}']),
+    ('\u{A7C5}', &['\u{282}']),
+    ('\u{A7C6}', &['\u{1D8E}']),
+    ('\u{A7C7}', &['\u{A7C8}']),
+    ('\u{A7C8}', &['\u{A7C7}']),
+    ('\u{A7C9}', &['\u{A7CA}']),
+    ('\u{A7CA}', &['\u{A7C9}']),
+    ('\u{A7CB}', &['\u{264}']),
+    ('\u{A7CC}', &['\u{A7CD}']),
+    ('\u{A7CD}', &['\u{A7CC}']),
+    ('\u{A7CE}', &['\u{A7CF}']),
+    ('\u{A7CF}', &['\u{A7CE}']),
+    ('\u{A7D0}', &['\u{A7D1}']),
+    ('\u{A7D1}', &['\u{A7D0}']),
+    ('\u{A7D2}', &['\u{A7D3}']),
+    ('\u{A7D3}', &['\u{A7D2}']),
+    ('\u{A7D4}', &['\u{A7D5}']),
+    ('\u{A7D5}', &['\u{A7D4}']),
+    ('\u{A7D6}', &['\u{A7D7}']),
+    ('\u{A7D7}', &['\u{A7D6}']),
+    ('\u{A7D8}', &['\u{A7D9}']),
+    ('\u{A7D9}', &['\u{A7D8}']),
+    ('\u{A7DA}', &['\u{A7DB}']),
+    ('\u{A7DB}', &['\u{A7DA}']),
+    ('\u{A7DC}', &['\u{19B}']),
+    ('\u{A7F5}', &['\u{A7F6}']),
+    ('\u{A7F6}', &['\u{A7F5}']),
+    ('\u{AB53}', &['\u{A7B3}']),
+    ('\u{AB70}', &['\u{13A0}']),
+    ('\u{AB71}', &['\u{13A1}']),
+    ('\u{AB72}', &['\u{13A2}']),
+    ('\u{AB73}', &['\u{13A3}']),
+    ('\u{AB74}', &['\u{13A4}']),
+    ('\u{AB75}', &['\u{13A5}']),
+    ('\u{AB76}', &['\u{13A6}']),
+    ('\u{AB77}', &['\u{13A7}']),
+    ('\u{AB78}', &['\u{13A8}']),
+    ('\u{AB79}', &['\u{13A9}']),
+    ('\u{AB7A}', &['\u{13AA}']),
+    ('\u{AB7B}', &['\u{13AB}']),
+    ('\u{AB7C}', &['\u{13AC}']),
+    ('\u{AB7D}', &['\u{13AD}']),
+    ('\u{AB7E}', &['\u{13AE}']),
+    ('\u{AB7F}', &['\u{13AF}']),
+    ('\u{AB80}', &['\u{13B0}']),
+    ('\u{AB81}', &['\u{13B1}']),
+    ('\u{AB82}', &['\u{13B2}']),
+    ('\u{AB83}', &['\u{13B3}']),
+    ('\u{AB84}', &['\u{13B4}']),
+    ('\u{AB85}', &['\u{13B5}']),
+    ('\u{AB86}', &['\u{13B6}']),
+    ('\u{AB87}', &['\u{13B7}']),
+    ('\u{AB88}', &['\u{13B8}']),
+    ('\u{AB89}', &['\u{13B9}']),
+    ('\u{AB8A}', &['\u{13BA}']),
+    ('\u{AB8B}', &['\u{13BB}']),
+    ('\u{AB8C}', &['\u{13BC}']),
+    ('\u{AB8D}', &['\u{13BD}']),
+    ('\u{AB8E}', &['\u{13BE}']),
+    ('\u{AB8F}', &['\u{13BF}']),
+    ('\u{AB90}', &['\u{13C0}']),
+    ('\u{AB91}', &['\u{13C1}']),
+    ('\u{AB92}', &['\u{13C2}']),
+    ('\u{AB93}', &['\u{13C3}']),
+    ('\u{AB94}', &['\u{13C4}']),
+    ('\u{AB95}', &['\u{13C5}']),
+    ('\u{AB96}', &['\u{13C6}']),
+    ('\u{AB97}', &['\u{13C7}']),
+    ('\u{AB98}', &['\u{13C8}']),
+    ('\u{AB99}', &['\u{13C9}']),
+    ('\u{AB9A}', &['\u{13CA}']),
+    ('\u{AB9B}', &['\u{13CB}']),
+    ('\u{AB9C}', &['\u{13CC}']),
+    ('\u{AB9D}', &['\u{13CD}']),
+    ('\u{AB9E}', &['\u{13CE}']),
+    ('\u{AB9F}', &['\u{13CF}']),
+    ('\u{ABA0}', &['\u{13D0}']),
+    ('\u{ABA1}', &['\u{13D1}']),
+    ('\u{ABA2}', &['\u{13D2}']),
+    ('\u{ABA3}', &['\u{13D3}']),
+    ('\u{ABA4}', &['\u{13D4}']),
+    ('\u{ABA5}', &['\u{13D5}']),
+    ('\u{ABA6}', &['\u{13D6}']),
+    ('\u{ABA7}', &['\u{13D7}']),
+    ('\u{ABA8}', &['\u{13D8}']),
+    ('\u{ABA9}', &['\u{13D9}']),
+    ('\u{ABAA}', &['\u{13DA}']),
+    ('\u{ABAB}', &['\u{13DB}']),
+    ('\u{ABAC}', &['\u{13DC}']),
+    ('\u{ABAD}', &['\u{13DD}']),
+    ('\u{ABAE}', &['\u{13DE}']),
+    ('\u{ABAF}', &['\u{13DF}']),
+    ('\u{ABB0}', &['\u{13E0}']),
+    ('\u{ABB1}', &['\u{13E1}']),
+    ('\u{ABB2}', &['\u{13E2}']),
+    ('\u{ABB3}', &['\u{13E3}']),
+    ('\u{ABB4}', &['\u{13E4}']),
+    ('\u{ABB5}', &['\u{13E5}']),
+    ('\u{ABB6}', &['\u{13E6}']),
+    ('\u{ABB7}', &['\u{13E7}']),
+    ('\u{ABB8}', &['\u{13E8}']),
+    ('\u{ABB9}', &['\u{13E9}']),
+    ('\u{ABBA}', &['\u{13EA}']),
+    ('\u{ABBB}', &['\u{13EB}']),
+    ('\u{ABBC}', &['\u{13EC}']),
+    ('\u{ABBD}', &['\u{13ED}']),
+    ('\u{ABBE}', &['\u{13EE}']),
+    ('\u{ABBF}', &['\u{13EF}']),
+    ('\u{FF21}', &['\u{FF41}']),
+    ('\u{FF22}', &['\u{FF42}']),
+    ('\u{FF23}', &['\u{FF43}']),
+    ('\u{FF24}', &['\u{FF44}']),
+    ('\u{FF25}', &['\u{FF45}']),
+    ('\u{FF26}', &['\u{FF46}']),
+    ('\u{FF27}', &['\u{FF47}']),
+    ('\u{FF28}', &['\u{FF48}']),
+    ('\u{FF29}', &['\u{FF49}']),
+    ('\u{FF2A}', &['\u{FF4A}']),
+    ('\u{FF2B}', &['\u{FF4B}']),
+    ('\u{FF2C}', &['\u{FF4C}']),
+    ('\u{FF2D}', &['\u{FF4D}']),
+    ('\u{FF2E}', &['\u{FF4E}']),
+    ('\u{FF2F}', &['\u{FF4F}']),
+    ('\u{FF30}', &['\u{FF50}']),
+    ('\u{FF31}', &['\u{FF51}']),
+    ('\u{FF32}', &['\u{FF52}']),
+    ('\u{FF33}', &['\u{FF53}']),
+    ('\u{FF34}', &['\u{FF54}']),
+    ('\u{FF35}', &['\u{FF55}']),
+    ('\u{FF36}', &['\u{FF56}']),
+    ('\u{FF37}', &['\u{FF57}']),
+    ('\u{FF38}', &['\u{FF58}']),
+    ('\u{FF39}', &['\u{FF59}']),
+    ('\u{FF3A}', &['\u{FF5A}']),
+    ('\u{FF41}', &['\u{FF21}']),
+    ('\u{FF42}', &['\u{FF22}']),
+    ('\u{FF43}', &['\u{FF23}']),
+    ('\u{FF44}', &['\u{FF24}']),
+    ('\u{FF45}', &['\u{FF25}']),
+    ('\u{FF46}', &['\u{FF26}']),
+    ('\u{FF47}', &['\u{FF27}']),
+    ('\u{FF48}', &['\u{FF28}']),
+    ('\u{FF49}', &['\u{FF29}']),
+    ('\u{FF4A}', &['\u{FF2A}']),
+    ('\u{FF4B}', &['\u{FF2B}']),
+    ('\u{FF4C}', &['\u{FF2C}']),
+    ('\u{FF4D}', &['\u{FF2D}']),
+    ('\u{FF4E}', &['\u{FF2E}']),
+    ('\u{FF4F}', &['\u{FF2F}']),
+    ('\u{FF50}', &['\u{FF30}']),
+    ('\u{FF51}', &['\u{FF31}']),
+    ('\u{FF52}', &['\u{FF32}']),
+    ('\u{FF53}', &['\u{FF33}']),
+    ('\u{FF54}', &['\u{FF34}']),
+    ('\u{FF55}', &['\u{FF35}']),
+    ('\u{FF56}', &['\u{FF36}']),
+    ('\u{FF57}', &['\u{FF37}']),
+    ('\u{FF58}', &['\u{FF38}']),
+    ('\u{FF59}', &['\u{FF39}']),
+    ('\u{FF5A}', &['\u{FF3A}']),
+    ('\u{10400}', &['\u{10428}']),
+    ('\u{10401}', &['\u{10429}']),
+    ('\u{10402}', &['\u{1042A}']),
+    ('\u{10403}', &['\u{1042B}']),
+    ('\u{10404}', &['\u{1042C}']),
+    ('\u{10405}', &['\u{1042D}']),
+    ('\u{10406}', &['\u{1042E}']),
+    ('\u{10407}', &['\u{1042F}']),
+    ('\u{10408}', &['\u{10430}']),
+    ('\u{10409}', &['\u{10431}']),
+    ('\u{1040A}', &['\u{10432}']),
+    ('\u{1040B}', &['\u{10433}']),
+    ('\u{1040C}', &['\u{10434}']),
+    ('\u{1040D}', &['\u{10435}']),
+    ('\u{1040E}', &['\u{10436}']),
+    ('\u{1040F}', &['\u{10437}']),
+    ('\u{10410}', &['\u{10438}']),
+    ('\u{10411}', &['\u{10439}']),
+    ('\u{10412}', &['\u{1043A}']),
+    ('\u{10413}', &['\u{1043B}']),
+    ('\u{10414}', &['\u{1043C}']),
+    ('\u{10415}', &['\u{1043D}']),
+    ('\u{10416}', &['\u{1043E}']),
+    ('\u{10417}', &['\u{1043F}']),
+    ('\u{10418}', &['\u{10440}']),
+    ('\u{10419}', &['\u{10441}']),
+    ('\u{1041A}', &['\u{10442}']),
+    ('\u{1041B}', &['\u{10443}']),
+    ('\u{1041C}', &['\u{10444}']),
+    ('\u{1041D}', &['\u{10445}']),
+    ('\u{1041E}', &['\u{10446}']),
+    ('\u{1041F}', &['\u{10447}']),
+    ('\u{10420}', &['\u{10448}']),
+    ('\u{10421}', &['\u{10449}']),
+    ('\u{10422}', &['\u{1044A}']),
+    ('\u{10423}', &['\u{1044B}']),
+    ('\u{10424}', &['\u{1044C}']),
+    ('\u{10425}', &['\u{1044D}']),
+    ('\u{10426}', &['\u{1044E}']),
+    ('\u{10427}', &['\u{1044F}']),
+    ('\u{10428}', &['\u{10400}']),
+    ('\u{10429}', &['\u{10401}']),
+    ('\u{1042A}', &['\u{10402}']),
+    ('\u{1042B}', &['\u{10403}']),
+    ('\u{1042C}', &['\u{10404}']),
+    ('\u{1042D}', &['\u{10405}']),
+    ('\u{1042E}', &['\u{10406}']),
+    ('\u{1042F}', &['\u{10407}']),
+    ('\u{10430}', &['\u{10408}']),
+    ('\u{10431}', &['\u{10409}']),
+    ('\u{10432}', &['\u{1040A}']),
+    ('\u{10433}', &['\u{1040B}']),
+    ('\u{10434}', &['\u{1040C}']),
+    ('\u{10435}', &['\u{1040D}']),
+    ('\u{10436}', &['\u{1040E}']),
+    ('\u{10437}', &['\u{1040F}']),
+    ('\u{10438}', &['\u{10410}']),
+    ('\u{10439}', &['\u{10411}']),
+    ('\u{1043A}', &['\u{10412}']),
+    ('\u{1043B}', &['\u{10413}']),
+    ('\u{1043C}', &['\u{10414}']),
+    ('\u{1043D}', &['\u{10415}']),
+    ('\u{1043E}', &['\u{10416}']),
+    ('\u{1043F}', &['\u{10417}']),
+    ('\u{10440}', &['\u{10418}']),
+    ('\u{10441}', &['\u{10419}']),
+    ('\u{10442}', &['\u{1041A}']),
+    ('\u{10443}', &['\u{1041B}']),
+    ('\u{10444}', &['\u{1041C}']),
+    ('\u{10445}', &['\u{1041D}']),
+    ('\u{10446}', &['\u{1041E}']),
+    ('\u{10447}', &['\u{1041F}']),
+    ('\u{10448}', &['\u{10420}']),
+    ('\u{10449}', &['\u{10421}']),
+    ('\u{1044A}', &['\u{10422}']),
+    ('\u{1044B}', &['\u{10423}']),
+    ('\u{1044C}', &['\u{10424}']),
+    ('\u{1044D}', &['\u{10425}']),
+    ('\u{1044E}', &['\u{10426}']),
+    ('\u{1044F}', &['\u{10427}']),
+    ('\u{104B0}', &['\u{104D8}']),
+    ('\u{104B1}', &['\u{104D9}']),
+    ('\u{104B2}', &['\u{104DA}']),
+    ('\u{104B3}', &['\u{104DB}']),
+    ('\u{104B4}', &['\u{104DC}']),
+    ('\u{104B5}', &['\u{104DD}']),
+    ('\u{104B6}', &['\u{104DE}']),
+    ('\u{104B7}', &['\u{104DF}']),
+    ('\u{104B8}', &['\u{104E0}']),
+    ('\u{104B9}', &['\u{104E1}']),
+    ('\u{104BA}', &['\u{104E2}']),
+    ('\u{104BB}', &['\u{104E3}']),
+    ('\u{104BC}', &['\u{104E4}']),
+    ('\u{104BD}', &['\u{104E5}']),
+    ('\u{104BE}', &['\u{104E6}']),
+    ('\u{104BF}', &['\u{104E7}']),
+    ('\u{104C0}', &['\u{104E8}']),
+    ('\u{104C1}', &['\u{104E9}']),
+    ('\u{104C2}', &['\u{104EA}']),
+    ('\u{104C3}', &['\u{104EB}']),
+    ('\u{104C4}', &['\u{104EC}']),
+    ('\u{104C5}', &['\u{104ED}']),
+    ('\u{104C6}', &['\u{104EE}']),
+    ('\u{104C7}', &['\u{104EF}']),
+    ('\u{104C8}', &['\u{104F0}']),
+    ('\u{104C9}', &['\u{104F1}']),
+    ('\u{104CA}', &['\u{104F2}']),
+    ('\u{104CB}', &['\u{104F3}']),
+    ('\u{104CC}', &['\u{104F4}']),
+    ('\u{104CD}', &['\u{104F5}']),
+    ('\u{104CE}', &['\u{104F6}']),
+    ('\u{104CF}', &['\u{104F7}']),
+    ('\u{104D0}', &['\u{104F8}']),
+    ('\u{104D1}', &['\u{104F9}']),
+    ('\u{104D2}', &['\u{104FA}']),
+    ('\u{104D3}', &['\u{104FB}']),
+    ('\u{104D8}', &['\u{104B0}']),
+    ('\u{104D9}', &['\u{104B1}']),
+    ('\u{104DA}', &['\u{104B2}']),
+    ('\u{104DB}', &['\u{104B3}']),
+    ('\u{104DC}', &['\u{104B4}']),
+    ('\u{104DD}', &['\u{104B5}']),
+    ('\u{104DE}', &['\u{104B6}']),
+    ('\u{104DF}', &['\u{104B7}']),
+    ('\u{104E0}', &['\u{104B8}']),
+    ('\u{104E1}', &['\u{104B9}']),
+    ('\u{104E2}', &['\u{104BA}']),
+    ('\u{104E3}', &['\u{104BB}']),
+    ('\u{104E4}', &['\u{104BC}']),
+    ('\u{104E5}', &['\u{104BD}']),
+    ('\u{104E6}', &['\u{104BE}']),
+    ('\u{104E7}', &['\u{104BF}']),
+    ('\u{104E8}', &['\u{104C0}']),
+    ('\u{104E9}', &['\u{104C1}']),
+    ('\u{104EA}', &['\u{104C2}']),
+    ('\u{104EB}', &['\u{104C3}']),
+    ('\u{104EC}', &['\u{104C4}']),
+    ('\u{104ED}', &['\u{104C5}']),
+    ('\u{104EE}', &['\u{104C6}']),
+    ('\u{104EF}', &['\u{104C7}']),
+    ('\u{104F0}', &['\u{104C8}']),
+    ('\u{104F1}', &['\u{104C9}']),
+    ('\u{104F2}', &['\u{104CA}']),
+    ('\u{104F3}', &['\u{104CB}']),
+    ('\u{104F4}', &['\u{104CC}']),
+    ('\u{104F5}', &['\u{104CD}']),
+    ('\u{104F6}', &['\u{104CE}']),
+    ('\u{104F7}', &['\u{104CF}']),
+    ('\u{104F8}', &['\u{104D0}']),
+    ('\u{104F9}', &['\u{104D1}']),
+    ('\u{104FA}', &['\u{104D2}']),
+    ('\u{104FB}', &['\u{104D3}']),
+    ('\u{10570}', &['\u{10597}']),
+    ('\u{10571}', &['\u{10598}']),
+    ('\u{10572}', &['\u{10599}']),
+    ('\u{10573}', &['\u{1059A}']),
+    ('\u{10574}', &['\u{1059B}']),
+    ('\u{10575}', &['\u{1059C}']),
+    ('\u{10576}', &['\u{1059D}']),
+    ('\u{10577}', &['\u{1059E}']),
+    ('\u{10578}', &['\u{1059F}']),
+    ('\u{10579}', &['\u{105A0}']),
+    ('\u{1057A}', &['\u{105A1}']),
+    ('\u{1057C}', &['\u{105A3}']),
+    ('\u{1057D}', &['\u{105A4}']),
+    ('\u{1057E}', &['\u{105A5}']),
+    ('\u{1057F}', &['\u{105A6}']),
+    ('\u{10580}', &['\u{105A7}']),
+    ('\u{10581}', &['\u{105A8}']),
+    ('\u{10582}', &['\u{105A9}']),
+    ('\u{10583}', &['\u{105AA}']),
+    ('\u{10584}', &['\u{105AB}']),
+    ('\u{10585}', &['\u{105AC}']),
+    ('\u{10586}', &['\u{105AD}']),
+    ('\u{10587}', &['\u{105AE}']),
+    ('\u{10588}', &['\u{105AF}']),
+    ('\u{10589}', &['\u{105B0}']),
+    ('\u{1058A}', &['\u{105B1}']),
+    ('\u{1058C}', &['\u{105B3}']),
+    ('\u{1058D}', &['\u{105B4}']),
+    ('\u{1058E}', &['\u{105B5}']),
+    ('\u{1058F}', &['\u{105B6}']),
+    ('\u{10590}', &['\u{105B7}']),
+    ('\u{10591}', &['\u{105B8}']),
+    ('\u{10592}', &['\u{105B9}']),
+    ('\u{10594}', &['\u{105BB}']),
+    ('\u{10595}', &['\u{105BC}']),
+    ('\u{10597}', &['\u{10570}']),
+    ('\u{10598}', &['\u{10571}']),
+    ('\u{10599}', &['\u{10572}']),
+    ('\u{1059A}', &['\u{10573}']),
+    ('\u{1059B}', &['\u{10574}']),
+    ('\u{1059C}', &['\u{10575}']),
+    ('\u{1059D}', &['\u{10576}']),
+    ('\u{1059E}', &['\u{10577}']),
+    ('\u{1059F}', &['\u{10578}']),
+    ('\u{105A0}', &['\u{10579}']),
+    ('\u{105A1}', &['\u{1057A}']),
+    ('\u{105A3}', &['\u{1057C}']),
+    ('\u{105A4}', &['\u{1057D}']),
+    ('\u{105A5}', &['\u{1057E}']),
+    ('\u{105A6}', &['\u{1057F}']),
+    ('\u{105A7}', &['\u{10580}']),
+    ('\u{105A8}', &['\u{10581}']),
+    ('\u{105A9}', &['\u{10582}']),
+    ('\u{105AA}', &['\u{10583}']),
+    ('\u{105AB}', &['\u{10584}']),
+    ('\u{105AC}', &['\u{10585}']),
+    ('\u{105AD}', &['\u{10586}']),
+    ('\u{105AE}', &['\u{10587}']),
+    ('\u{105AF}', &['\u{10588}']),
+    ('\u{105B0}', &['\u{10589}']),
+    ('\u{105B1}', &['\u{1058A}']),
+    ('\u{105B3}', &['\u{1058C}']),
+    ('\u{105B4}', &['\u{1058D}']),
+    ('\u{105B5}', &['\u{1058E}']),
+    ('\u{105B6}', &['\u{1058F}']),
+    ('\u{105B7}', &['\u{10590}']),
+    ('\u{105B8}', &['\u{10591}']),
+    ('\u{105B9}', &['\u{10592}']),
+    ('\u{105BB}', &['\u{10594}']),
+    ('\u{105BC}', &['\u{10595}']),
+    ('\u{10C80}', &['\u{10CC0}']),
+    ('\u{10C81}', &['\u{10CC1}']),
+    ('\u{10C82}', &['\u{10CC2}']),
+    ('\u{10C83}', &['\u{10CC3}']),
+    ('\u{10C84}', &['\u{10CC4}']),
+    ('\u{10C85}', &['\u{10CC5}']),
+    ('\u{10C86}', &['\u{10CC6}']),
+    ('\u{10C87}', &['\u{10CC7}']),
+    ('\u{10C88}', &['\u{10CC8}']),
+    ('\u{10C89}', &['\u{10CC9}']),
+    ('\u{10C8A}', &['\u{10CCA}']),
+    ('\u{10C8B}', &['\u{10CCB}']),
+    ('\u{10C8C}', &['\u{10CCC}']),
+    ('\u{10C8D}', &['\u{10CCD}']),
+    ('\u{10C8E}', &['\u{10CCE}']),
+    ('\u{10C8F}', &['\u{10CCF}']),
+    ('\u{10C90}', &['\u{10CD0}']),
+    ('\u{10C91}', &['\u{10CD1}']),
+    ('\u{10C92}', &['\u{10CD2}']),
+    ('\u{10C93}', &['\u{10CD3}']),
+    ('\u{10C94}', &['\u{10CD4}']),
+    ('\u{10C95}', &['\u{10CD5}']),
+    ('\u{10C96}', &['\u{10CD6}']),
+    ('\u{10C97}', &['\u{10CD7}']),
+    ('\u{10C98}', &['\u{10CD8}']),
+    ('\u{10C99}', &['\u{10CD9}']),
+    ('\u{10C9A}', &['\u{10CDA}']),
+    ('\u{10C9B}', &['\u{10CDB}']),
+    ('\u{10C9C}', &['\u{10CDC}']),
+    ('\u{10C9D}', &['\u{10CDD}']),
+    ('\u{10C9E}', &['\u{10CDE}']),
+    ('\u{10C9F}', &['\u{10CDF}']),
+    ('\u{10CA0}', &['\u{10CE0}']),
+    ('\u{10CA1}', &['\u{10CE1}']),
+    ('\u{10CA2}', &['\u{10CE2}']),
+    ('\u{10CA3}', &['\u{10CE3}']),
+    ('\u{10CA4}', &['\u{10CE4}']),
+    ('\u{10CA5}', &['\u{10CE5}']),
+    ('\u{10CA6}', &['\u{10CE6}']),
+    ('\u{10CA7}', &['\u{10CE7}']),
+    ('\u{10CA8}', &['\u{10CE8}']),
+    ('\u{10CA9}', &['\u{10CE9}']),
+    ('\u{10CAA}', &['\u{10CEA}']),
+    ('\u{10CAB}', &['\u{10CEB}']),
+    ('\u{10CAC}', &['\u{10CEC}']),
+    ('\u{10CAD}', &['\u{10CED}']),
+    ('\u{10CAE}', &['\u{10CEE}']),
+    ('\u{10CAF}', &['\u{10CEF}']),
+    ('\u{10CB0}', &['\u{10CF0}']),
+    ('\u{10CB1}', &['\u{10CF1}']),
+    ('\u{10CB2}', &['\u{10CF2}']),
+    ('\u{10CC0}', &['\u{10C80}']),
+    ('\u{10CC1}', &['\u{10C81}']),
+    ('\u{10CC2}', &['\u{10C82}']),
+    ('\u{10CC3}', &['\u{10C83}']),
+    ('\u{10CC4}', &['\u{10C84}']),
+    ('\u{10CC5}', &['\u{10C85}']),
+    ('\u{10CC6}', &['\u{10C86}']),
+    ('\u{10CC7}', &['\u{10C87}']),
+    ('\u{10CC8}', &['\u{10C88}']),
+    ('\u{10CC9}', &['\u{10C89}']),
+    ('\u{10CCA}', &['\u{10C8A}']),
+    ('\u{10CCB}', &['\u{10C8B}']),
+    ('\u{10CCC}', &['\u{10C8C}']),
+    ('\u{10CCD}', &['\u{10C8D}']),
+    ('\u{10CCE}', &['\u{10C8E}']),
+    ('\u{10CCF}', &['\u{10C8F}']),
+    ('\u{10CD0}', &['\u{10C90}']),
+    ('\u{10CD1}', &['\u{10C91}']),
+    ('\u{10CD2}', &['\u{10C92}']),
+    ('\u{10CD3}', &['\u{10C93}']),
+    ('\u{10CD4}', &['\u{10C94}']),
+    ('\u{10CD5}', &['\u{10C95}']),
+    ('\u{10CD6}', &['\u{10C96}']),
+    ('\u{10CD7}', &['\u{10C97}']),
+    ('\u{10CD8}', &['\u{10C98}']),
+    ('\u{10CD9}', &['\u{10C99}']),
+    ('\u{10CDA}', &['\u{10C9A}']),
+    ('\u{10CDB}', &['\u{10C9B}']),
+    ('\u{10CDC}', &['\u{10C9C}']),
+    ('\u{10CDD}', &['\u{10C9D}']),
+    ('\u{10CDE}', &['\u{10C9E}']),
+    ('\u{10CDF}', &['\u{10C9F}']),
+    ('\u{10CE0}', &['\u{10CA0}']),
+    ('\u{10CE1}', &['\u{10CA1}']),
+    ('\u{10CE2}', &['\u{10CA2}']),
+    ('\u{10CE3}', &['\u{10CA3}']),
+    ('\u{10CE4}', &['\u{10CA4}']),
+    ('\u{10CE5}', &['\u{10CA5}']),
+    ('\u{10CE6}', &['\u{10CA6}']),
+    ('\u{10CE7}', &['\u{10CA7}']),
+    ('\u{10CE8}', &['\u{10CA8}']),
+    ('\u{10CE9}', &['\u{10CA9}']),
+    ('\u{10CEA}', &['\u{10CAA}']),
+    ('\u{10CEB}', &['\u{10CAB}']),
+    ('\u{10CEC}', &['\u{10CAC}']),
+    ('\u{10CED}', &['\u{10CAD}']),
+    ('\u{10CEE}', &['\u{10CAE}']),
+    ('\u{10CEF}', &['\u{10CAF}']),
+    ('\u{10CF0}', &['\u{10CB0}']),
+    ('\u{10CF1}', &['\u{10CB1}']),
+    ('\u{10CF2}', &['\u{10CB2}']),
+    ('\u{10D50}', &['\u{10D70}']),
+    ('\u{10D51}', &['\u{10D71}']),
+    ('\u{10D52}', &['\u{10D72}']),
+    ('\u{10D53}', &['\u{10D73}']),
+    ('\u{10D54}', &['\u{10D74}']),
+    ('\u{10D55}', &['\u{10D75}']),
+    ('\u{10D56}', &['\u{10D76}']),
+    ('\u{10D57}', &['\u{10D77}']),
+    ('\u{10D58}', &['\u{10D78}']),
+    ('\u{10D59}', &['\u{10D79}']),
+    ('\u{10D5A}', &['\u{10D7A}']),
+    ('\u{10D5B}', &['\u{10D7B}']),
+    ('\u{10D5C}', &['\u{10D7C}']),
+    ('\u{10D5D}', &['\u{10D7D}']),
+    ('\u{10D5E}', &['\u{10D7E}']),
+    ('\u{10D5F}', &['\u{10D7F}']),
+    ('\u{10D60}', &['\u{10D80}']),
+    ('\u{10D61}', &['\u{10D81}']),
+    ('\u{10D62}', &['\u{10D82}']),
+    ('\u{10D63}', &['\u{10D83}']),
+    ('\u{10D64}', &['\u{10D84}']),
+    ('\u{10D65}', &['\u{10D85}']),
+    ('\u{10D70}', &['\u{10D50}']),
+    ('\u{10D71}', &['\u{10D51}']),
+    ('\u{10D72}', &['\u{10D52}']),
+    ('\u{10D73}', &['\u{10D53}']),
+    ('\u{10D74}', &['\u{10D54}']),
+    ('\u{10D75}', &['\u{10D55}']),
+    ('\u{10D76}', &['\u{10D56}']),
+    ('\u{10D77}', &['\u{10D57}']),
+    ('\u{10D78}', &['\u{10D58}']),
+    ('\u{10D79}', &['\u{10D59}']),
+    ('\u{10D7A}', &['\u{10D5A}']),
+    ('\u{10D7B}', &['\u{10D5B}']),
+    ('\u{10D7C}', &['\u{10D5C}']),
+    ('\u{10D7D}', &['\u{10D5D}']),
+    ('\u{10D7E}', &['\u{10D5E}']),
+    ('\u{10D7F}', &['\u{10D5F}']),
+    ('\u{10D80}', &['\u{10D60}']),
+    ('\u{10D81}', &['\u{10D61}']),
+    ('\u{10D82}', &['\u{10D62}']),
+    ('\u{10D83}', &['\u{10D63}']),
+    ('\u{10D84}', &['\u{10D64}']),
+    ('\u{10D85}', &['\u{10D65}']),
+    ('\u{118A0}', &['\u{118C0}']),
+    ('\u{118A1}', &['\u{118C1}']),
+    ('\u{118A2}', &['\u{118C2}']),
+    ('\u{118A3}', &['\u{118C3}']),
+    ('\u{118A4}', &['\u{118C4}']),
+    ('\u{118A5}', &['\u{118C5}']),
+    ('\u{118A6}', &['\u{118C6}']),
+    ('\u{118A7}', &['\u{118C7}']),
+    ('\u{118A8}', &['\u{118C8}']),
+    ('\u{118A9}', &['\u{118C9}']),
+    ('\u{118AA}', &['\u{118CA}']),
+    ('\u{118AB}', &['\u{118CB}']),
+    ('\u{118AC}', &['\u{118CC}']),
+    ('\u{118AD}', &['\u{118CD}']),
+    ('\u{118AE}', &['\u{118CE}']),
+    ('\u{118AF}', &['\u{118CF}']),
+    ('\u{118B0}', &['\u{118D0}']),
+    ('\u{118B1}', &['\u{118D1}']),
+    ('\u{118B2}', &['\u{118D2}']),
+    ('\u{118B3}', &['\u{118D3}']),
+    ('\u{118B4}', &['\u{118D4}']),
+    ('\u{118B5}', &['\u{118D5}']),
+    ('\u{118B6}', &['\u{118D6}']),
+    ('\u{118B7}', &['\u{118D7}']),
+    ('\u{118B8}', &['\u{118D8}']),
+    ('\u{118B9}', &['\u{118D9}']),
+    ('\u{118BA}', &['\u{118DA}']),
+    ('\u{118BB}', &['\u{118DB}']),
+    ('\u{118BC}', &['\u{118DC}']),
+    ('\u{118BD}', &['\u{118DD}']),
+    ('\u{118BE}', &['\u{118DE}']),
+    ('\u{118BF}', &['\u{118DF}']),
+    ('\u{118C0}', &['\u{118A0}']),
+    ('\u{118C1}', &['\u{118A1}']),
+    ('\u{118C2}', &['\u{118A2}']),
+    ('\u{118C3}', &['\u{118A3}']),
+    ('\u{118C4}', &['\u{118A4}']),
+    ('\u{118C5}', &['\u{118A5}']),
+    ('\u{118C6}', &['\u{118A6}']),
+    ('\u{118C7}', &['\u{118A7}']),
+    ('\u{118C8}', &['\u{118A8}']),
+    ('\u{118C9}', &['\u{118A9}']),
+    ('\u{118CA}', &['\u{118AA}']),
+    ('\u{118CB}', &['\u{118AB}']),
+    ('\u{118CC}', &['\u{118AC}']),
+    ('\u{118CD}', &['\u{118AD}']),
+    ('\u{118CE}', &['\u{118AE}']),
+    ('\u{118CF}', &['\u{118AF}']),
+    ('\u{118D0}', &['\u{118B0}']),
+    ('\u{118D1}', &['\u{118B1}']),
+    ('\u{118D2}', &['\u{118B2}']),
+    ('\u{118D3}', &['\u{118B3}']),
+    ('\u{118D4}', &['\u{118B4}']),
+    ('\u{118D5}', &['\u{118B5}']),
+    ('\u{118D6}', &['\u{118B6}']),
+    ('\u{118D7}', &['\u{118B7}']),
+    ('\u{118D8}', &['\u{118B8}']),
+    ('\u{118D9}', &['\u{118B9}']),
+    ('\u{118DA}', &['\u{118BA}']),
+    ('\u{118DB}', &['\u{118BB}']),
+    ('\u{118DC}', &['\u{118BC}']),
+    ('\u{118DD}', &['\u{118BD}']),
+    ('\u{118DE}', &['\u{118BE}']),
+    ('\u{118DF}', &['\u{118BF}']),
+    ('\u{16E40}', &['\u{16E60}']),
+    ('\u{16E41}', &['\u{16E61}']),
+    ('\u{16E42}', &['\u{16E62}']),
+    ('\u{16E43}', &['\u{16E63}']),
+    ('\u{16E44}', &['\u{16E64}']),
+    ('\u{16E45}', &['\u{16E65}']),
+    ('\u{16E46}', &['\u{16E66}']),
+    ('\u{16E47}', &['\u{16E67}']),
+    ('\u{16E48}', &['\u{16E68}']),
+    ('\u{16E49}', &['\u{16E69}']),
+    ('\u{16E4A}', &['\u{16E6A}']),
+    ('\u{16E4B}', &['\u{16E6B}']),
+    ('\u{16E4C}', &['\u{16E6C}']),
+    ('\u{16E4D}', &['\u{16E6D}']),
+    ('\u{16E4E}', &['\u{16E6E}']),
+    ('\u{16E4F}', &['\u{16E6F}']),
+    ('\u{16E50}', &['\u{16E70}']),
+    ('\u{16E51}', &['\u{16E71}']),
+    ('\u{16E52}', &['\u{16E72}']),
+    ('\u{16E53}', &['\u{16E73}']),
+    ('\u{16E54}', &['\u{16E74}']),
+    ('\u{16E55}', &['\u{16E75}']),
+    ('\u{16E56}', &['\u{16E76}']),
+    ('\u{16E57}', &['\u{16E77}']),
+    ('\u{16E58}', &['\u{16E78}']),
+    ('\u{16E59}', &['\u{16E79}']),
+    ('\u{16E5A}', &['\u{16E7A}']),
+    ('\u{16E5B}', &['\u{16E7B}']),
+    ('\u{16E5C}', &['\u{16E7C}']),
+    ('\u{16E5D}', &['\u{16E7D}']),
+    ('\u{16E5E}', &['\u{16E7E}']),
+    ('\u{16E5F}', &['\u{16E7F}']),
+    ('\u{16E60}', &['\u{16E40}']),
+    ('\u{16E61}', &['\u{16E41}']),
+    ('\u{16E62}', &['\u{16E42}']),
+    ('\u{16E63}', &['\u{16E43}']),
+    ('\u{16E64}', &['\u{16E44}']),
+    ('\u{16E65}', &['\u{16E45}']),
+    ('\u{16E66}', &['\u{16E46}']),
+    ('\u{16E67}', &['\u{16E47}']),
+    ('\u{16E68}', &['\u{16E48}']),
+    ('\u{16E69}', &['\u{16E49}']),
+    ('\u{16E6A}', &['\u{16E4A}']),
+    ('\u{16E6B}', &['\u{16E4B}']),
+    ('\u{16E6C}', &['\u{16E4C}']),
+    ('\u{16E6D}', &['\u{16E4D}']),
+    ('\u{16E6E}', &['\u{16E4E}']),
+    ('\u{16E6F}', &['\u{16E4F}']),
+    ('\u{16E70}', &['\u{16E50}']),
+    ('\u{16E71}', &['\u{16E51}']),
+    ('\u{16E72}', &['\u{16E52}']),
+    ('\u{16E73}', &['\u{16E53}']),
+    ('\u{16E74}', &['\u{16E54}']),
+    ('\u{16E75}', &['\u{16E55}']),
+    ('\u{16E76}', &['\u{16E56}']),
+    ('\u{16E77}', &['\u{16E57}']),
+    ('\u{16E78}', &['\u{16E58}']),
+    ('\u{16E79}', &['\u{16E59}']),
+    ('\u{16E7A}', &['\u{16E5A}']),
+    ('\u{16E7B}', &['\u{16E5B}']),
+    ('\u{16E7C}', &['\u{16E5C}']),
+    ('\u{16E7D}', &['\u{16E5D}']),
+    ('\u{16E7E}', &['\u{16E5E}']),
+    ('\u{16E7F}', &['\u{16E5F}']),
+    ('\u{16EA0}', &['\u{16EBB}']),
+    ('\u{16EA1}', &['\u{16EBC}']),
+    ('\u{16EA2}', &['\u{16EBD}']),
+    ('\u{16EA3}', &['\u{16EBE}']),
+    ('\u{16EA4}', &['\u{16EBF}']),
+    ('\u{16EA5}', &['\u{16EC0}']),
+    ('\u{16EA6}', &['\u{16EC1}']),
+    ('\u{16EA7}', &['\u{16EC2}']),
+    ('\u{16EA8}', &['\u{16EC3}']),
+    ('\u{16EA9}', &['\u{16EC4}']),
+    ('\u{16EAA}', &['\u{16EC5}']),
+    ('\u{16EAB}', &['\u{16EC6}']),
+    ('\u{16EAC}', &['\u{16EC7}']),
+    ('\u{16EAD}', &['\u{16EC8}']),
+    ('\u{16EAE}', &['\u{16EC9}']),
+    ('\u{16EAF}', &['\u{16ECA}']),
+    ('\u{16EB0}', &['\u{16ECB}']),
+    ('\u{16EB1}', &['\u{16ECC}']),
+    ('\u{16EB2}', &['\u{16ECD}']),
+    ('\u{16EB3}', &['\u{16ECE}']),
+    ('\u{16EB4}', &['\u{16ECF}']),
+    ('\u{16EB5}', &['\u{16ED0}']),
+    ('\u{16EB6}', &['\u{16ED1}']),
+    ('\u{16EB7}', &['\u{16ED2}']),
+    ('\u{16EB8}', &['\u{16ED3}']),
+    ('\u{16EBB}', &['\u{16EA0}']),
+    ('\u{16EBC}', &['\u{16EA1}']),
+    ('\u{16EBD}', &['\u{16EA2}']),
+    ('\u{16EBE}', &['\u{16EA3}']),
+    ('\u{16EBF}', &['\u{16EA4}']),
+    ('\u{16EC0}', &['\u{16EA5}']),
+    ('\u{16EC1}', &['\u{16EA6}']),
+    ('\u{16EC2}', &['\u{16EA7}']),
+    ('\u{16EC3}', &['\u{16EA8}']),
+    ('\u{16EC4}', &['\u{16EA9}']),
+    ('\u{16EC5}', &['\u{16EAA}']),
+    ('\u{16EC6}', &['\u{16EAB}']),
+    ('\u{16EC7}', &['\u{16EAC}']),
+    ('\u{16EC8}', &['\u{16EAD}']),
+    ('\u{16EC9}', &['\u{16EAE}']),
+    ('\u{16ECA}', &['\u{16EAF}']),
+    ('\u{16ECB}', &['\u{16EB0}']),
+    ('\u{16ECC}', &['\u{16EB1}']),
+    ('\u{16ECD}', &['\u{16EB2}']),
+    ('\u{16ECE}', &['\u{16EB3}']),
+    ('\u{16ECF}', &['\u{16EB4}']),
+    ('\u{16ED0}', &['\u{16EB5}']),
+    ('\u{16ED1}', &['\u{16EB6}']),
+    ('\u{16ED2}', &['\u{16EB7}']),
+    ('\u{16ED3}', &['\u{16EB8}']),
+    ('\u{1E900}', &['\u{1E922}']),
+    ('\u{1E901}', &['\u{1E923}']),
+    ('\u{1E902}', &['\u{1E924}']),
+    ('\u{1E903}', &['\u{1E925}']),
+    ('\u{1E904}', &['\u{1E926}']),
+    ('\u{1E905}', &['\u{1E927}']),
+    ('\u{1E906}', &['\u{1E928}']),
+    ('\u{1E907}', &['\u{1E929}']),
+    ('\u{1E908}', &['\u{1E92A}']),
+    ('\u{1E909}', &['\u{1E92B}']),
+    ('\u{1E90A}', &['\u{1E92C}']),
+    ('\u{1E90B}', &['\u{1E92D}']),
+    ('\u{1E90C}', &['\u{1E92E}']),
+    ('\u{1E90D}', &['\u{1E92F}']),
+    ('\u{1E90E}', &['\u{1E930}']),
+    ('\u{1E90F}', &['\u{1E931}']),
+    ('\u{1E910}', &['\u{1E932}']),
+    ('\u{1E911}', &['\u{1E933}']),
+    ('\u{1E912}', &['\u{1E934}']),
+    ('\u{1E913}', &['\u{1E935}']),
+    ('\u{1E914}', &['\u{1E936}']),
+    ('\u{1E915}', &['\u{1E937}']),
+    ('\u{1E916}', &['\u{1E938}']),
+    ('\u{1E917}', &['\u{1E939}']),
+    ('\u{1E918}', &['\u{1E93A}']),
+    ('\u{1E919}', &['\u{1E93B}']),
+    ('\u{1E91A}', &['\u{1E93C}']),
+    ('\u{1E91B}', &['\u{1E93D}']),
+    ('\u{1E91C}', &['\u{1E93E}']),
+    ('\u{1E91D}', &['\u{1E93F}']),
+    ('\u{1E91E}', &['\u{1E940}']),
+    ('\u{1E91F}', &['\u{1E941}']),
+    ('\u{1E920}', &['\u{1E942}']),
+    ('\u{1E921}', &['\u{1E943}']),
+    ('\u{1E922}', &['\u{1E900}']),
+    ('\u{1E923}', &['\u{1E901}']),
+    ('\u{1E924}', &['\u{1E902}']),
+    ('\u{1E925}', &['\u{1E903}']),
+    ('\u{1E926}', &['\u{1E904}']),
+    ('\u{1E927}', &['\u{1E905}']),
+    ('\u{1E928}', &['\u{1E906}']),
+    ('\u{1E929}', &['\u{1E907}']),
+    ('\u{1E92A}', &['\u{1E908}']),
+    ('\u{1E92B}', &['\u{1E909}']),
+    ('\u{1E92C}', &['\u{1E90A}']),
+    ('\u{1E92D}', &['\u{1E90B}']),
+    ('\u{1E92E}', &['\u{1E90C}']),
+    ('\u{1E92F}', &['\u{1E90D}']),
+    ('\u{1E930}', &['\u{1E90E}']),
+    ('\u{1E931}', &['\u{1E90F}']),
+    ('\u{1E932}', &['\u{1E910}']),
+    ('\u{1E933}', &['\u{1E911}']),
+    ('\u{1E934}', &['\u{1E912}']),
+    ('\u{1E935}', &['\u{1E913}']),
+    ('\u{1E936}', &['\u{1E914}']),
+    ('\u{1E937}', &['\u{1E915}']),
+    ('\u{1E938}', &['\u{1E916}']),
+    ('\u{1E939}', &['\u{1E917}']),
+    ('\u{1E93A}', &['\u{1E918}']),
+    ('\u{1E93B}', &['\u{1E919}']),
+    ('\u{1E93C}', &['\u{1E91A}']),
+    ('\u{1E93D}', &['\u{1E91B}']),
+    ('\u{1E93E}', &['\u{1E91C}']),
+    ('\u{1E93F}', &['\u{1E91D}']),
+    ('\u{1E940}', &['\u{1E91E}']),
+    ('\u{1E941}', &['\u{1E91F}']),
+    ('\u{1E942}', &['\u{1E920}']),
+    ('\u{1E943}', &['\u{1E921}']),
+];
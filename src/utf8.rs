@@ -0,0 +1,139 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::str;
+
+/// A unit of a haystack, which the engines consume one at a time.
+///
+/// A haystack is read as a sequence of chars in UTF-8, where every byte that
+/// is not part of a valid encoding of a char is a unit by itself. Such bytes
+/// are always at least 0x80 and can only be matched with `(?-u)`. A haystack
+/// that is a `&str` consists only of chars.
+///
+/// A program compiled to UTF-8 (see [`Compiler::utf8`](crate::Compiler::utf8))
+/// instead reads every byte as a unit by itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Char(char),
+    Byte(u8),
+}
+
+impl Unit {
+    /// Decodes the unit at the start of the bytes and returns it with its
+    /// length, or None if the bytes are empty.
+    #[inline]
+    pub fn decode(bytes: &[u8]) -> Option<(Unit, usize)> {
+        let &b = bytes.first()?;
+        if b < 0x80 {
+            return Some((Unit::Char(b as char), 1));
+        }
+        let len = match b {
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return Some((Unit::Byte(b), 1)),
+        };
+        match bytes.get(..len).and_then(|s| str::from_utf8(s).ok()) {
+            Some(s) => Some((Unit::Char(s.chars().next().unwrap()), len)),
+            None => Some((Unit::Byte(b), 1)),
+        }
+    }
+
+    /// Decodes the unit at the end of the bytes and returns it with its
+    /// length, or None if the bytes are empty.
+    #[inline]
+    pub fn decode_last(bytes: &[u8]) -> Option<(Unit, usize)> {
+        let &last = bytes.last()?;
+        if last < 0x80 {
+            return Some((Unit::Char(last as char), 1));
+        }
+        // A char ending here starts at the nearest byte that is not a
+        // continuation byte.
+        for len in 1..=bytes.len().min(4) {
+            let start = bytes.len() - len;
+            if bytes[start] & 0xC0 != 0x80 {
+                if let Some((unit @ Unit::Char(_), n)) = Unit::decode(&bytes[start..]) {
+                    if n == len {
+                        return Some((unit, len));
+                    }
+                }
+                break;
+            }
+        }
+        Some((Unit::Byte(last), 1))
+    }
+
+    /// Returns the char, or None if the unit is a byte.
+    #[inline]
+    pub fn as_char(self) -> Option<char> {
+        match self {
+            Unit::Char(ch) => Some(ch),
+            Unit::Byte(_) => None,
+        }
+    }
+
+    /// Returns the number of bytes of the unit in the haystack.
+    #[inline]
+    pub fn byte_len(self) -> usize {
+        match self {
+            Unit::Char(ch) => ch.len_utf8(),
+            Unit::Byte(_) => 1,
+        }
+    }
+}
+
+impl From<char> for Unit {
+    #[inline]
+    fn from(ch: char) -> Self {
+        Unit::Char(ch)
+    }
+}
+
+// Appends the UTF-8 encodings of the chars from lo to hi as sequences of byte
+// ranges, such that every char in the range is matched by exactly one
+// sequence. The sequences are in order of the chars they match.
+pub(crate) fn sequences(lo: char, hi: char, out: &mut Vec<Vec<(u8, u8)>>) {
+    // Ranges are split in halves, with the upper half pushed first, so they
+    // are popped in order.
+    let mut stack = vec![(lo as u32, hi as u32)];
+    'ranges: while let Some((lo, hi)) = stack.pop() {
+        // Split around the surrogates, which have no encoding.
+        if lo < 0xD800 && hi > 0xDFFF {
+            stack.push((0xE000, hi));
+            stack.push((lo, 0xD7FF));
+            continue;
+        }
+        // Split where the length of the encoding changes.
+        for max in [0x7F, 0x7FF, 0xFFFF] {
+            if lo <= max && max < hi {
+                stack.push((max + 1, hi));
+                stack.push((lo, max));
+                continue 'ranges;
+            }
+        }
+        // Split until each continuation byte spans a whole range or is
+        // fixed by the leading bytes.
+        let (lo_ch, hi_ch) = (char::from_u32(lo).unwrap(), char::from_u32(hi).unwrap());
+        for i in 1..lo_ch.len_utf8() {
+            let max = (1 << (6 * i)) - 1;
+            if lo & !max != hi & !max {
+                if lo & max != 0 {
+                    stack.push(((lo | max) + 1, hi));
+                    stack.push((lo, lo | max));
+                    continue 'ranges;
+                }
+                if hi & max != max {
+                    stack.push((hi & !max, hi));
+                    stack.push((lo, (hi & !max) - 1));
+                    continue 'ranges;
+                }
+            }
+        }
+        let (mut lo_buf, mut hi_buf) = ([0; 4], [0; 4]);
+        let lo = lo_ch.encode_utf8(&mut lo_buf).as_bytes();
+        let hi = hi_ch.encode_utf8(&mut hi_buf).as_bytes();
+        out.push(lo.iter().zip(hi).map(|(&lo, &hi)| (lo, hi)).collect());
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Assertion, Regex, Regexp, ENGINES};
+
+#[test]
+fn anchors_parse_to_assertions() {
+    for (pattern, assertion) in [
+        ("^", Assertion::StartText),
+        ("$", Assertion::EndText),
+        (r"\A", Assertion::StartText),
+        (r"\z", Assertion::EndText),
+        ("(?m)^", Assertion::StartLine),
+        ("(?m)$", Assertion::EndLine),
+    ] {
+        assert_eq!(
+            *Regexp::parse(pattern).unwrap(),
+            Regexp::Assert(assertion),
+            "{pattern}"
+        );
+    }
+}
+
+#[test]
+fn assertions_at_offsets() {
+    let s = b"ab\ncd";
+    assert!(Assertion::StartText.matches(s, 0));
+    assert!(!Assertion::StartText.matches(s, 3));
+    assert!(Assertion::EndText.matches(s, 5));
+    assert!(!Assertion::EndText.matches(s, 2));
+    assert!(Assertion::StartLine.matches(s, 3));
+    assert!(!Assertion::StartLine.matches(s, 2));
+    assert!(Assertion::EndLine.matches(s, 2));
+    assert!(!Assertion::EndLine.matches(s, 3));
+}
+
+#[test]
+fn engines_honor_anchors() {
+    for engine in ENGINES {
+        let re = Regex::new("^ab").unwrap().with_engine(engine);
+        assert!(re.is_match("abc"), "{engine:?}");
+        assert!(!re.is_match("cab"), "{engine:?}");
+        let re = Regex::new("ab$").unwrap().with_engine(engine);
+        assert!(re.is_match("cab"), "{engine:?}");
+        assert!(!re.is_match("abc"), "{engine:?}");
+        assert!(!re.is_match("ab\n"), "{engine:?}");
+        let re = Regex::new(r"\Aa+\z").unwrap().with_engine(engine);
+        assert!(re.is_match("aaa"), "{engine:?}");
+        assert!(!re.is_match("aab"), "{engine:?}");
+        let re = Regex::new("a|b$").unwrap().with_engine(engine);
+        assert_eq!(re.find("xbab").unwrap().start(), 2, "{engine:?}");
+    }
+}
+
+#[test]
+fn multi_line_anchors() {
+    for engine in ENGINES {
+        let re = Regex::new("(?m)^[a-z]+$").unwrap().with_engine(engine);
+        let lines: Vec<_> = re.find_iter("ab\n12\ncd").map(|m| m.as_str()).collect();
+        assert_eq!(lines, ["ab", "cd"], "{engine:?}");
+        let re = Regex::new("^[a-z]+$").unwrap().with_engine(engine);
+        assert!(!re.is_match("ab\ncd"), "{engine:?}");
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{
+    Backtrack, BacktrackError, Engine, Input, Pike, Prog, Regexp, Sub, DEFAULT_VISITED_CAPACITY, VM,
+};
+
+fn compile(pattern: &str) -> Prog {
+    Regexp::Paren(0, None, Regexp::parse(pattern).unwrap())
+        .compile()
+        .unwrap()
+}
+
+#[test]
+fn agrees_with_pike() {
+    for (pattern, hay) in [
+        (r"(a*)*b", "aaab"),
+        (r"(a|ab)(c|bcd)(d*)", "abcd"),
+        (r"(a*)+$", "aab"),
+        (r"(a+|b+)*c", "abab c"),
+        (r"x(\w*?)(\d+)", "yxab12"),
+        (r"(?:(a)|b)*", "abab"),
+    ] {
+        let prog = compile(pattern);
+        let input = Input::new(hay);
+        let mut want = Sub::new(prog.nsub());
+        let matched = Pike.search(&prog, &input, &mut want);
+        let mut sub = Sub::new(prog.nsub());
+        let mut vm = VM::with_input(&prog, &input, false);
+        assert_eq!(vm.match_backtrack(&mut sub), Ok(matched), "{pattern}");
+        assert_eq!(sub, want, "{pattern}");
+    }
+}
+
+#[test]
+fn nested_repetition_does_not_overflow() {
+    let prog = compile(r"(a*)*b");
+    let hay = "a".repeat(5000);
+    let mut sub = Sub::new(prog.nsub());
+    let mut vm = VM::with_input(&prog, &Input::new(&hay), false);
+    assert_eq!(vm.match_backtrack(&mut sub), Ok(false));
+    let hay = hay + "b";
+    let mut vm = VM::with_input(&prog, &Input::new(&hay), false);
+    assert_eq!(vm.match_backtrack(&mut sub), Ok(true));
+    assert_eq!(sub.group(1), Some((0, 5000)));
+}
+
+#[test]
+fn haystack_too_large() {
+    let prog = compile(r"(a*)*b");
+    let capacity = 100;
+    let limit = capacity * 8 / prog.insts().len() - 1;
+    let mut sub = Sub::new(prog.nsub());
+    let hay = "a".repeat(limit);
+    let mut vm = VM::with_input(&prog, &Input::new(&hay), false);
+    assert_eq!(
+        vm.match_backtrack_with_capacity(&mut sub, capacity),
+        Ok(false)
+    );
+    let hay = "a".repeat(limit + 1);
+    let mut vm = VM::with_input(&prog, &Input::new(&hay), false);
+    let err = vm
+        .match_backtrack_with_capacity(&mut sub, capacity)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        BacktrackError::HaystackTooLarge {
+            len: limit + 1,
+            limit
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "span of {} bytes exceeds backtracking limit of {limit} bytes",
+            limit + 1
+        )
+    );
+}
+
+#[test]
+fn engine_falls_back_to_pike() {
+    let prog = compile(r"(a*)*(b)");
+    let len = DEFAULT_VISITED_CAPACITY * 8 / prog.insts().len();
+    let hay = "a".repeat(len) + "b";
+    let mut vm = VM::with_input(&prog, &Input::new(&hay), false);
+    let mut sub = Sub::new(prog.nsub());
+    assert!(vm.match_backtrack(&mut sub).is_err());
+    assert!(Backtrack.search(&prog, &Input::new(&hay), &mut sub));
+    assert_eq!(sub.group(2), Some((len, len + 1)));
+}
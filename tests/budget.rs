@@ -0,0 +1,156 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use re1::{
+    bytes, Budget, Dfa, Engine, GaveUp, Input, LazyDfa, Meta, OnePass, Prog, RecursionError,
+    RecursiveConfig, Regex, Regexp, Sub, ENGINES, VM,
+};
+
+fn compile(pattern: &str) -> Prog {
+    Regexp::Paren(0, None, Regexp::parse(pattern).unwrap())
+        .compile()
+        .unwrap()
+}
+
+// Checks that every engine gives up with the budget on a search that takes
+// many steps, and leaves sub reset.
+fn assert_every_engine_gives_up(budget: &Budget, want: GaveUp) {
+    let prog = compile(r"(a|b)*c");
+    assert!(prog.is_one_pass());
+    let dfa = Dfa::new(&prog).unwrap();
+    let (meta, lazy) = (Meta::new(), LazyDfa::new());
+    let mut engines: Vec<&dyn Engine> = ENGINES.to_vec();
+    engines.extend([&meta as &dyn Engine, &lazy, &OnePass, &dfa]);
+    let hay = "ab".repeat(5000);
+    let input = Input::new(&hay).budget(budget.clone());
+    for engine in engines {
+        for nsub in [0, 2, prog.nsub()] {
+            let mut sub = Sub::new(nsub);
+            assert_eq!(
+                engine.try_search(&prog, &input, &mut sub),
+                Err(want),
+                "{} {nsub}",
+                engine.name()
+            );
+            assert_eq!(sub, Sub::new(nsub), "{}", engine.name());
+        }
+    }
+}
+
+#[test]
+fn step_limit() {
+    assert_every_engine_gives_up(
+        Budget::new().step_limit(100),
+        GaveUp::StepLimit { limit: 100 },
+    );
+}
+
+#[test]
+fn deadline() {
+    let past = Instant::now() - Duration::from_millis(1);
+    assert_every_engine_gives_up(Budget::new().deadline(past), GaveUp::Deadline);
+}
+
+#[test]
+fn cancel_flag() {
+    let flag = Arc::new(AtomicBool::new(true));
+    assert_every_engine_gives_up(Budget::new().cancel_flag(flag), GaveUp::Cancelled);
+}
+
+#[test]
+fn searches_within_the_budget_finish() {
+    let prog = compile(r"(a|b)*c");
+    let hay = "ab".repeat(20) + "c";
+    let input = Input::new(&hay).budget(Budget::new().step_limit(1_000_000).clone());
+    for engine in ENGINES {
+        let mut sub = Sub::new(prog.nsub());
+        assert_eq!(
+            engine.try_search(&prog, &input, &mut sub),
+            Ok(true),
+            "{}",
+            engine.name()
+        );
+        assert_eq!(sub.group(0), Some((0, 41)), "{}", engine.name());
+    }
+}
+
+#[test]
+fn regex_try_search() {
+    let re = Regex::new(r"(\d+)").unwrap();
+    let hay = "x".repeat(10_000) + "42";
+    let limited = Input::new(&hay).budget(Budget::new().step_limit(100).clone());
+    let gave_up = GaveUp::StepLimit { limit: 100 };
+    assert_eq!(re.try_search(&limited).unwrap_err(), gave_up);
+    assert_eq!(re.try_search_captures(&limited).unwrap_err(), gave_up);
+    let m = re.try_search(&Input::new(&hay)).unwrap().unwrap();
+    assert_eq!(m.as_str(), "42");
+    let caps = re.try_search_captures(&Input::new(&hay)).unwrap().unwrap();
+    assert_eq!(&caps[1], "42");
+
+    let re = bytes::Regex::new(r"(\d+)").unwrap();
+    assert_eq!(re.try_search(&limited).unwrap_err(), gave_up);
+    assert_eq!(re.try_search_captures(&limited).unwrap_err(), gave_up);
+    let m = re.try_search(&Input::new(&hay)).unwrap().unwrap();
+    assert_eq!(m.as_bytes(), b"42");
+}
+
+#[test]
+#[should_panic = "search exceeds step limit of 10; use try_search to handle a budget"]
+fn search_panics_when_it_gives_up() {
+    let re = Regex::new(r"\d").unwrap();
+    let _ = re.search(
+        &Input::new("abcdefghijklmnopqrstuvwxyz").budget(Budget::new().step_limit(10).clone()),
+    );
+}
+
+#[test]
+fn cancel_from_another_thread() {
+    // Without memoization, this takes exponential time.
+    let prog = compile(r"(a|aa)*c");
+    let hay = "a".repeat(200);
+    let flag = Arc::new(AtomicBool::new(false));
+    let canceller = {
+        let flag = flag.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            flag.store(true, Ordering::Relaxed);
+        })
+    };
+    let mut vm = VM::with_input(
+        &prog,
+        &Input::new(&hay).budget(Budget::new().cancel_flag(flag).clone()),
+        false,
+    );
+    let mut config = RecursiveConfig::new();
+    config.depth_limit(usize::MAX);
+    let mut sub = Sub::new(prog.nsub());
+    assert_eq!(
+        vm.match_recursive_stack(&mut sub, &config),
+        Err(RecursionError::GaveUp(GaveUp::Cancelled))
+    );
+    canceller.join().unwrap();
+}
+
+#[test]
+fn vm_budget() {
+    let prog = compile(r"(a|b)*c");
+    let hay = "ab".repeat(1000);
+    let mut vm = VM::with_input(&prog, &Input::new(&hay), false);
+    vm.budget(Budget::new().step_limit(10).clone());
+    let mut sub = Sub::new(prog.nsub());
+    assert_eq!(
+        vm.match_pikevm(&mut sub),
+        Err(GaveUp::StepLimit { limit: 10 })
+    );
+    assert_eq!(
+        GaveUp::StepLimit { limit: 10 }.to_string(),
+        "search exceeds step limit of 10"
+    );
+}
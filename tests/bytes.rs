@@ -0,0 +1,131 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{
+    bytes, Anchored, Dfa, Engine, Input, LazyDfa, Meta, OnePass, Pike, Regex, Regexp, Sub, ENGINES,
+};
+
+#[test]
+fn arbitrary_bytes() {
+    let re = bytes::Regex::new(r"(?-u)caf\xE9").unwrap();
+    assert_eq!(re.find(b"le caf\xE9 ").unwrap().range(), 3..7);
+    assert!(!re.is_match("café".as_bytes()));
+    let re = bytes::Regex::new(r"(?-u:.)+").unwrap();
+    assert_eq!(re.find(b"a\xFFb\n").unwrap().as_bytes(), b"a\xFFb");
+    let re = bytes::Regex::new(r"(?-u)[^a]").unwrap();
+    assert_eq!(re.find("aé".as_bytes()).unwrap().range(), 1..3);
+    assert_eq!(re.find(b"a\xC3").unwrap().range(), 1..2);
+}
+
+#[test]
+fn unicode_mode_skips_invalid_bytes() {
+    let re = bytes::Regex::new(r".+").unwrap();
+    let all: Vec<_> = re
+        .find_iter(b"ab\xFFc\xE2\x82")
+        .map(|m| m.range())
+        .collect();
+    assert_eq!(all, [0..2, 3..4]);
+    let re = bytes::Regex::new(r"b*").unwrap();
+    assert_eq!(re.find_iter(b"\xFFa\xF0\x9F\x98\x80").count(), 4);
+}
+
+#[test]
+fn captures() {
+    let re = bytes::Regex::new(r"(?P<x>\w+)=([^;]*)").unwrap();
+    let caps = re.captures(b"\xFF\xFFk=v\xC3\xA9;").unwrap();
+    assert_eq!(&caps["x"], b"k");
+    assert_eq!(&caps[2], "vé".as_bytes());
+    assert_eq!(caps.len(), 3);
+    let all: Vec<_> = re
+        .captures_iter(b"a=1;b=\xFF")
+        .map(|caps| caps[1].to_vec())
+        .collect();
+    assert_eq!(all, [b"a".to_vec(), b"b".to_vec()]);
+}
+
+#[test]
+fn invalid_byte_classes() {
+    for pattern in [r"(?-u)[\xFF-\x80]", r"(?-u)[é-\xFF]", r"(?-u)[\xFF-a]"] {
+        assert!(Regexp::parse(pattern).is_err(), "{pattern}");
+    }
+}
+
+#[test]
+fn engines_agree_on_bytes() {
+    let hays: [&[u8]; 9] = [
+        b"caf\xE9",
+        "café".as_bytes(),
+        b"\xFF",
+        b"a\xFFb",
+        b"\xC3\xA9\xC3",
+        b"a\x85b",
+        b"\xFEx\xFE",
+        b"",
+        b"A\xC3\xC3",
+    ];
+    for pattern in [
+        r"(?-u)caf\xE9",
+        r"(?-u:.)",
+        r".",
+        r"(?-u)[a-\xFF]+",
+        r"(?-u)\W+",
+        r"(?-u)\b\w+\b",
+        r"\b\w+\b",
+        r"(?-u)^\xFF|\xFE$",
+        r"(a)(?-u:[\x80-\xFF])(b)",
+        r"(?i)(?-u)A\xC3",
+    ] {
+        let prog = Regexp::Paren(0, None, Regexp::parse(pattern).unwrap())
+            .compile()
+            .unwrap();
+        let dfa = Dfa::new(&prog).unwrap();
+        let (meta, lazy) = (Meta::new(), LazyDfa::new());
+        let mut engines: Vec<&dyn Engine> = ENGINES.to_vec();
+        engines.extend([&meta as &dyn Engine, &lazy, &dfa, &OnePass]);
+        for hay in hays {
+            for anchored in [Anchored::No, Anchored::Yes] {
+                for start in 0..=hay.len() {
+                    let input = Input::new(hay).span(start..hay.len()).anchored(anchored);
+                    let mut want = Sub::new(prog.nsub());
+                    let matched = Pike.search(&prog, &input, &mut want);
+                    for engine in &engines {
+                        let mut sub = Sub::new(prog.nsub());
+                        assert_eq!(
+                            engine.search(&prog, &input, &mut sub),
+                            matched,
+                            "{pattern} {hay:?} {start} {anchored:?} {}",
+                            engine.name()
+                        );
+                        if matched && engine.captures() {
+                            assert_eq!(sub, want, "{pattern} {hay:?} {start} {}", engine.name());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+#[should_panic = "span 1..3 is not on char boundaries of the haystack"]
+fn str_span_inside_a_char() {
+    let re = Regex::new(r"x*").unwrap();
+    let _ = re.search(&Input::new("éa").span(1..3));
+}
+
+#[test]
+#[should_panic = "haystack is not valid UTF-8"]
+fn str_search_of_invalid_utf8() {
+    let re = Regex::new(r"a").unwrap();
+    let _ = re.search(&Input::new(b"a\xFF"));
+}
+
+#[test]
+fn str_spans_on_char_boundaries() {
+    let re = Regex::new(r"x*").unwrap();
+    let m = re.search(&Input::new("éa").span(2..3)).unwrap();
+    assert_eq!(m.range(), 2..2);
+    assert_eq!(m.as_str(), "");
+}
@@ -0,0 +1,67 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Class, ErrorKind, Regex, Regexp, ENGINES};
+
+fn class(pattern: &str) -> Class {
+    match *Regexp::parse(pattern).unwrap() {
+        Regexp::Class(class) => class,
+        re => panic!("{pattern} parsed as {re}"),
+    }
+}
+
+#[test]
+fn ranges_are_merged() {
+    assert_eq!(
+        class("[a-z0-9_]").ranges(),
+        [('0', '9'), ('_', '_'), ('a', 'z')]
+    );
+    assert_eq!(class("[c-ea-cx]").ranges(), [('a', 'e'), ('x', 'x')]);
+    assert_eq!(class("[ab-d]").ranges(), [('a', 'd')]);
+}
+
+#[test]
+fn negated_class() {
+    assert_eq!(class("[^b-y]").ranges(), [('\0', 'a'), ('z', char::MAX)]);
+    // Negation skips the surrogates.
+    assert_eq!(
+        class("[^\0-\u{D7FF}\u{E001}-\u{10FFFF}]").ranges(),
+        [('\u{E000}', '\u{E000}')]
+    );
+}
+
+#[test]
+fn escaped_and_literal_bracket_and_dash() {
+    assert_eq!(class(r"[\]]").ranges(), [(']', ']')]);
+    assert_eq!(class("[]a]").ranges(), [(']', ']'), ('a', 'a')]);
+    assert_eq!(
+        class(r"[a\-z]").ranges(),
+        [('-', '-'), ('a', 'a'), ('z', 'z')]
+    );
+    assert_eq!(class("[-a]").ranges(), [('-', '-'), ('a', 'a')]);
+    assert_eq!(class("[a-]").ranges(), [('-', '-'), ('a', 'a')]);
+    assert_eq!(class(r"[\n\t]").ranges(), [('\t', '\n')]);
+}
+
+#[test]
+fn class_errors() {
+    let err = Regexp::parse("[z-a]").unwrap_err();
+    assert_eq!(*err.kind(), ErrorKind::InvalidRange);
+    let err = Regexp::parse("[a-c").unwrap_err();
+    assert_eq!(*err.kind(), ErrorKind::MissingBracket);
+}
+
+#[test]
+fn engines_match_classes() {
+    for engine in ENGINES {
+        let re = Regex::new("[a-z0-9_]+").unwrap().with_engine(engine);
+        assert_eq!(re.find("--ab_9Z").unwrap().as_str(), "ab_9", "{engine:?}");
+        let re = Regex::new("[^a-z]+").unwrap().with_engine(engine);
+        assert_eq!(re.find("abé€Zc").unwrap().as_str(), "é€Z", "{engine:?}");
+        let re = Regex::new("x[]]").unwrap().with_engine(engine);
+        assert!(re.is_match("ax]"), "{engine:?}");
+        assert!(!re.is_match("ax["), "{engine:?}");
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Anchored, Dfa, DfaBuilder, DfaError, Engine, Input, Pike, Prog, Regexp, Sub};
+
+fn compile(pattern: &str) -> Prog {
+    Regexp::Paren(0, None, Regexp::parse(pattern).unwrap())
+        .compile()
+        .unwrap()
+}
+
+#[test]
+fn serialized_dfas_agree_with_pike() {
+    for pattern in [
+        r"\bfoo\b",
+        r"(?m)^ab$",
+        r"^a|b$",
+        r"[a-c]+x",
+        r"é\B.",
+        r"(?-u:\b)é",
+        r"(?s)a.c",
+        r"\d{2,3}z",
+        r"\Aq|x\z",
+        r"(a|b)*a(a|b){3}",
+    ] {
+        let prog = compile(pattern);
+        let dfa = Dfa::new(&prog).unwrap();
+        let loaded = Dfa::from_bytes(&dfa.to_bytes()).unwrap();
+        assert_eq!(loaded, dfa, "{pattern}");
+        for hay in [
+            "foo", "xfoo foo", "ab\nab", "zab\n", "cab", "aabcx", "éaé", " é", "a\nc", "12z", "q",
+            "xx", "", "abbb", "babab",
+        ] {
+            let second = hay.char_indices().nth(1).map_or(0, |(i, _)| i);
+            for span in [0..hay.len(), second..hay.len()] {
+                for anchored in [Anchored::No, Anchored::Yes] {
+                    let input = Input::new(hay).span(span.clone()).anchored(anchored);
+                    let want = Pike.search(&prog, &input, &mut Sub::new(0));
+                    assert_eq!(
+                        loaded.is_match(&input),
+                        want,
+                        "{pattern} {hay:?} {span:?} {anchored:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn minimized() {
+    // Both prefixes lead to the same states.
+    let a = Dfa::new(&compile(r"^(?:ab|cb)d")).unwrap();
+    let b = Dfa::new(&compile(r"^[ac]bd")).unwrap();
+    assert_eq!(a.len(), b.len());
+}
+
+#[test]
+fn captures_use_the_pike_vm() {
+    let prog = compile(r"(a+)(b)?");
+    let dfa = Dfa::new(&prog).unwrap();
+    let mut sub = Sub::new(prog.nsub());
+    assert!(dfa.search(&prog, &Input::new("xaab"), &mut sub));
+    assert_eq!(sub.group(1), Some((1, 3)));
+    assert_eq!(sub.group(2), Some((3, 4)));
+}
+
+#[test]
+fn invalid_bytes() {
+    let bytes = Dfa::new(&compile(r"a+b")).unwrap().to_bytes();
+    for (bytes, what) in [
+        (&bytes[..bytes.len() - 4], "truncated transitions"),
+        (&bytes[..bytes.len() - 1], "length is not a multiple of 4"),
+        (&bytes[..0], "missing magic number"),
+        (&bytes[4..], "bad magic number"),
+    ] {
+        assert_eq!(Dfa::from_bytes(bytes), Err(DfaError::InvalidFormat(what)));
+    }
+    let mut trailing = bytes.clone();
+    trailing.extend([0; 4]);
+    assert_eq!(
+        Dfa::from_bytes(&trailing),
+        Err(DfaError::InvalidFormat("trailing bytes"))
+    );
+    let mut out_of_bounds = bytes.clone();
+    let n = out_of_bounds.len();
+    out_of_bounds[n - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(
+        Dfa::from_bytes(&out_of_bounds),
+        Err(DfaError::InvalidFormat("state out of bounds"))
+    );
+}
+
+#[test]
+fn state_limit() {
+    let prog = compile(r"(a|b)*a(a|b){12}");
+    assert_eq!(
+        DfaBuilder::new().state_limit(1000).build(&prog),
+        Err(DfaError::TooManyStates { limit: 1000 })
+    );
+    assert!(DfaBuilder::new()
+        .state_limit(1 << 14)
+        .build(&compile(r"(a|b)*a(a|b){8}"))
+        .is_ok());
+}
@@ -0,0 +1,128 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Anchored, Engine, Input, Pike, Prog, Regexp, Sub, Thompson, ENGINES};
+
+const PATTERNS: [&str; 10] = [
+    r"(a*)*b",
+    r"(a|ab)(c|bcd)(d*)",
+    r"(a*)+$",
+    r"(a+|b+)*c",
+    r"x(\w*?)(\d+)",
+    r"(?:(a)|b)*",
+    r"\b(\w+)\b",
+    r"(a)|(b)",
+    r"(?m)^(\d)?$",
+    r"(é+)(.)",
+];
+
+const HAYSTACKS: [&str; 9] = [
+    "aaab",
+    "abcd",
+    "aab",
+    "abab c",
+    "yxab12",
+    "  foo bar",
+    "b",
+    "1\n\n2",
+    "xéé€",
+];
+
+fn compile(pattern: &str) -> Prog {
+    Regexp::Paren(0, None, Regexp::parse(pattern).unwrap())
+        .compile()
+        .unwrap()
+}
+
+#[test]
+fn engines_agree() {
+    for pattern in PATTERNS {
+        let prog = compile(pattern);
+        for hay in HAYSTACKS {
+            for anchored in [Anchored::No, Anchored::Yes] {
+                let input = Input::new(hay).anchored(anchored);
+                let mut want = Sub::new(prog.nsub());
+                let matched = Pike.search(&prog, &input, &mut want);
+                for engine in ENGINES {
+                    let mut sub = Sub::new(prog.nsub());
+                    assert_eq!(
+                        engine.search(&prog, &input, &mut sub),
+                        matched,
+                        "{} {pattern} {hay:?}",
+                        engine.name()
+                    );
+                    if engine.captures() {
+                        assert_eq!(sub, want, "{} {pattern} {hay:?}", engine.name());
+                    } else {
+                        assert_eq!(sub.group(0), want.group(0), "{pattern} {hay:?}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn sub_is_reset_on_no_match() {
+    let prog = compile(r"(a)(b)?c");
+    for engine in ENGINES {
+        let mut sub = Sub::new(prog.nsub());
+        assert!(engine.search(&prog, &Input::new("abc"), &mut sub));
+        assert!(!engine.search(&prog, &Input::new("ab"), &mut sub));
+        assert_eq!(sub, Sub::new(prog.nsub()), "{}", engine.name());
+    }
+}
+
+#[test]
+fn program_without_group_0() {
+    let prog = Regexp::parse("b").unwrap().compile().unwrap();
+    for engine in ENGINES {
+        let mut sub = Sub::new(2);
+        let matched = engine.search(&prog, &Input::new("aab"), &mut sub);
+        assert_eq!((matched, sub.group(0)), (true, None), "{}", engine.name());
+    }
+}
+
+#[test]
+fn thompson_fills_only_the_overall_match() {
+    let prog = compile(r"x(a)");
+    let mut sub = Sub::new(prog.nsub());
+    assert!(!Thompson.captures());
+    assert!(Thompson.search(&prog, &Input::new("yxa"), &mut sub));
+    assert_eq!(sub.group(0), Some((1, 3)));
+    assert_eq!(sub.group(1), None);
+}
+
+#[test]
+fn engines_are_generic() {
+    fn count<E: Engine>(engine: E, prog: &Prog, hay: &str) -> usize {
+        let mut sub = Sub::new(2);
+        (0..=hay.len())
+            .filter(|&start| {
+                let input = Input::new(hay)
+                    .span(start..hay.len())
+                    .anchored(Anchored::Yes);
+                engine.search(prog, &input, &mut sub)
+            })
+            .count()
+    }
+    let prog = compile(r"ab");
+    let names: Vec<_> = ENGINES.iter().map(|engine| engine.name()).collect();
+    assert_eq!(
+        names,
+        [
+            "recursive",
+            "recursiveloop",
+            "recursivestack",
+            "backtrack",
+            "thompson",
+            "pike"
+        ]
+    );
+    for engine in ENGINES {
+        assert_eq!(count(engine, &prog, "abab"), 2, "{}", engine.name());
+    }
+    assert_eq!(count(Pike, &prog, "ab"), 1);
+}
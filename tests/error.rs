@@ -0,0 +1,57 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{ErrorKind, Regex, Regexp};
+
+#[test]
+fn kinds_and_spans() {
+    for (pattern, kind, span) in [
+        ("(ab", ErrorKind::MissingParen, 3..3),
+        ("ab)", ErrorKind::UnmatchedParen, 2..3),
+        ("a(b))", ErrorKind::UnmatchedParen, 4..5),
+        ("[ab", ErrorKind::MissingBracket, 3..3),
+        ("*a", ErrorKind::NothingToRepeat, 0..1),
+        ("a|*", ErrorKind::NothingToRepeat, 2..3),
+        ("a**", ErrorKind::NothingToRepeat, 2..3),
+        ("", ErrorKind::MissingExpression, 0..0),
+        ("a|", ErrorKind::MissingExpression, 2..2),
+        ("()", ErrorKind::MissingExpression, 1..2),
+        (r"a\", ErrorKind::TrailingBackslash, 1..2),
+        (r"a\q", ErrorKind::UnknownEscape('q'), 1..3),
+        (r"\x4g", ErrorKind::InvalidEscape, 0..4),
+        ("[b-a]", ErrorKind::InvalidRange, 1..4),
+        ("(?", ErrorKind::InvalidGroup, 0..2),
+        ("(?=a)", ErrorKind::UnknownFlag('='), 2..3),
+        ("a{2,1}", ErrorKind::InvalidRepeat, 1..6),
+    ] {
+        let err = Regexp::parse(pattern).unwrap_err();
+        assert_eq!(*err.kind(), kind, "{pattern:?}");
+        assert_eq!(err.span(), span, "{pattern:?}");
+        assert_eq!(err.pattern(), pattern);
+    }
+}
+
+#[test]
+fn display() {
+    let err = Regexp::parse("(abc").unwrap_err();
+    assert_eq!(err.to_string(), "regex: error at 4: missing )");
+    assert_eq!(
+        format!("{err:#}"),
+        "regex: error at 4: missing )\n    (abc\n        ^"
+    );
+    let err = Regexp::parse(r"éa\q").unwrap_err();
+    assert_eq!(
+        format!("{err:#}"),
+        "regex: error at 3: unknown escape \\q\n    éa\\q\n      ^^"
+    );
+}
+
+#[test]
+fn compile_errors_span_the_pattern() {
+    let err = Regex::new("(?:a{1000}){1000}").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::TooBig { .. }));
+    assert_eq!(err.span(), 0..17);
+    assert!(err.to_string().starts_with("regex: error at 0: program of"));
+}
@@ -0,0 +1,79 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{ErrorKind, Regex, Regexp};
+
+#[test]
+fn escaped_metachars_are_literals() {
+    for meta in [
+        '\\', '*', '+', '?', '|', '.', '(', ')', '[', ']', '{', '}', '^', '$',
+    ] {
+        let pattern = format!("\\{meta}");
+        assert_eq!(
+            *Regexp::parse(&pattern).unwrap(),
+            Regexp::Lit(meta),
+            "{pattern}"
+        );
+    }
+    let re = Regex::new(r"\*\.rs\|\(x\)").unwrap();
+    assert_eq!(re.find("a *.rs|(x) b").unwrap().range(), 2..10);
+    assert!(!re.is_match("main.rs"));
+}
+
+#[test]
+fn escaped_control_chars() {
+    for (pattern, ch) in [
+        (r"\n", '\n'),
+        (r"\t", '\t'),
+        (r"\r", '\r'),
+        (r"\0", '\0'),
+        (r"\x41", 'A'),
+        (r"\x7f", '\x7F'),
+        (r"\u{e9}", 'é'),
+        (r"\u{1F600}", '😀'),
+        (r"\u{10FFFF}", '\u{10FFFF}'),
+    ] {
+        assert_eq!(
+            *Regexp::parse(pattern).unwrap(),
+            Regexp::Lit(ch),
+            "{pattern}"
+        );
+    }
+    let re = Regex::new(r"a\tb\u{e9}").unwrap();
+    assert!(re.is_match("xa\tbé"));
+}
+
+#[test]
+fn trailing_backslash() {
+    let err = Regexp::parse(r"ab\").unwrap_err();
+    assert_eq!(*err.kind(), ErrorKind::TrailingBackslash);
+    assert_eq!(err.span(), 2..3);
+}
+
+#[test]
+fn unknown_escape() {
+    for (pattern, ch) in [(r"\q", 'q'), (r"a\k", 'k'), (r"\é", 'é')] {
+        let err = Regexp::parse(pattern).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::UnknownEscape(ch), "{pattern}");
+    }
+}
+
+#[test]
+fn invalid_hex_escape() {
+    for pattern in [
+        r"\x",
+        r"\x4",
+        r"\xg0",
+        r"\u41",
+        r"\u{}",
+        r"\u{1234567}",
+        r"\u{D800}",
+        r"\u{110000}",
+        r"\u{41",
+    ] {
+        let err = Regexp::parse(pattern).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidEscape, "{pattern}");
+    }
+}
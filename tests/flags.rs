@@ -0,0 +1,104 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Class, ErrorKind, Parser, Regex, Regexp, ENGINES};
+
+fn folded(ch: char) -> Class {
+    let mut class = Class::from_ranges([(ch, ch)]);
+    class.case_fold(true);
+    class
+}
+
+#[test]
+fn case_folding_orbits() {
+    assert_eq!(
+        folded('k').ranges(),
+        [('K', 'K'), ('k', 'k'), ('\u{212A}', '\u{212A}')]
+    );
+    assert_eq!(folded('σ').ranges(), [('Σ', 'Σ'), ('ς', 'σ')]);
+    assert_eq!(folded('ß').ranges(), [('ß', 'ß'), ('ẞ', 'ẞ')]);
+    assert_eq!(folded('1').ranges(), [('1', '1')]);
+    let mut ascii = Class::from_ranges([('k', 'k')]);
+    ascii.case_fold(false);
+    assert_eq!(ascii.ranges(), [('K', 'K'), ('k', 'k')]);
+}
+
+#[test]
+fn case_folding_agrees_with_std() {
+    for ch in '\0'..=char::MAX {
+        let mut lower = ch.to_lowercase();
+        let mut upper = ch.to_uppercase();
+        let mapped = [(lower.next(), lower.next()), (upper.next(), upper.next())];
+        for mapped in mapped {
+            if let (Some(mapped), None) = mapped {
+                if mapped != ch {
+                    assert!(folded(ch).contains(mapped), "{ch:?}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn case_insensitive() {
+    for engine in ENGINES {
+        let re = Regex::new("(?i)straße").unwrap().with_engine(engine);
+        assert!(re.is_match("STRAẞE"), "{engine:?}");
+        assert!(!re.is_match("STRASSE"), "{engine:?}");
+        let re = Regex::new("(?i:a)b").unwrap().with_engine(engine);
+        assert!(re.is_match("Ab"), "{engine:?}");
+        assert!(!re.is_match("AB"), "{engine:?}");
+        let re = Regex::new("(?i)[a-c]x(?-i)y").unwrap().with_engine(engine);
+        assert!(re.is_match("BXy"), "{engine:?}");
+        assert!(!re.is_match("BXY"), "{engine:?}");
+    }
+    let re = Parser::new().case_insensitive(true).parse("a").unwrap();
+    assert_eq!(
+        *re,
+        Regexp::Class(Class::from_ranges([('A', 'A'), ('a', 'a')]))
+    );
+}
+
+#[test]
+fn dot_matches_new_line() {
+    for engine in ENGINES {
+        let re = Regex::new("a.b").unwrap().with_engine(engine);
+        assert!(!re.is_match("a\nb"), "{engine:?}");
+        let re = Regex::new("(?s)a.b").unwrap().with_engine(engine);
+        assert!(re.is_match("a\nb"), "{engine:?}");
+        let re = Regex::new("(?s:a.)b.").unwrap().with_engine(engine);
+        assert!(re.is_match("a\nbc"), "{engine:?}");
+        assert!(!re.is_match("a\nb\n"), "{engine:?}");
+    }
+}
+
+#[test]
+fn multi_line_scope() {
+    let re = Regex::new("(?m:^a$)|^b").unwrap();
+    assert!(re.is_match("x\na\n"));
+    assert!(!re.is_match("x\nb"));
+}
+
+#[test]
+fn verbose() {
+    let re = Regex::new("(?x) a b # comment\n c [ ]").unwrap();
+    assert!(re.is_match("abc "));
+    assert!(!re.is_match("a b c "));
+    let re = Regex::new(r"(?x)a\ b").unwrap();
+    assert!(re.is_match("a b"));
+}
+
+#[test]
+fn flag_errors() {
+    let err = Regexp::parse("(?q)a").unwrap_err();
+    assert_eq!(*err.kind(), ErrorKind::UnknownFlag('q'));
+    for pattern in ["(?)a", "(?-)a", "(?i-)a", "(?i--s)a"] {
+        let err = Regexp::parse(pattern).unwrap_err();
+        assert!(
+            matches!(err.kind(), ErrorKind::InvalidGroup),
+            "{pattern}: {err}"
+        );
+    }
+}
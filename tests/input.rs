@@ -0,0 +1,71 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Anchored, Input, Regex, ENGINES};
+
+#[test]
+fn builder() {
+    let input = Input::new("abc");
+    assert_eq!(input.haystack(), b"abc");
+    assert_eq!(input.get_span(), 0..3);
+    assert_eq!(input.get_anchored(), Anchored::No);
+    let input = input.span(1..2).anchored(Anchored::Yes);
+    assert_eq!((input.start(), input.end()), (1, 2));
+    assert_eq!(input.get_anchored(), Anchored::Yes);
+}
+
+#[test]
+#[should_panic = "invalid span"]
+fn span_out_of_bounds() {
+    let _ = Input::new("abc").span(2..4);
+}
+
+#[test]
+fn assertions_see_outside_the_span() {
+    for engine in ENGINES {
+        let re = Regex::new(r"\bfoo\b").unwrap().with_engine(engine);
+        let hay = "xfoo foo";
+        assert_eq!(re.search(&Input::new(hay).span(1..4)), None, "{engine:?}");
+        let m = re.search(&Input::new(hay).span(4..8)).unwrap();
+        assert_eq!(m.range(), 5..8, "{engine:?}");
+        let re = Regex::new(r"^b|b$").unwrap().with_engine(engine);
+        assert_eq!(re.search(&Input::new("abc").span(1..2)), None, "{engine:?}");
+        let re = Regex::new(r"(?m)^b").unwrap().with_engine(engine);
+        let m = re.search(&Input::new("a\nb").span(2..3)).unwrap();
+        assert_eq!(m.range(), 2..3, "{engine:?}");
+    }
+}
+
+#[test]
+fn matches_stay_within_the_span() {
+    for engine in ENGINES {
+        let re = Regex::new(r"ab+").unwrap().with_engine(engine);
+        let m = re.search(&Input::new("abbb").span(0..3)).unwrap();
+        assert_eq!(m.range(), 0..3, "{engine:?}");
+        assert_eq!(
+            re.search(&Input::new("xabc").span(1..2)),
+            None,
+            "{engine:?}"
+        );
+        assert_eq!(re.search(&Input::new("ab").span(1..1)), None, "{engine:?}");
+    }
+}
+
+#[test]
+fn anchored() {
+    for engine in ENGINES {
+        let re = Regex::new(r"ab").unwrap().with_engine(engine);
+        let input = Input::new("xab").anchored(Anchored::Yes);
+        assert_eq!(re.search(&input), None, "{engine:?}");
+        let m = re.search(&input.span(1..3)).unwrap();
+        assert_eq!(m.range(), 1..3, "{engine:?}");
+        let re = Regex::new(r"(b)|a(b)").unwrap().with_engine(engine);
+        let input = Input::new("ab").anchored(Anchored::Yes);
+        if engine.captures() {
+            let caps = re.search_captures(&input).unwrap();
+            assert_eq!(caps.get(2).unwrap().range(), 1..2, "{engine:?}");
+        }
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Regex, ENGINES};
+
+#[test]
+fn find_iter_is_non_overlapping() {
+    for engine in ENGINES {
+        let re = Regex::new(r"\d+").unwrap().with_engine(engine);
+        let all: Vec<_> = re.find_iter("a1 22 333b").map(|m| m.as_str()).collect();
+        assert_eq!(all, ["1", "22", "333"], "{engine:?}");
+        let re = Regex::new(r"aa").unwrap().with_engine(engine);
+        assert_eq!(re.find_iter("aaaaa").count(), 2, "{engine:?}");
+        let re = Regex::new(r"\bx").unwrap().with_engine(engine);
+        assert_eq!(re.find_iter("x ax x").count(), 2, "{engine:?}");
+        assert_eq!(re.find_iter("").count(), 0, "{engine:?}");
+    }
+}
+
+#[test]
+fn empty_matches_advance_by_one_char() {
+    for engine in ENGINES {
+        let re = Regex::new(r"a*").unwrap().with_engine(engine);
+        let all: Vec<_> = re.find_iter("baaéc").map(|m| m.range()).collect();
+        assert_eq!(all, [0..0, 1..3, 5..5, 6..6], "{engine:?}");
+        let re = Regex::new(r"x?").unwrap().with_engine(engine);
+        let all: Vec<_> = re.find_iter("éa😀").map(|m| m.range()).collect();
+        assert_eq!(all, [0..0, 2..2, 3..3, 7..7], "{engine:?}");
+        assert_eq!(re.find_iter("").count(), 1, "{engine:?}");
+    }
+}
+
+#[test]
+fn no_empty_match_right_after_a_match() {
+    for engine in ENGINES {
+        let re = Regex::new(r"a*?|b").unwrap().with_engine(engine);
+        let all: Vec<_> = re.find_iter("ab").map(|m| m.range()).collect();
+        assert_eq!(all, [0..0, 1..1, 2..2], "{engine:?}");
+        let re = Regex::new(r"b|x?").unwrap().with_engine(engine);
+        let all: Vec<_> = re.find_iter("abb").map(|m| m.range()).collect();
+        assert_eq!(all, [0..0, 1..2, 2..3], "{engine:?}");
+    }
+}
+
+#[test]
+fn captures_iter() {
+    for engine in ENGINES.iter().filter(|engine| engine.captures()) {
+        let re = Regex::new(r"(\w)(\d)?").unwrap().with_engine(engine);
+        let all: Vec<_> = re
+            .captures_iter("a1 b c3")
+            .map(|caps| {
+                (
+                    caps.get(1).unwrap().as_str(),
+                    caps.get(2).map(|m| m.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            all,
+            [("a", Some("1")), ("b", None), ("c", Some("3"))],
+            "{engine:?}"
+        );
+    }
+}
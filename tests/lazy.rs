@@ -0,0 +1,101 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Anchored, Engine, Input, LazyDfa, Pike, Prog, Regexp, Sub};
+
+fn compile(pattern: &str) -> Prog {
+    Regexp::Paren(0, None, Regexp::parse(pattern).unwrap())
+        .compile()
+        .unwrap()
+}
+
+#[test]
+fn agrees_with_pike() {
+    let dfa = LazyDfa::new();
+    let tiny = LazyDfa::with_capacity(100);
+    for pattern in [
+        r"\bfoo\b",
+        r"(?m)^ab$",
+        r"^a|b$",
+        r"[a-c]+x",
+        r"é\B.",
+        r"(?-u:\b)é",
+        r"(?s)a.c",
+        r"\d{2,3}z",
+        r"\Aq|x\z",
+    ] {
+        let prog = compile(pattern);
+        for hay in [
+            "foo", "xfoo foo", "ab\nab", "zab\n", "cab", "aabcx", "éaé", " é", "a\nc", "12z", "q",
+            "xx", "",
+        ] {
+            let second = hay.char_indices().nth(1).map_or(0, |(i, _)| i);
+            for span in [0..hay.len(), second..hay.len()] {
+                for anchored in [Anchored::No, Anchored::Yes] {
+                    let input = Input::new(hay).span(span.clone()).anchored(anchored);
+                    let want = Pike.search(&prog, &input, &mut Sub::new(0));
+                    for dfa in [&dfa, &tiny] {
+                        assert_eq!(
+                            dfa.search(&prog, &input, &mut Sub::new(0)),
+                            want,
+                            "{pattern} {hay:?} {span:?} {anchored:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn cache_is_rebuilt_for_another_program() {
+    let dfa = LazyDfa::new();
+    let a = compile("a");
+    let b = compile("b");
+    for _ in 0..2 {
+        assert!(dfa.search(&a, &Input::new("xa"), &mut Sub::new(0)));
+        assert!(!dfa.search(&b, &Input::new("xa"), &mut Sub::new(0)));
+        assert!(dfa.search(&b.clone(), &Input::new("b"), &mut Sub::new(0)));
+    }
+    assert_eq!(a, compile("a"));
+    assert_ne!(a, b);
+}
+
+#[test]
+fn captures_use_the_pike_vm() {
+    let prog = compile(r"(a+)(b)?");
+    let dfa = LazyDfa::new();
+    let mut sub = Sub::new(prog.nsub());
+    assert!(dfa.search(&prog, &Input::new("xaab"), &mut sub));
+    assert_eq!(sub.group(1), Some((1, 3)));
+    assert_eq!(sub.group(2), Some((3, 4)));
+}
+
+#[test]
+fn small_caches_flush_or_give_up() {
+    let prog = compile(r"a[ab]{8}c");
+    let mut x: u32 = 12345;
+    let hay: String = (0..20_000)
+        .map(|_| {
+            x = x.wrapping_mul(1103515245).wrapping_add(12345);
+            if (x >> 16).is_multiple_of(2) {
+                'a'
+            } else {
+                'b'
+            }
+        })
+        .collect();
+    for capacity in [2000, 20_000, 200_000, 1 << 30] {
+        let dfa = LazyDfa::with_capacity(capacity);
+        for end in [hay.len(), 5000] {
+            let matching = hay[..end].to_owned() + "c";
+            let input = Input::new(&matching);
+            let want = Pike.search(&prog, &input, &mut Sub::new(0));
+            assert_eq!(dfa.search(&prog, &input, &mut Sub::new(0)), want);
+            let input = Input::new(&hay[..end]);
+            assert!(!dfa.search(&prog, &input, &mut Sub::new(0)), "{capacity}");
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{
+    Anchored, Compiler, Engine, Input, Meta, Pike, Prog, Regex, Regexp, Sub,
+    DEFAULT_VISITED_CAPACITY,
+};
+
+fn compile(pattern: &str) -> Prog {
+    Regexp::Paren(0, None, Regexp::parse(pattern).unwrap())
+        .compile()
+        .unwrap()
+}
+
+#[test]
+fn regex_uses_meta() {
+    let re = Regex::new("a").unwrap();
+    assert_eq!(re.engine().name(), "meta");
+}
+
+#[test]
+fn agrees_with_pike() {
+    let meta = Meta::new();
+    for pattern in [
+        r"key=(\w+);val=(\d+)",
+        r"(a|ab)(c|bcd)(d*)",
+        r"(a*)(a*)",
+        r"\b(\w+)\b",
+        r"(?m)^(\d+)$",
+        r"(a)|b",
+    ] {
+        let prog = compile(pattern);
+        for hay in [
+            "key=ab_1;val=42",
+            "abcd",
+            "aaa",
+            " foo bar",
+            "x\n12\n",
+            "b",
+            "",
+        ] {
+            for anchored in [Anchored::No, Anchored::Yes] {
+                let input = Input::new(hay).anchored(anchored);
+                for nsub in [0, 2, prog.nsub()] {
+                    let mut want = Sub::new(nsub);
+                    let matched = Pike.search(&prog, &input, &mut want);
+                    let mut sub = Sub::new(nsub);
+                    assert_eq!(
+                        meta.search(&prog, &input, &mut sub),
+                        matched,
+                        "{pattern} {hay:?} {anchored:?}"
+                    );
+                    assert_eq!(sub, want, "{pattern} {hay:?} {anchored:?} {nsub}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn program_without_group_0() {
+    let meta = Meta::new();
+    let prog = Regexp::parse("b").unwrap().compile().unwrap();
+    // UTF-8 programs that can match empty are searched for whole chars.
+    let utf8 = Compiler::new()
+        .utf8(true)
+        .compile(&Regexp::parse("b|x?").unwrap())
+        .unwrap();
+    for (prog, hay) in [(&prog, "aab"), (&utf8, "éb")] {
+        for nsub in [0, 2, 4] {
+            let mut sub = Sub::new(nsub);
+            let matched = meta.search(prog, &Input::new(hay), &mut sub);
+            assert_eq!((matched, sub.group(0)), (true, None), "{hay:?} {nsub}");
+        }
+    }
+}
+
+#[test]
+fn alternation_under_repetition_is_linear() {
+    for pattern in [r"(a|a)*c", r"(a*)*b", r"(a|aa)+$b"] {
+        let re = Regex::new(pattern).unwrap();
+        let hay = "a".repeat(10_000);
+        assert!(!re.is_match(&hay), "{pattern}");
+        assert!(re.find(&hay).is_none(), "{pattern}");
+        assert!(re.captures(&hay).is_none(), "{pattern}");
+    }
+}
+
+#[test]
+fn long_spans_capture_without_the_backtracker() {
+    let re = Regex::new(r"(\w+)@(\w+)").unwrap();
+    let hay = "x".repeat(DEFAULT_VISITED_CAPACITY) + " a@b";
+    let caps = re.captures(&hay).unwrap();
+    assert_eq!(&caps[1], "a");
+    assert_eq!(&caps[2], "b");
+    assert_eq!(re.find(&hay).unwrap().as_str(), "a@b");
+}
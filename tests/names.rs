@@ -0,0 +1,62 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{ErrorKind, Regex, Regexp, Sub, ENGINES};
+
+#[test]
+fn both_syntaxes_name_groups() {
+    let re = Regex::new(r"(?P<key>\w+)=(?<val>\w+)").unwrap();
+    let names: Vec<_> = re.capture_names().collect();
+    assert_eq!(names, [None, Some("key"), Some("val")]);
+    let caps = re.captures("a b=c").unwrap();
+    assert_eq!(&caps["key"], "b");
+    assert_eq!(caps.name("val").unwrap().range(), 4..5);
+    assert!(caps.name("nope").is_none());
+}
+
+#[test]
+fn names_follow_group_numbering() {
+    let re = Regex::new(r"(a)(?P<x>(b)(?P<y>c))").unwrap();
+    let names: Vec<_> = re.capture_names().collect();
+    assert_eq!(names, [None, None, Some("x"), None, Some("y")]);
+    assert_eq!(re.prog().group_index("y"), Some(4));
+    let caps = re.captures("abc").unwrap();
+    assert_eq!(&caps["x"], "bc");
+    assert_eq!(&caps[4], "c");
+}
+
+#[test]
+fn groups_that_are_never_emitted_are_counted() {
+    let re = Regex::new(r"(a){0}b").unwrap();
+    assert_eq!(re.captures_len(), 2);
+    let caps = re.captures("ab").unwrap();
+    assert_eq!(caps.len(), 2);
+    assert!(caps.get(1).is_none());
+    let re = Regex::new(r"(?P<x>a){0}(?P<y>b)").unwrap();
+    let names: Vec<_> = re.capture_names().collect();
+    assert_eq!(names, [None, Some("x"), Some("y")]);
+    assert_eq!(&re.captures("b").unwrap()["y"], "b");
+}
+
+#[test]
+fn lookup_by_name_on_sub() {
+    let re = Regex::new(r"(?P<n>\d+)").unwrap();
+    for engine in ENGINES.iter().filter(|engine| engine.captures()) {
+        let mut sub = Sub::new(re.prog().nsub());
+        let input = re1::Input::new("ab 42");
+        assert!(engine.search(re.prog(), &input, &mut sub));
+        assert_eq!(sub.name(re.prog(), "n"), Some((3, 5)), "{engine:?}");
+    }
+}
+
+#[test]
+fn invalid_names() {
+    for pattern in ["(?P<>a)", "(?P<1a>a)", "(?P<a-b>a)", "(?P<a"] {
+        let err = Regexp::parse(pattern).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidGroupName, "{pattern}");
+    }
+    let err = Regexp::parse("(?P<a>x)(?<a>y)").unwrap_err();
+    assert_eq!(*err.kind(), ErrorKind::DuplicateGroupName("a".to_owned()));
+}
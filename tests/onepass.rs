@@ -0,0 +1,80 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Anchored, Engine, Input, OnePass, Pike, Prog, Regexp, Sub};
+
+fn compile(pattern: &str) -> Prog {
+    Regexp::Paren(0, None, Regexp::parse(pattern).unwrap())
+        .compile()
+        .unwrap()
+}
+
+#[test]
+fn detects_one_pass_programs() {
+    for (pattern, one_pass) in [
+        (r"key=(\w+);val=(\d+)", true),
+        (r"^(a+)(b*)$", true),
+        (r"(a|b)*c", true),
+        (r"a(b?)c", true),
+        (r"x(\d+)?y", true),
+        (r"\b(foo)\b", true),
+        (r"(?m)^(ab)$", true),
+        (r"é(.)", true),
+        (r"(a*)(a*)", false),
+        (r"(a|ab)(c|bcd)(d*)", false),
+        (r"(a+)?(a)", false),
+        (r"a*?(a)", false),
+        (r"(?:a?)?b", false),
+    ] {
+        assert_eq!(compile(pattern).is_one_pass(), one_pass, "{pattern}");
+    }
+}
+
+#[test]
+fn agrees_with_pike() {
+    for pattern in [
+        r"key=(\w+);val=(\d+)",
+        r"^(a+)(b*)$",
+        r"(a|b)*c",
+        r"(a*)b?",
+        r"x(\d+)?y",
+        r"\b(foo)\b",
+        r"é(.)",
+        r"(a*)(a*)",
+        r"(a|ab)(c|bcd)(d*)",
+    ] {
+        let prog = compile(pattern);
+        for hay in [
+            "key=ab_1;val=42",
+            "xkey=a;val=1z",
+            "aabb",
+            "abc",
+            "bac",
+            "x12y",
+            "xy",
+            "foo bar",
+            " foo",
+            "éé",
+            "abcd",
+            "",
+        ] {
+            let second = hay.char_indices().nth(1).map_or(0, |(i, _)| i);
+            for span in [0..hay.len(), second..hay.len()] {
+                for anchored in [Anchored::No, Anchored::Yes] {
+                    let input = Input::new(hay).span(span.clone()).anchored(anchored);
+                    let mut want = Sub::new(prog.nsub());
+                    let matched = Pike.search(&prog, &input, &mut want);
+                    let mut sub = Sub::new(prog.nsub());
+                    assert_eq!(
+                        OnePass.search(&prog, &input, &mut sub),
+                        matched,
+                        "{pattern} {hay:?} {span:?} {anchored:?}"
+                    );
+                    assert_eq!(sub, want, "{pattern} {hay:?} {span:?} {anchored:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Class, Parser, Perl, Regex, Regexp, ENGINES};
+
+#[test]
+fn ascii_definitions() {
+    assert_eq!(Class::perl(Perl::Digit, false).ranges(), [('0', '9')]);
+    assert_eq!(
+        Class::perl(Perl::Word, false).ranges(),
+        [('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')]
+    );
+    assert_eq!(
+        Class::perl(Perl::Space, false).ranges(),
+        [('\t', '\r'), (' ', ' ')]
+    );
+}
+
+#[test]
+fn unicode_definitions_agree_with_std() {
+    let digit = Class::perl(Perl::Digit, true);
+    let word = Class::perl(Perl::Word, true);
+    let space = Class::perl(Perl::Space, true);
+    for ch in '\0'..=char::MAX {
+        assert_eq!(
+            word.contains(ch),
+            ch.is_alphanumeric() || ch == '_',
+            "{ch:?}"
+        );
+        assert_eq!(space.contains(ch), ch.is_whitespace(), "{ch:?}");
+        if digit.contains(ch) {
+            assert!(ch.is_numeric(), "{ch:?}");
+        }
+    }
+    for ch in ['٣', '߀', '９', '𝟎'] {
+        assert!(digit.contains(ch), "{ch:?}");
+    }
+    assert!(!digit.contains('²'));
+}
+
+#[test]
+fn negated_shorthands() {
+    let re = Regex::new(r"\D+\W\S").unwrap();
+    assert_eq!(re.find("12ab c!d").unwrap().as_str(), "ab c!d");
+    let mut not_digit = Class::perl(Perl::Digit, true);
+    not_digit.negate();
+    assert_eq!(*Regexp::parse(r"\D").unwrap(), Regexp::Class(not_digit));
+}
+
+#[test]
+fn digits_from_unicode_17() {
+    let digit = Regex::new(r"\d").unwrap();
+    let not_digit = Regex::new(r"\D").unwrap();
+    for hay in [
+        "\u{10D40}",
+        "\u{11BF0}",
+        "\u{11DE0}",
+        "\u{16130}",
+        "\u{1CCF0}",
+    ] {
+        assert!(digit.is_match(hay), "{hay:?}");
+        assert!(!not_digit.is_match(hay), "{hay:?}");
+    }
+}
+
+#[test]
+fn shorthands_in_brackets() {
+    let re = Regex::new(r"[\d\s,]+").unwrap();
+    assert_eq!(re.find("ab1, 2\t3x").unwrap().as_str(), "1, 2\t3");
+    let re = Regex::new(r"[^\w]+").unwrap();
+    assert_eq!(re.find("héllo, wörld").unwrap().as_str(), ", ");
+}
+
+#[test]
+fn unicode_flag_selects_definition() {
+    for engine in ENGINES {
+        let re = Regex::new(r"\w+").unwrap().with_engine(engine);
+        assert_eq!(re.find("«héllo»").unwrap().as_str(), "héllo", "{engine:?}");
+        let re = Regex::new(r"(?-u:\w)+").unwrap().with_engine(engine);
+        assert_eq!(re.find("«héllo»").unwrap().as_str(), "h", "{engine:?}");
+        let re = Regex::new(r"\d\s").unwrap().with_engine(engine);
+        assert!(re.is_match("٣\u{3000}"), "{engine:?}");
+    }
+    let re = Parser::new().unicode(false).parse(r"\d").unwrap();
+    assert_eq!(*re, Regexp::Class(Class::perl(Perl::Digit, false)));
+}
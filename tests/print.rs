@@ -0,0 +1,74 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::Regexp;
+
+#[test]
+fn minimal_parentheses() {
+    for (pattern, printed) in [
+        ("a|b|c", "a|b|c"),
+        ("a|(?:b|c)", "a|(?:b|c)"),
+        ("(?:ab)*", "(?:ab)*"),
+        ("(?:a|b)c", "(?:a|b)c"),
+        ("(?:(?:a))", "a"),
+        ("a(?:bc)", "a(?:bc)"),
+        ("(?:a*)?", "(?:a*)?"),
+        ("(a)(?P<n>b|c)", "(a)(?P<n>b|c)"),
+        ("(?<n>x)", "(?P<n>x)"),
+        ("a{2,}?b{3}c{1,2}", "a{2,}?b{3}c{1,2}"),
+        (r"\*\.\{", r"\*\.\{"),
+        ("x]}", "x]}"),
+        (r"\n\t\u{1}", r"\n\t\u{1}"),
+    ] {
+        let re = Regexp::parse(pattern).unwrap();
+        assert_eq!(re.to_pattern(), printed, "{pattern}");
+    }
+}
+
+#[test]
+fn classes_and_assertions() {
+    for (pattern, printed) in [
+        (r"\d", r"\d"),
+        (r"\W", r"\W"),
+        ("[a-c]", "[a-c]"),
+        ("[^a-c]", "[^a-c]"),
+        (r"[\]\-]", r"[\-\]]"),
+        ("(?s).", "(?s:.)"),
+        (".", "."),
+        ("^$", "^$"),
+        (r"\A\z", "^$"),
+        ("(?m)^$", "(?m:^)(?m:$)"),
+        (r"\b\B", r"\b\B"),
+        (r"(?-u:\b)", r"(?-u:\b)"),
+        (r"(?-u)\xFF", r"(?-u:[\xFF])"),
+    ] {
+        let re = Regexp::parse(pattern).unwrap();
+        assert_eq!(re.to_pattern(), printed, "{pattern}");
+    }
+}
+
+#[test]
+fn round_trip() {
+    for pattern in [
+        r"(a|ab)(c|bcd)(d*)",
+        r"(?i)straße|[^\s,]+",
+        r"(?x) a # b",
+        r"(?-u)[\x80-\xFF]+|\W",
+        r"(?P<year>\d{4})-(?P<month>\d{2})",
+        r"(?m)^\s*#.*$",
+        r"[^\n]*?|(?s:.)+?",
+        r"\u{0}[\0-\x1F]",
+        r"(?-u:.)",
+        r"[\[\]^-]|[-a]",
+    ] {
+        let re = Regexp::parse(pattern).unwrap();
+        let printed = re.to_pattern();
+        assert_eq!(
+            Regexp::parse(&printed).unwrap(),
+            re,
+            "{pattern} printed as {printed}"
+        );
+    }
+}
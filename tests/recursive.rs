@@ -0,0 +1,154 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{
+    Engine, GaveUp, Input, Pike, Prog, RecursionError, Recursive, RecursiveConfig, RecursiveLoop,
+    RecursiveStack, Regexp, Sub, DEFAULT_DEPTH_LIMIT, VM,
+};
+
+fn compile(pattern: &str) -> Prog {
+    Regexp::Paren(0, None, Regexp::parse(pattern).unwrap())
+        .compile()
+        .unwrap()
+}
+
+const RECURSIVE: [&dyn Engine; 3] = [&Recursive, &RecursiveLoop, &RecursiveStack];
+
+#[test]
+fn engines_are_not_exponential() {
+    let prog = compile(r"(a|a)*c");
+    for len in [30, 60, 100] {
+        let hay = "a".repeat(len);
+        for engine in RECURSIVE {
+            let mut sub = Sub::new(prog.nsub());
+            assert_eq!(
+                engine.try_search(&prog, &Input::new(&hay), &mut sub),
+                Ok(false),
+                "{} {len}",
+                engine.name()
+            );
+        }
+        let hay = hay + "c";
+        for engine in RECURSIVE {
+            let mut sub = Sub::new(prog.nsub());
+            assert_eq!(
+                engine.try_search(&prog, &Input::new(&hay), &mut sub),
+                Ok(true),
+                "{} {len}",
+                engine.name()
+            );
+            assert_eq!(sub.group(1), Some((len - 1, len)), "{}", engine.name());
+        }
+    }
+}
+
+#[test]
+fn memoized_matchers_agree_with_pike() {
+    let mut config = RecursiveConfig::new();
+    config.memoize(true);
+    for pattern in [
+        r"(a*)*b",
+        r"(a|ab)(c|bcd)(d*)",
+        r"(a*)+$",
+        r"(a+|b+)*c",
+        r"x(\w*?)(\d+)",
+        r"(?:(a)|b)*",
+        r"\b(\w+)\b",
+    ] {
+        let prog = compile(pattern);
+        for hay in ["aaab", "abcd", "aab", "abab c", "yxab12", "  foo bar"] {
+            let input = Input::new(hay);
+            let mut want = Sub::new(prog.nsub());
+            let matched = Pike.search(&prog, &input, &mut want);
+            for i in 0..3 {
+                let mut sub = Sub::new(prog.nsub());
+                let mut vm = VM::with_input(&prog, &input, false);
+                let result = match i {
+                    0 => vm.match_recursive_with(&mut sub, &config),
+                    1 => vm.match_recursive_loop_with(&mut sub, &config),
+                    _ => vm.match_recursive_stack(&mut sub, &config),
+                };
+                assert_eq!(result, Ok(matched), "{pattern} {hay:?} {i}");
+                if matched {
+                    assert_eq!(sub, want, "{pattern} {hay:?}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn depth_limit() {
+    let prog = compile(r"(a*)*b");
+    let hay = "a".repeat(5000);
+    let mut sub = Sub::new(prog.nsub());
+    let mut vm = VM::new(&prog, &hay, false);
+    assert_eq!(
+        vm.match_recursive(&mut sub),
+        Err(RecursionError::TooDeep {
+            limit: DEFAULT_DEPTH_LIMIT
+        })
+    );
+    let mut vm = VM::new(&prog, &hay, false);
+    assert_eq!(
+        vm.match_recursive_loop(&mut sub),
+        Err(RecursionError::TooDeep {
+            limit: DEFAULT_DEPTH_LIMIT
+        })
+    );
+    for engine in [&Recursive as &dyn Engine, &RecursiveLoop] {
+        assert_eq!(
+            engine.try_search(&prog, &Input::new(&hay), &mut sub),
+            Err(GaveUp::DepthLimit {
+                limit: DEFAULT_DEPTH_LIMIT
+            }),
+            "{}",
+            engine.name()
+        );
+        assert_eq!(sub.group(0), None);
+    }
+    assert_eq!(
+        RecursiveStack.try_search(&prog, &Input::new(&hay), &mut sub),
+        Ok(false)
+    );
+}
+
+#[test]
+fn engines_give_up_on_long_spans() {
+    let prog = compile(r"(a|a)*c");
+    let hay = "a".repeat(1_000_000) + "c";
+    for engine in RECURSIVE {
+        let mut sub = Sub::new(prog.nsub());
+        assert!(
+            matches!(
+                engine.try_search(&prog, &Input::new(&hay), &mut sub),
+                Err(GaveUp::HaystackTooLarge { len: 1_000_001, .. })
+            ),
+            "{}",
+            engine.name()
+        );
+    }
+    let mut sub = Sub::new(prog.nsub());
+    assert!(Pike.search(&prog, &Input::new(&hay), &mut sub));
+}
+
+#[test]
+fn stack_limit_is_separate_from_depth_limit() {
+    let prog = compile(r"(a)*b");
+    let hay = "a".repeat(5000) + "b";
+    let mut config = RecursiveConfig::new();
+    let mut sub = Sub::new(prog.nsub());
+    let mut vm = VM::new(&prog, &hay, false);
+    assert_eq!(vm.match_recursive_stack(&mut sub, &config), Ok(true));
+    assert_eq!(sub.group(1), Some((4999, 5000)));
+    config.stack_limit(100);
+    let mut vm = VM::new(&prog, &hay, false);
+    let err = vm.match_recursive_stack(&mut sub, &config).unwrap_err();
+    assert_eq!(err, RecursionError::StackTooLarge { limit: 100 });
+    assert_eq!(
+        err.to_string(),
+        "backtracking stack exceeds limit of 100 jobs"
+    );
+}
@@ -0,0 +1,73 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{ErrorKind, Regex};
+
+#[test]
+fn is_match_and_find() {
+    let re = Regex::new(r"b+c").unwrap();
+    assert_eq!(re.as_str(), "b+c");
+    assert_eq!(re.to_string(), "b+c");
+    assert!(re.is_match("abbbcd"));
+    assert!(!re.is_match("abd"));
+    let m = re.find("abbbcd").unwrap();
+    assert_eq!((m.start(), m.end()), (1, 5));
+    assert_eq!(m.range(), 1..5);
+    assert_eq!(m.len(), 4);
+    assert_eq!(m.as_str(), "bbbc");
+    assert!(re.find("abd").is_none());
+}
+
+#[test]
+fn find_is_leftmost_first() {
+    let re = Regex::new(r"a|ab").unwrap();
+    assert_eq!(re.find("xab").unwrap().as_str(), "a");
+    let re = Regex::new(r"é+").unwrap();
+    assert_eq!(re.find("aééb").unwrap().range(), 1..5);
+    let re = Regex::new(r"x*").unwrap();
+    assert!(re.find("abc").unwrap().is_empty());
+}
+
+#[test]
+fn captures_leave_unset_groups_empty() {
+    let re = Regex::new(r"(a)|(b)").unwrap();
+    assert_eq!(re.captures_len(), 3);
+    let caps = re.captures("b").unwrap();
+    assert_eq!(caps.len(), 3);
+    assert_eq!(caps.get(0).unwrap().as_str(), "b");
+    assert!(caps.get(1).is_none());
+    assert_eq!(caps.get(2).unwrap().range(), 0..1);
+    assert!(caps.get(3).is_none());
+    let groups: Vec<_> = caps.iter().map(|m| m.map(|m| m.as_str())).collect();
+    assert_eq!(groups, [Some("b"), None, Some("b")]);
+    assert!(re.captures("c").is_none());
+}
+
+#[test]
+fn captures_take_the_last_iteration() {
+    let re = Regex::new(r"(\w)+ (\d)?").unwrap();
+    let caps = re.captures("abc x").unwrap();
+    assert_eq!(&caps[0], "abc ");
+    assert_eq!(&caps[1], "c");
+    assert!(caps.get(2).is_none());
+}
+
+#[test]
+#[should_panic]
+fn index_of_unset_group_panics() {
+    let re = Regex::new(r"(a)?b").unwrap();
+    let _ = &re.captures("b").unwrap()[1];
+}
+
+#[test]
+fn invalid_patterns() {
+    for (pattern, kind) in [
+        ("a(b", ErrorKind::MissingParen),
+        ("a**", ErrorKind::NothingToRepeat),
+    ] {
+        let err = Regex::new(pattern).unwrap_err();
+        assert_eq!(*err.kind(), kind, "{pattern}");
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{CompileError, Compiler, ErrorKind, Regex, Regexp, ENGINES};
+
+#[test]
+fn counted_repetition_parses() {
+    let a = || Box::new(Regexp::Lit('a'));
+    for (pattern, re) in [
+        ("a{3}", Regexp::Repeat(true, 3, Some(3), a())),
+        ("a{2,}", Regexp::Repeat(true, 2, None, a())),
+        ("a{2,5}", Regexp::Repeat(true, 2, Some(5), a())),
+        ("a{2,5}?", Regexp::Repeat(false, 2, Some(5), a())),
+        ("a{0,}?", Regexp::Repeat(false, 0, None, a())),
+    ] {
+        assert_eq!(*Regexp::parse(pattern).unwrap(), re, "{pattern}");
+    }
+}
+
+#[test]
+fn braces_without_a_count_are_literal() {
+    for pattern in ["a{", "a{x}", "a{,3}", "a{1,x}"] {
+        let re = Regex::new(pattern).unwrap();
+        assert!(re.is_match(pattern), "{pattern}");
+    }
+}
+
+#[test]
+fn invalid_counts() {
+    for pattern in ["a{3,2}", "a{99999999999}"] {
+        let err = Regexp::parse(pattern).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidRepeat, "{pattern}");
+    }
+    let err = Regexp::parse("{2}").unwrap_err();
+    assert_eq!(*err.kind(), ErrorKind::NothingToRepeat);
+}
+
+#[test]
+fn engines_count_repetitions() {
+    for engine in ENGINES {
+        let re = Regex::new("a{2,3}").unwrap().with_engine(engine);
+        let all: Vec<_> = re.find_iter("aaaaaaa").map(|m| m.len()).collect();
+        assert_eq!(all, [3, 3], "{engine:?}");
+        let re = Regex::new("a{2,3}?").unwrap().with_engine(engine);
+        let all: Vec<_> = re.find_iter("aaaaaaa").map(|m| m.len()).collect();
+        assert_eq!(all, [2, 2, 2], "{engine:?}");
+        let re = Regex::new("^(?:ab){2,}$").unwrap().with_engine(engine);
+        assert!(re.is_match("ababab"), "{engine:?}");
+        assert!(!re.is_match("ab"), "{engine:?}");
+        let re = Regex::new("x{0}y").unwrap().with_engine(engine);
+        assert_eq!(re.find("xy").unwrap().range(), 1..2, "{engine:?}");
+    }
+}
+
+#[test]
+fn size_limit() {
+    let err = Regex::new("(a{1000}){1000}").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::TooBig { .. }), "{err}");
+    let re = Regexp::parse("a{10}").unwrap();
+    // The unanchored prefix and the final match add 4 instructions.
+    assert_eq!(
+        Compiler::new().size_limit(13).compile(&re),
+        Err(CompileError::TooBig {
+            size: 14,
+            limit: 13
+        })
+    );
+    assert_eq!(
+        Compiler::new()
+            .size_limit(14)
+            .compile(&re)
+            .unwrap()
+            .insts()
+            .len(),
+        14
+    );
+}
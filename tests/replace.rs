@@ -0,0 +1,73 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::borrow::Cow;
+
+use re1::{Captures, Regex};
+
+#[test]
+fn templates() {
+    let re = Regex::new(r"(?P<y>\d+)-(\d+)").unwrap();
+    assert_eq!(
+        re.replace_all("1-2 x 33-44", "$2/${y}$$ ${9}$x"),
+        "2/1$ $x x 44/33$ $x"
+    );
+    assert_eq!(re.replace("1-2 x 33-44", "${2}0"), "20 x 33-44");
+    assert_eq!(re.replace("1-2", "$20"), "");
+    assert_eq!(re.replace("1-2", "$0!"), "1-2!");
+    assert_eq!(re.replace("1-2", "${}$"), "${}$");
+    assert_eq!(re.replace("1-2", "$99999999999999999999999"), "");
+    let re = Regex::new(r"(a)|b").unwrap();
+    assert_eq!(re.replace_all("ab", "[$1]"), "[a][]");
+}
+
+#[test]
+fn limits() {
+    let re = Regex::new(r"\d").unwrap();
+    assert_eq!(re.replace("1 2 3", "_"), "_ 2 3");
+    assert_eq!(re.replacen("1 2 3", 2, "_"), "_ _ 3");
+    assert_eq!(re.replacen("1 2 3", 5, "_"), "_ _ _");
+    assert_eq!(re.replacen("1 2 3", 0, "_"), "_ _ _");
+    assert_eq!(re.replace_all("1 2 3", String::from("$0$0")), "11 22 33");
+}
+
+#[test]
+fn borrowed_when_nothing_matched() {
+    let re = Regex::new(r"\d").unwrap();
+    assert!(matches!(re.replace_all("abc", "_"), Cow::Borrowed("abc")));
+    assert!(matches!(re.replace_all("a1", "_"), Cow::Owned(_)));
+}
+
+#[test]
+fn closures() {
+    let re = Regex::new(r"(?P<n>\d+)").unwrap();
+    let doubled = re.replace_all("a1 b21", |caps: &Captures| {
+        (caps["n"].parse::<u32>().unwrap() * 2).to_string()
+    });
+    assert_eq!(doubled, "a2 b42");
+    let mut count = 0;
+    let numbered = re.replace_all("x y", |_: &Captures| {
+        count += 1;
+        count.to_string()
+    });
+    assert_eq!(numbered, "x y");
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn empty_matches() {
+    let re = Regex::new(r"a*").unwrap();
+    assert_eq!(re.replace_all("baac", "-"), "-b-c-");
+    assert_eq!(re.replace_all("é", "-"), "-é-");
+}
+
+#[test]
+fn expand() {
+    let re = Regex::new(r"(\w+)@(?P<host>\w+)").unwrap();
+    let caps = re.captures("me@host").unwrap();
+    let mut dst = String::from(">");
+    caps.expand("${host}:$1", &mut dst);
+    assert_eq!(dst, ">host:me");
+}
@@ -0,0 +1,42 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::Regex;
+
+#[test]
+fn split() {
+    let re = Regex::new(r"[ ,]+").unwrap();
+    let pieces: Vec<_> = re.split("a, b  c").collect();
+    assert_eq!(pieces, ["a", "b", "c"]);
+    let pieces: Vec<_> = re.split(",a, b  c,").collect();
+    assert_eq!(pieces, ["", "a", "b", "c", ""]);
+    let pieces: Vec<_> = re.split("abc").collect();
+    assert_eq!(pieces, ["abc"]);
+    let pieces: Vec<_> = re.split("").collect();
+    assert_eq!(pieces, [""]);
+}
+
+#[test]
+fn split_on_empty_matches() {
+    let re = Regex::new(r"x?").unwrap();
+    let pieces: Vec<_> = re.split("aé").collect();
+    assert_eq!(pieces, ["", "a", "é", ""]);
+    let pieces: Vec<_> = re.split("axb").collect();
+    assert_eq!(pieces, ["", "a", "b", ""]);
+}
+
+#[test]
+fn splitn() {
+    let re = Regex::new(r" ").unwrap();
+    let pieces: Vec<_> = re.splitn("a b c d", 2).collect();
+    assert_eq!(pieces, ["a", "b c d"]);
+    let pieces: Vec<_> = re.splitn("a b c d", 1).collect();
+    assert_eq!(pieces, ["a b c d"]);
+    let pieces: Vec<_> = re.splitn("a b", 5).collect();
+    assert_eq!(pieces, ["a", "b"]);
+    let pieces: Vec<_> = re.splitn("a b ", 3).collect();
+    assert_eq!(pieces, ["a", "b", ""]);
+    assert_eq!(re.splitn("a b", 0).count(), 0);
+}
@@ -0,0 +1,38 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::rc::Rc;
+
+use re1::{Backtrack, Engine, Input, Pike, Regexp, Sub};
+
+#[test]
+fn update_sets_slot_in_bounds() {
+    let sub = Rc::new(Sub::new(4));
+    let shared = sub.clone();
+    let sub = sub.update(2, 7);
+    assert_eq!(sub.get(2), 7);
+    // The shared sub is copied on write.
+    assert_eq!(shared.get(2), usize::MAX);
+}
+
+#[test]
+fn update_ignores_slot_out_of_bounds() {
+    let sub = Rc::new(Sub::new(2));
+    let updated = sub.clone().update(4, 7);
+    assert!(Rc::ptr_eq(&sub, &updated));
+    assert_eq!(*updated, Sub::new(2));
+}
+
+#[test]
+fn fewer_slots_than_groups() {
+    let prog = Regexp::Paren(0, None, Regexp::parse(r"(a)(b)").unwrap())
+        .compile()
+        .unwrap();
+    for engine in [&Pike as &dyn Engine, &Backtrack] {
+        let mut sub = Sub::new(2);
+        assert!(engine.search(&prog, &Input::new("xab"), &mut sub));
+        assert_eq!(sub.group(0), Some((1, 3)), "{}", engine.name());
+    }
+}
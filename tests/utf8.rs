@@ -0,0 +1,175 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{
+    Anchored, CompileError, Compiler, Dfa, DfaError, Engine, Input, Inst, LazyDfa, Meta, OnePass,
+    Pike, Prog, Regexp, Sub, ENGINES,
+};
+
+fn compile_utf8(pattern: &str) -> Prog {
+    let re = Regexp::Paren(0, None, Regexp::parse(pattern).unwrap());
+    Compiler::new().utf8(true).compile(&re).unwrap()
+}
+
+#[test]
+fn every_char_matches_exactly() {
+    let prog = compile_utf8(r"[\0-\u{10FFFF}]");
+    assert!(prog.is_utf8());
+    for i in (0..=0x10FFFF)
+        .step_by(97)
+        .chain([0x7F, 0x80, 0x7FF, 0x800, 0xFFFF, 0x10000, 0xD7FF, 0xE000])
+    {
+        let Some(ch) = char::from_u32(i) else {
+            continue;
+        };
+        let s = ch.to_string();
+        let input = Input::new(&s).anchored(Anchored::Yes);
+        let mut sub = Sub::new(2);
+        assert!(Pike.search(&prog, &input, &mut sub), "{i:x}");
+        assert_eq!(sub.group(0), Some((0, s.len())), "{i:x}");
+    }
+    for bad in [
+        &b"\xED\xA0\x80"[..],
+        b"\xC0\x80",
+        b"\xF4\x90\x80\x80",
+        b"\x80",
+        b"\xE2\x82",
+    ] {
+        let input = Input::new(bad).anchored(Anchored::Yes);
+        assert!(!Pike.search(&prog, &input, &mut Sub::new(2)), "{bad:?}");
+    }
+}
+
+#[test]
+fn automata_share_prefixes_and_suffixes() {
+    let byte_ranges = |pattern| {
+        let prog = compile_utf8(pattern);
+        let n = prog.insts().iter();
+        n.filter(|inst| matches!(inst, Inst::ByteRange(..))).count()
+    };
+    // C3 followed by A9-AB or B3-B5, plus the unanchored prefix.
+    assert_eq!(byte_ranges(r"[é-ëó-õ]"), 1 + 3);
+    // E0 A0-BF, E1-EC, ED 80-9F, and EE-EF, all ending in 80-BF.
+    assert_eq!(byte_ranges(r"[\u{800}-\u{FFFF}]"), 1 + 8);
+}
+
+#[test]
+fn raw_bytes_are_not_compiled_to_utf8() {
+    for pattern in [r"(?-u:\xA9)", r"a|(?-u:[\x80-\xFF])+", r"(?-u:\xA9+)"] {
+        let re = Regexp::parse(pattern).unwrap();
+        assert_eq!(
+            Compiler::new().utf8(true).compile(&re),
+            Err(CompileError::RawBytes),
+            "{pattern}"
+        );
+        // With units, the raw bytes never match within a char.
+        let units = Compiler::new().compile(&re).unwrap();
+        assert!(!Pike.search(&units, &Input::new("é"), &mut Sub::new(2)));
+        assert!(Pike.search(&units, &Input::new(b"\xA9"), &mut Sub::new(2)));
+    }
+}
+
+#[test]
+fn no_empty_matches_within_chars() {
+    let prog = compile_utf8(r"((?:(?:\B){1,2}){1,2})+");
+    let mut engines: Vec<&dyn Engine> = ENGINES.to_vec();
+    let (meta, lazy) = (Meta::new(), LazyDfa::new());
+    engines.extend([&meta as &dyn Engine, &lazy, &OnePass]);
+    for engine in engines {
+        for nsub in [0, 2, prog.nsub()] {
+            let mut sub = Sub::new(nsub);
+            assert!(
+                engine.search(&prog, &Input::new("ébb"), &mut sub),
+                "{}",
+                engine.name()
+            );
+            if nsub != 0 {
+                assert_eq!(sub.group(0), Some((2, 2)), "{} {nsub}", engine.name());
+            }
+            assert!(
+                !engine.search(&prog, &Input::new("é"), &mut Sub::new(nsub)),
+                "{}",
+                engine.name()
+            );
+        }
+    }
+}
+
+#[test]
+fn engines_agree_with_units() {
+    let pats = [
+        r"café",
+        r"[α-ω]+",
+        r"([a-zö]+)\s(\d|ö)",
+        r"\d+",
+        r"[^a-z]+",
+        r"a.c",
+        r"(?s)a.c",
+        r"[\u{80}-\u{10FFFF}]",
+        r"(?-u:\b)x(?-u:\b)",
+        r"(?m)^é$",
+        r"(?i)straße",
+        r"[€-😀]+",
+        r"(a|é)(b|€)*",
+        r"\bé\b",
+        r"\B",
+        r"(?-u:\B)",
+        r"(?m)$",
+        r"x*\b",
+        r"[é-ëó-õ]+",
+        r"[^é]",
+    ];
+    let hays: [&[u8]; 12] = [
+        "café".as_bytes(),
+        "αβγ δ".as_bytes(),
+        "hello wörld".as_bytes(),
+        b"12\xFF34",
+        "x é\nx".as_bytes(),
+        "a\u{80}c a\nc".as_bytes(),
+        "é\n€😀".as_bytes(),
+        "STRASSE straße STRAẞE".as_bytes(),
+        b"a\xE2\x82\xAC\xE2\x82b",
+        b"\xC3\xA9\xC3",
+        b"",
+        "aé€b€".as_bytes(),
+    ];
+    for pattern in pats {
+        let re = Regexp::Paren(0, None, Regexp::parse(pattern).unwrap());
+        let units = Compiler::new().compile(&re).unwrap();
+        let bytes = Compiler::new().utf8(true).compile(&re).unwrap();
+        let dfa = match Dfa::new(&bytes) {
+            Ok(dfa) => Some(Dfa::from_bytes(&dfa.to_bytes()).unwrap()),
+            Err(DfaError::UnicodeWordBoundary) => None,
+            Err(err) => panic!("{pattern}: {err}"),
+        };
+        let mut engines: Vec<&dyn Engine> = ENGINES.to_vec();
+        let (meta, lazy) = (Meta::new(), LazyDfa::new());
+        engines.extend([&meta as &dyn Engine, &lazy, &OnePass]);
+        if let Some(dfa) = &dfa {
+            engines.push(dfa);
+        }
+        for hay in hays {
+            for anchored in [Anchored::No, Anchored::Yes] {
+                for start in 0..=hay.len() {
+                    let input = Input::new(hay).span(start..hay.len()).anchored(anchored);
+                    let mut want = Sub::new(units.nsub());
+                    let matched = Pike.search(&units, &input, &mut want);
+                    for engine in &engines {
+                        let mut sub = Sub::new(bytes.nsub());
+                        assert_eq!(
+                            engine.search(&bytes, &input, &mut sub),
+                            matched,
+                            "{pattern} {hay:?} {start} {anchored:?} {}",
+                            engine.name()
+                        );
+                        if matched && engine.captures() {
+                            assert_eq!(sub, want, "{pattern} {hay:?} {start} {}", engine.name());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
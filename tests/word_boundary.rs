@@ -0,0 +1,56 @@
+// Copyright 2023 Thalia Archibald. All Rights Reserved.
+// Copyright 2007-2009 Russ Cox. All Rights Reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use re1::{Assertion, Regex, ENGINES};
+
+#[test]
+fn boundaries_at_offsets() {
+    let s = "a b_c".as_bytes();
+    let at: Vec<_> = (0..=s.len())
+        .filter(|&i| Assertion::WordBoundary(true).matches(s, i))
+        .collect();
+    assert_eq!(at, [0, 1, 2, 5]);
+    let not_at: Vec<_> = (0..=s.len())
+        .filter(|&i| Assertion::NotWordBoundary(true).matches(s, i))
+        .collect();
+    assert_eq!(not_at, [3, 4]);
+}
+
+#[test]
+fn unicode_and_ascii_boundaries() {
+    let s = "é1".as_bytes();
+    assert!(Assertion::WordBoundary(true).matches(s, 0));
+    assert!(!Assertion::WordBoundary(true).matches(s, 2));
+    assert!(!Assertion::WordBoundary(false).matches(s, 0));
+    assert!(Assertion::WordBoundary(false).matches(s, 2));
+}
+
+#[test]
+fn engines_find_whole_words() {
+    for engine in ENGINES {
+        let re = Regex::new(r"\bfoo\b").unwrap().with_engine(engine);
+        let all: Vec<_> = re
+            .find_iter("foo foobar barfoo (foo)")
+            .map(|m| m.start())
+            .collect();
+        assert_eq!(all, [0, 19], "{engine:?}");
+        let re = Regex::new(r"\Boo\B").unwrap().with_engine(engine);
+        assert_eq!(re.find("oo foo fool").unwrap().start(), 8, "{engine:?}");
+        let re = Regex::new(r"\bé").unwrap().with_engine(engine);
+        assert_eq!(re.find("aé é").unwrap().start(), 4, "{engine:?}");
+        let re = Regex::new(r"(?-u:\b)é").unwrap().with_engine(engine);
+        assert_eq!(re.find("aé é").unwrap().start(), 1, "{engine:?}");
+    }
+}
+
+#[test]
+fn empty_haystack_has_no_boundary() {
+    for engine in ENGINES {
+        let re = Regex::new(r"\b").unwrap().with_engine(engine);
+        assert!(!re.is_match(""), "{engine:?}");
+        let re = Regex::new(r"\B").unwrap().with_engine(engine);
+        assert!(re.is_match(""), "{engine:?}");
+    }
+}